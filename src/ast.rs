@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 use crate::token::TokenKind;
 use crate::value::Value;
-use crate::environment::{ValueType, EnvVariableType};
+use crate::environment::{ValueType, EnvVariableType, describe_type};
+use crate::value::JsonFractionPolicy;
+use serde::{Serialize, Serializer};
 
-#[derive(Debug, PartialEq, Clone)]
+/// エディタなどから使えるよう、各ノードは{"type": "BinaryOp", ...}のようなJSONに変換できる
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "type")]
 pub enum ASTNode {
     // 数値や文字列などのリテラル
     Literal {
+        #[serde(serialize_with = "serialize_literal")]
         value: Value,
         line: usize,
         column: usize,
@@ -14,6 +19,7 @@ pub enum ASTNode {
     // 変数
     Variable {
         name: String,
+        #[serde(serialize_with = "serialize_optional_value_type")]
         value_type: Option<ValueType>,
         line: usize,
         column: usize,
@@ -23,6 +29,25 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    // [x, x * 2]のように式を要素に持つリスト
+    List {
+        elements: Vec<ASTNode>,
+        line: usize,
+        column: usize,
+    },
+    // [0, ...xs]やf(...args)のようにリストを展開する
+    Spread {
+        expr: Box<ASTNode>,
+        line: usize,
+        column: usize,
+    },
+    // print(a, b, sep: ", ")のsep: ", "のような名前付き引数(今はprintだけが受け付ける)
+    NamedArgument {
+        name: String,
+        value: Box<ASTNode>,
+        line: usize,
+        column: usize,
+    },
     // -5, !trueなどの一つのオペランドを持つ演算子
     PrefixOp {
         op: TokenKind,
@@ -43,6 +68,7 @@ pub enum ASTNode {
         name: String,
         value: Box<ASTNode>,
         variable_type: EnvVariableType,
+        #[serde(serialize_with = "serialize_value_type")]
         value_type: ValueType,
         is_new: bool,
         line: usize,
@@ -52,6 +78,7 @@ pub enum ASTNode {
         name: String,
         arguments: Vec<ASTNode>,
         body: Box<ASTNode>,
+        #[serde(serialize_with = "serialize_value_type")]
         return_type: ValueType,
         line: usize,
         column: usize,
@@ -60,6 +87,7 @@ pub enum ASTNode {
         name: String,
         arguments: Vec<ASTNode>,
         body: Box<ASTNode>,
+        #[serde(serialize_with = "serialize_value_type")]
         return_type: ValueType,
         is_mut: bool,
         line: usize,
@@ -89,11 +117,14 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    // break outerのようにラベルを指定すると外側のループを抜ける
     Break {
+        label: Option<String>,
         line: usize,
         column: usize,
     },
     Continue {
+        label: Option<String>,
         line: usize,
         column: usize,
     },
@@ -144,6 +175,7 @@ pub enum ASTNode {
         is_statement: bool,
         then: Box<ASTNode>,
         else_: Option<Box<ASTNode>>,
+        #[serde(serialize_with = "serialize_value_type")]
         value_type: ValueType,
         line: usize,
         column: usize,
@@ -155,6 +187,7 @@ pub enum ASTNode {
         column: usize,
     },
     StructField {
+        #[serde(serialize_with = "serialize_value_type")]
         value_type: ValueType,
         is_public: bool,
         line: usize,
@@ -179,7 +212,15 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    // base with { field: value } で一部のフィールドを置き換えたコピーを作る
+    StructUpdate {
+        base: Box<ASTNode>,
+        fields: HashMap<String, ASTNode>,
+        line: usize,
+        column: usize,
+    },
     Impl {
+        #[serde(serialize_with = "serialize_value_type")]
         base_struct: Box<ValueType>,
         methods: Vec<ASTNode>,
         line: usize,
@@ -194,6 +235,23 @@ pub enum ASTNode {
         variable: String,
         iterable: Box<ASTNode>,
         body: Box<ASTNode>,
+        label: Option<String>,
+        line: usize,
+        column: usize,
+    },
+    // while cond { ... }。条件がtrueの間、本体を繰り返す
+    While {
+        condition: Box<ASTNode>,
+        body: Box<ASTNode>,
+        label: Option<String>,
+        line: usize,
+        column: usize,
+    },
+    // do { ... } while (cond)。本体を1回実行してから条件を評価する
+    DoWhile {
+        body: Box<ASTNode>,
+        condition: Box<ASTNode>,
+        label: Option<String>,
         line: usize,
         column: usize,
     },
@@ -233,6 +291,14 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    // matchの型パターン。_: numberのように値の実行時の型で分岐する
+    TypePattern {
+        name: String,
+        #[serde(serialize_with = "serialize_value_type")]
+        value_type: ValueType,
+        line: usize,
+        column: usize,
+    },
     DictKeyAccess {
         dict: Box<ASTNode>,
         key: Box<ASTNode>,
@@ -260,3 +326,23 @@ pub enum ASTNode {
         column: usize,
     },
 }
+
+/// リテラルの値はjson_stringifyと同じJSONにする(有限小数にならない分数は"1/3"のような文字列)
+fn serialize_literal<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    match value.to_json(JsonFractionPolicy::String).ok().and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok()) {
+        Some(json) => json.serialize(serializer),
+        None => serializer.serialize_str(&value.to_string()),
+    }
+}
+
+/// 型はソースコード上の型名(number、List<string>など)にする
+fn serialize_value_type<S: Serializer>(value_type: &ValueType, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&describe_type(value_type))
+}
+
+fn serialize_optional_value_type<S: Serializer>(value_type: &Option<ValueType>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value_type {
+        Some(value_type) => serialize_value_type(value_type, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -1,95 +1,1429 @@
 use crate::environment::Env;
 use crate::environment::ValueType;
-use crate::value::Value;
+use crate::value::{Value, JsonFractionPolicy};
+use crate::evals::lambda_node::call_lambda;
+use crate::evals::method_call_node::{compare_values, sort_values};
+use crate::evals::request_exit;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use fraction::Fraction;
+use indexmap::IndexMap;
+use fraction::{Fraction, ToPrimitive};
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn register_builtins(env: &mut Env) -> HashMap<(String, String), ValueType> {
     let mut builtins = HashMap::new();
-    env.register_builtin("print".to_string(), |args: Vec<Value>| {
-        for arg in args {
-            print!("{} ", arg);
-        }
-        println!();
-        Value::Void
+    env.register_builtin("print".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        print_values(&args, DEFAULT_PRINT_SEPARATOR, DEFAULT_PRINT_TERMINATOR);
+        Ok(Value::Void)
     });
     builtins.insert(("global".into(), "print".to_string()), ValueType::Void);
 
-    env.register_builtin("len".to_string(), |args: Vec<Value>| {
-        if args.len() != 1 {
-            panic!("len function takes exactly one argument");
-        }
-        match &args[0] {
-            Value::List(l) => Value::Number(l.len().into()),
-            Value::String(s) => Value::Number(s.len().into()),
-            _ => panic!("len function takes a list as an argument"),
-        }
+    env.register_builtin("read_lines".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let path = read_lines_path(&args)?;
+        Ok(Value::Result(match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(Box::new(split_lines(&contents))),
+            Err(e) => Err(Box::new(Value::String(format!("read_lines could not read {:?}: {}", path, e)))),
+        }))
     });
-    builtins.insert(("global".into(), "len".to_string()), ValueType::Number);
+    builtins.insert(("global".into(), "read_lines".to_string()), read_lines_type());
 
-    env.register_builtin("range".to_string(), |args: Vec<Value>| {
-        if let [Value::Number(start), Value::Number(end)] = args.as_slice() {
-            Value::List(((*start.numer().unwrap() as i64)..(*end.numer().unwrap() as i64)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else if let [Value::Number(end)] = args.as_slice() {
-            Value::List((0..(*end.numer().unwrap() as i64)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else if let [Value::Number(start), Value::Number(end), Value::Number(step)] = args.as_slice() {
-            Value::List(((*start.numer().unwrap() as i64..*end.numer().unwrap() as i64).step_by(*step.numer().unwrap() as usize)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else {
-            panic!("range function takes 1, 2 or 3 arguments")
-        }
+    env.register_builtin("env".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let name = env_var_name(&args)?;
+        Ok(Value::Option(std::env::var(name).ok().map(|value| Box::new(Value::String(value)))))
     });
-    builtins.insert(("global".into(), "range".to_string()), ValueType::Number);
+    builtins.insert(("global".into(), "env".to_string()), env_var_type());
+
+    register_common_builtins(env, &mut builtins);
     builtins
 }
 
+/// 引数をsepで区切り、最後にendを付けて標準出力に書き出す
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn print_values(args: &[Value], sep: &str, end: &str) {
+    use std::io::Write;
+    print!("{}{}", format_print_args(args, sep), end);
+    // endが改行でなくても、続く出力より前に表示されるようにする
+    let _ = std::io::stdout().flush();
+}
+
 #[cfg(target_arch = "wasm32")]
 pub fn register_builtins(env: &mut Env) -> HashMap<(String, String), ValueType> {
     use crate::wasm::CONSOLE_OUTPUT;
 
     let mut builtins = HashMap::new();
-    env.register_builtin("print".to_string(), |args: Vec<Value>| {
-        let output = args
-            .iter()
-            .map(|arg| format!("{}", arg))
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        CONSOLE_OUTPUT.with(|console| {
-            let mut console = console.borrow_mut();
-            if !console.is_empty() {
-                console.push('\n');
-            }
-            console.push_str(&output);
-        });
-
-        Value::Void
+    env.register_builtin("print".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        print_values(&args, DEFAULT_PRINT_SEPARATOR, DEFAULT_PRINT_TERMINATOR);
+        Ok(Value::Void)
     });
     builtins.insert(("global".into(), "print".to_string()), ValueType::Void);
 
-    env.register_builtin("len".to_string(), |args: Vec<Value>| {
+    // ブラウザではファイルを読めないので、常に失敗のResultを返す
+    env.register_builtin("read_lines".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        read_lines_path(&args)?;
+        Ok(Value::Result(Err(Box::new(Value::String("read_lines is not available in WebAssembly".to_string())))))
+    });
+    builtins.insert(("global".into(), "read_lines".to_string()), read_lines_type());
+
+    // ブラウザには環境変数がないので、常にNoneを返す
+    env.register_builtin("env".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        env_var_name(&args)?;
+        Ok(Value::Option(None))
+    });
+    builtins.insert(("global".into(), "env".to_string()), env_var_type());
+
+    register_common_builtins(env, &mut builtins);
+    builtins
+}
+
+/// 引数をsepで区切り、最後にendを付けてコンソール出力に書き足す(末尾の改行はevaluateで取り除く)
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn print_values(args: &[Value], sep: &str, end: &str) {
+    use crate::wasm::CONSOLE_OUTPUT;
+
+    let output = format_print_args(args, sep);
+    CONSOLE_OUTPUT.with(|console| {
+        let mut console = console.borrow_mut();
+        console.push_str(&output);
+        console.push_str(end);
+    });
+}
+
+// print(a, b, sep: ", ", end: "")で変えられる区切り文字と終端
+pub(crate) const DEFAULT_PRINT_SEPARATOR: &str = " ";
+pub(crate) const DEFAULT_PRINT_TERMINATOR: &str = "\n";
+
+/// printの引数をsep区切りの1行にする(引数がなければ空行になる)
+fn format_print_args(args: &[Value], sep: &str) -> String {
+    args.iter()
+        .map(|arg| format!("{}", arg))
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn read_lines_path(args: &[Value]) -> Result<&str, String> {
+    match args {
+        [Value::String(path)] => Ok(path),
+        [path] => Err(format!("read_lines path must be a string: {}", path)),
+        _ => Err("read_lines function takes exactly one argument".to_string()),
+    }
+}
+
+fn read_lines_type() -> ValueType {
+    ValueType::ResultType {
+        success: Box::new(ValueType::List(Box::new(ValueType::String))),
+        failure: Box::new(ValueType::String),
+    }
+}
+
+fn env_var_name(args: &[Value]) -> Result<&str, String> {
+    match args {
+        [Value::String(name)] => Ok(name),
+        [name] => Err(format!("env name must be a string: {}", name)),
+        _ => Err("env function takes exactly one argument".to_string()),
+    }
+}
+
+fn env_var_type() -> ValueType {
+    ValueType::OptionType(Box::new(ValueType::String))
+}
+
+/// 改行(\nと\r\n)で分割した文字列のリストにする。末尾の改行による空の要素は含めない
+#[cfg(not(target_arch = "wasm32"))]
+fn split_lines(contents: &str) -> Value {
+    Value::List(contents.lines().map(|line| Value::String(line.to_string())).collect())
+}
+
+thread_local! {
+    // `sag run file.sag -- a b`の`--`より後ろの引数(args()で取り出す)
+    static SCRIPT_ARGS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+// sag runだけが設定するので、ライブラリとしてビルドしたときは使われない
+#[allow(dead_code)]
+pub fn set_script_args(args: Vec<String>) {
+    SCRIPT_ARGS.with(|script_args| *script_args.borrow_mut() = args);
+}
+
+/// 実行環境に依存しない組み込み関数を登録する
+fn register_common_builtins(env: &mut Env, builtins: &mut HashMap<(String, String), ValueType>) {
+    env.register_builtin("len".to_string(), |args: Vec<Value>, _env: &mut Env| {
         if args.len() != 1 {
-            panic!("len function takes exactly one argument");
+            return Err("len function takes exactly one argument".to_string());
         }
         match &args[0] {
-            Value::List(l) => Value::Number(l.len().into()),
-            Value::String(s) => Value::Number(s.len().into()),
-            _ => panic!("len function takes a list as an argument"),
+            Value::List(l) => Ok(Value::Number(l.len().into())),
+            Value::String(s) => Ok(Value::Number(s.len().into())),
+            _ => Err("len function takes a list as an argument".to_string()),
         }
     });
     builtins.insert(("global".into(), "len".to_string()), ValueType::Number);
 
-    env.register_builtin("range".to_string(), |args: Vec<Value>| {
-        if let [Value::Number(start), Value::Number(end)] = args.as_slice() {
-            Value::List(((*start.numer().unwrap() as i64)..(*end.numer().unwrap() as i64)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else if let [Value::Number(end)] = args.as_slice() {
-            Value::List((0..(*end.numer().unwrap() as i64)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else if let [Value::Number(start), Value::Number(end), Value::Number(step)] = args.as_slice() {
-            Value::List(((*start.numer().unwrap() as i64..*end.numer().unwrap() as i64).step_by(*step.numer().unwrap() as usize)).map(|x| Value::Number(Fraction::from(x))).collect())
-        } else {
-            panic!("range function takes 1, 2 or 3 arguments")
+    env.register_builtin("range".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Number(end)] => range_list(&Fraction::from(0), end, &Fraction::from(1)),
+            [Value::Number(start), Value::Number(end)] => range_list(start, end, &Fraction::from(1)),
+            [Value::Number(start), Value::Number(end), Value::Number(step)] => range_list(start, end, step),
+            _ => Err("range function takes 1, 2 or 3 arguments".to_string()),
         }
     });
     builtins.insert(("global".into(), "range".to_string()), ValueType::Number);
-    builtins
+
+    env.register_builtin("map".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => map_list(list, f, env),
+            [_, _] => Err("map function takes a list as the first argument".to_string()),
+            _ => Err("map function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "map".to_string()), ValueType::List(Box::new(ValueType::Any)));
+
+    env.register_builtin("filter".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => filter_list(list, f, env),
+            [_, _] => Err("filter function takes a list as the first argument".to_string()),
+            _ => Err("filter function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "filter".to_string()), ValueType::List(Box::new(ValueType::Any)));
+
+    env.register_builtin("count".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => count_list(list, f, env),
+            [_, _] => Err("count function takes a list as the first argument".to_string()),
+            _ => Err("count function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "count".to_string()), ValueType::Number);
+
+    env.register_builtin("partition".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => partition_list(list, f, env),
+            [_, _] => Err("partition function takes a list as the first argument".to_string()),
+            _ => Err("partition function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "partition".to_string()), ValueType::List(Box::new(ValueType::List(Box::new(ValueType::Any)))));
+
+    env.register_builtin("find".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => {
+                let index = find_index_in_list(list, f, env)?;
+                Ok(Value::Option(index.map(|i| Box::new(list[i].clone()))))
+            }
+            [_, _] => Err("find function takes a list as the first argument".to_string()),
+            _ => Err("find function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "find".to_string()), ValueType::OptionType(Box::new(ValueType::Any)));
+
+    env.register_builtin("find_index".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => {
+                let index = find_index_in_list(list, f, env)?;
+                Ok(Value::Option(index.map(|i| Box::new(Value::Number(Fraction::from(i))))))
+            }
+            [_, _] => Err("find_index function takes a list as the first argument".to_string()),
+            _ => Err("find_index function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "find_index".to_string()), ValueType::OptionType(Box::new(ValueType::Number)));
+
+    env.register_builtin("group_by".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list), f] => group_by_list(list, f, env),
+            [_, _] => Err("group_by function takes a list as the first argument".to_string()),
+            _ => Err("group_by function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "group_by".to_string()), ValueType::Dict(Box::new(ValueType::List(Box::new(ValueType::Any)))));
+
+    env.register_builtin("zip_with".to_string(), |args: Vec<Value>, env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(a), Value::List(b), f] => {
+                // 短い方のリストの長さに揃える
+                let mut result = vec![];
+                for (x, y) in a.iter().zip(b.iter()) {
+                    result.push(call_lambda(f, vec![x.clone(), y.clone()], env)?);
+                }
+                Ok(Value::List(result))
+            }
+            [_, _, _] => Err("zip_with function takes lists as the first and second arguments".to_string()),
+            _ => Err("zip_with function takes exactly three arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "zip_with".to_string()), ValueType::List(Box::new(ValueType::Any)));
+
+    // 任意のメッセージでランタイムエラーを発生させて評価を止める
+    env.register_builtin("error".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [message] => Err(format!("{}", message)),
+            _ => Err("error function takes exactly one argument".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "error".to_string()), ValueType::Any);
+
+    env.register_builtin("clamp".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Number(x), Value::Number(lo), Value::Number(hi)] => {
+                if lo > hi {
+                    return Err(format!("clamp lower bound {} is greater than upper bound {}", lo, hi));
+                }
+                Ok(Value::Number((*x).max(*lo).min(*hi)))
+            }
+            [_, _, _] => Err("clamp function takes numbers as arguments".to_string()),
+            _ => Err("clamp function takes exactly three arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "clamp".to_string()), ValueType::Number);
+
+    env.register_builtin("sign".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Number(x)] => {
+                let zero = Fraction::from(0);
+                let sign = if *x > zero { 1 } else if *x < zero { -1 } else { 0 };
+                Ok(Value::Number(Fraction::from(sign)))
+            }
+            [_] => Err("sign function takes a number as an argument".to_string()),
+            _ => Err("sign function takes exactly one argument".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "sign".to_string()), ValueType::Number);
+
+    env.register_builtin("abs_diff".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Number(a), Value::Number(b)] => {
+                let diff = if a > b { a - b } else { b - a };
+                Ok(Value::Number(diff))
+            }
+            [_, _] => Err("abs_diff function takes numbers as arguments".to_string()),
+            _ => Err("abs_diff function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "abs_diff".to_string()), ValueType::Number);
+
+    // 三角関数・対数関数は分数で正確に表せないためf64で計算した近似値を返す
+    env.register_builtin("sin".to_string(), |args: Vec<Value>, _env: &mut Env| apply_f64("sin", &args, f64::sin));
+    builtins.insert(("global".into(), "sin".to_string()), ValueType::Number);
+
+    env.register_builtin("cos".to_string(), |args: Vec<Value>, _env: &mut Env| apply_f64("cos", &args, f64::cos));
+    builtins.insert(("global".into(), "cos".to_string()), ValueType::Number);
+
+    env.register_builtin("tan".to_string(), |args: Vec<Value>, _env: &mut Env| apply_f64("tan", &args, f64::tan));
+    builtins.insert(("global".into(), "tan".to_string()), ValueType::Number);
+
+    env.register_builtin("exp".to_string(), |args: Vec<Value>, _env: &mut Env| apply_f64("exp", &args, f64::exp));
+    builtins.insert(("global".into(), "exp".to_string()), ValueType::Number);
+
+    env.register_builtin("ln".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        if let [Value::Number(x)] = args.as_slice()
+            && *x <= Fraction::from(0)
+        {
+            return Err(format!("ln is only defined for positive numbers: {}", x));
+        }
+        apply_f64("ln", &args, f64::ln)
+    });
+    builtins.insert(("global".into(), "ln".to_string()), ValueType::Number);
+
+    env.register_builtin("log10".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        if let [Value::Number(x)] = args.as_slice()
+            && *x <= Fraction::from(0)
+        {
+            return Err(format!("log10 is only defined for positive numbers: {}", x));
+        }
+        apply_f64("log10", &args, f64::log10)
+    });
+    builtins.insert(("global".into(), "log10".to_string()), ValueType::Number);
+
+    env.register_builtin("gcd".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let (a, b) = integer_pair("gcd", &args)?;
+        Ok(Value::Number(Fraction::from(gcd(a, b))))
+    });
+    builtins.insert(("global".into(), "gcd".to_string()), ValueType::Number);
+
+    env.register_builtin("lcm".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let (a, b) = integer_pair("lcm", &args)?;
+        if a == 0 || b == 0 {
+            return Ok(Value::Number(Fraction::from(0)));
+        }
+        let lcm = (a / gcd(a, b)).checked_mul(b).ok_or("lcm result is too large".to_string())?;
+        Ok(Value::Number(Fraction::from(lcm)))
+    });
+    builtins.insert(("global".into(), "lcm".to_string()), ValueType::Number);
+
+    // 数値の分子・分母はu64なので、20!を超える結果はエラーにする
+    env.register_builtin("factorial".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let n = match args.as_slice() {
+            [Value::Number(n)] => match (n.numer(), n.denom()) {
+                (Some(_), Some(1)) if n.is_sign_negative() && *n != Fraction::from(0) => {
+                    return Err(format!("factorial is only defined for non-negative integers: {}", n));
+                }
+                (Some(numer), Some(1)) => *numer,
+                _ => return Err(format!("factorial is only defined for non-negative integers: {}", n)),
+            },
+            [_] => return Err("factorial function takes a number as an argument".to_string()),
+            _ => return Err("factorial function takes exactly one argument".to_string()),
+        };
+        let mut result: u64 = 1;
+        for i in 2..=n {
+            result = result.checked_mul(i).ok_or(format!("factorial({}) is too large to represent as a number", n))?;
+        }
+        Ok(Value::Number(Fraction::from(result)))
+    });
+    builtins.insert(("global".into(), "factorial".to_string()), ValueType::Number);
+
+    env.register_builtin("args".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        if !args.is_empty() {
+            return Err("args function takes no arguments".to_string());
+        }
+        Ok(Value::List(SCRIPT_ARGS.with(|script_args| {
+            script_args.borrow().iter().map(|arg| Value::String(arg.clone())).collect()
+        })))
+    });
+    builtins.insert(("global".into(), "args".to_string()), ValueType::List(Box::new(ValueType::String)));
+
+    env.register_builtin("json_stringify".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        // 有限小数で表せない分数は、第2引数で"string"(既定は"decimal")を指定すると"1/3"のような文字列で書き出す
+        let (value, fraction_policy) = match args.as_slice() {
+            [value] => (value, JsonFractionPolicy::Decimal),
+            [value, Value::String(name)] => (value, JsonFractionPolicy::from_name(name)?),
+            [_, policy] => return Err(format!("json_stringify fraction policy must be a string: {}", policy)),
+            _ => return Err("json_stringify function takes one or two arguments".to_string()),
+        };
+        Ok(Value::Result(match value.to_json(fraction_policy) {
+            Ok(json) => Ok(Box::new(Value::String(json))),
+            Err(message) => Err(Box::new(Value::String(message))),
+        }))
+    });
+    builtins.insert(("global".into(), "json_stringify".to_string()), ValueType::ResultType {
+        success: Box::new(ValueType::String),
+        failure: Box::new(ValueType::String),
+    });
+
+    // sinやlnなどの近似値を比較するため、差がeps以下なら等しいとみなす
+    env.register_builtin("approx_eq".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Number(a), Value::Number(b), Value::Number(eps)] => {
+                if *eps < Fraction::from(0) {
+                    return Err(format!("approx_eq tolerance must not be negative: {}", eps));
+                }
+                let diff = if a > b { a - b } else { b - a };
+                Ok(Value::Bool(diff <= *eps))
+            }
+            [_, _, _] => Err("approx_eq function takes numbers as arguments".to_string()),
+            _ => Err("approx_eq function takes exactly three arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "approx_eq".to_string()), ValueType::Bool);
+
+    // 固定幅の整数を模擬する加算。結果が整数でないか上限を超えるとFailを返す
+    env.register_builtin("checked_add".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let (a, b, bound) = match args.as_slice() {
+            [Value::Number(a), Value::Number(b)] => (a, b, Fraction::from(i64::MAX)),
+            [Value::Number(a), Value::Number(b), Value::Number(bound)] => (a, b, *bound),
+            [_, _] | [_, _, _] => return Err("checked_add function takes numbers as arguments".to_string()),
+            _ => return Err("checked_add function takes two or three arguments".to_string()),
+        };
+        if bound < Fraction::from(0) {
+            return Err(format!("checked_add bound must not be negative: {}", bound));
+        }
+        let failure = |message: String| Ok(Value::Result(Err(Box::new(Value::String(message)))));
+        // 分数の内部表現(u64)で溢れる場合も上限を超えたものとしてFailにする
+        let sum = match fraction::CheckedAdd::checked_add(a, b) {
+            Some(sum) => sum,
+            None => return failure(format!("checked_add result of {} + {} overflows", a, b)),
+        };
+        if sum.denom() != Some(&1) {
+            return failure(format!("checked_add result is not an integer: {}", sum));
+        }
+        if sum > bound || sum < -bound {
+            return failure(format!("checked_add result {} exceeds the bound {}", sum, bound));
+        }
+        Ok(Value::Result(Ok(Box::new(Value::Number(sum)))))
+    });
+    builtins.insert(("global".into(), "checked_add".to_string()), ValueType::ResultType {
+        success: Box::new(ValueType::Number),
+        failure: Box::new(ValueType::String),
+    });
+
+    // 関数の事前条件を確認する。条件がfalseならメッセージ付きのエラーで評価を中断する
+    env.register_builtin("require".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::Bool(true)] | [Value::Bool(true), Value::String(_)] => Ok(Value::Void),
+            [Value::Bool(false)] => Err("requirement failed".to_string()),
+            [Value::Bool(false), Value::String(message)] => Err(format!("requirement failed: {}", message)),
+            [_] | [_, _] => Err("require function takes a bool and an optional string message".to_string()),
+            _ => Err("require function takes one or two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "require".to_string()), ValueType::Void);
+
+    // 数値同士・文字列同士の大小を-1/0/1で返す。比較できない組み合わせはエラー
+    env.register_builtin("compare".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [a, b] => Ok(Value::Number(Fraction::from(compare_values(a, b)? as i8))),
+            _ => Err("compare function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "compare".to_string()), ValueType::Number);
+
+    // リスト・辞書・構造体も含めて中身が等しいかを返す
+    env.register_builtin("equals".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [a, b] => Ok(Value::Bool(a == b)),
+            _ => Err("equals function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "equals".to_string()), ValueType::Bool);
+
+    // 同じ実体かどうかを返す。値はすべて代入や引数渡しでコピーされ参照型がないので、今はequalsと同じ結果になる
+    env.register_builtin("same".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [a, b] => Ok(Value::Bool(a == b)),
+            _ => Err("same function takes exactly two arguments".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "same".to_string()), ValueType::Bool);
+
+    // 数値だけ、または文字列だけのリストを昇順に並べ替えた新しいリストを返す
+    env.register_builtin("sort".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        match args.as_slice() {
+            [Value::List(list)] => sort_values(list.clone()).map(Value::List),
+            [_] => Err("sort function takes a list as an argument".to_string()),
+            _ => Err("sort function takes exactly one argument".to_string()),
+        }
+    });
+    builtins.insert(("global".into(), "sort".to_string()), ValueType::List(Box::new(ValueType::Any)));
+
+    // 評価を終了する。CLIはこのコードで終了し、REPLはセッションを終える
+    env.register_builtin("exit".to_string(), |args: Vec<Value>, _env: &mut Env| {
+        let code = match args.as_slice() {
+            [] => 0,
+            [Value::Number(code)] => match (code.denom(), code.to_i32()) {
+                (Some(1), Some(code)) => code,
+                _ => return Err(format!("exit code must be an integer: {}", code)),
+            },
+            [_] => return Err("exit function takes a number as an argument".to_string()),
+            _ => return Err("exit function takes zero or one argument".to_string()),
+        };
+        request_exit(code);
+        Err(format!("exit({})", code))
+    });
+    builtins.insert(("global".into(), "exit".to_string()), ValueType::Void);
+}
+
+/// 2つの整数引数の絶対値を取り出す(gcd/lcmは符号に依存しない)
+fn integer_pair(name: &str, args: &[Value]) -> Result<(u64, u64), String> {
+    match args {
+        [Value::Number(a), Value::Number(b)] => {
+            let to_integer = |n: &Fraction| match (n.numer(), n.denom()) {
+                (Some(numer), Some(1)) => Ok(*numer),
+                _ => Err(format!("{} function takes integers as arguments: {}", name, n)),
+            };
+            Ok((to_integer(a)?, to_integer(b)?))
+        }
+        [_, _] => Err(format!("{} function takes integers as arguments", name)),
+        _ => Err(format!("{} function takes exactly two arguments", name)),
+    }
+}
+
+/// startからendの手前までstep刻みの整数のリストを作る
+/// stepが負のときはendより大きい間だけ数え下げ、0のときは無限ループになるのでエラーにする
+fn range_list(start: &Fraction, end: &Fraction, step: &Fraction) -> Result<Value, String> {
+    let to_integer = |n: &Fraction| match (n.numer(), n.denom()) {
+        (Some(numer), Some(1)) if n.is_sign_negative() => Ok(-(*numer as i64)),
+        (Some(numer), Some(1)) => Ok(*numer as i64),
+        _ => Err(format!("range function takes integers as arguments: {}", n)),
+    };
+    let (start, end, step) = (to_integer(start)?, to_integer(end)?, to_integer(step)?);
+    if step == 0 {
+        return Err("range step must not be zero".to_string());
+    }
+    let mut values = vec![];
+    let mut i = start;
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        values.push(Value::Number(Fraction::from(i)));
+        i += step;
+    }
+    Ok(Value::List(values))
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// ラムダが返す文字列をキーにして要素をまとめる(グループは最初に現れた順、各グループ内の順序は元のリストの順序)
+fn group_by_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Value, String> {
+    let mut groups: IndexMap<String, Value> = IndexMap::new();
+    for value in list {
+        let key = match call_lambda(f, vec![value.clone()], env)? {
+            Value::String(key) => key,
+            other => return Err(format!("group_by key must be a string: {}", other)),
+        };
+        if let Value::List(group) = groups.entry(key).or_insert_with(|| Value::List(vec![])) {
+            group.push(value.clone());
+        }
+    }
+    Ok(Value::Dict(groups))
+}
+
+/// ラムダが最初にtrueを返す要素の位置を探す
+fn find_index_in_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Option<usize>, String> {
+    for (i, value) in list.iter().enumerate() {
+        match call_lambda(f, vec![value.clone()], env)? {
+            Value::Bool(true) => return Ok(Some(i)),
+            Value::Bool(false) => {}
+            other => return Err(format!("find predicate must return a bool: {}", other)),
+        }
+    }
+    Ok(None)
+}
+
+/// 1つの数値引数をf64に変換して計算し、結果を分数に戻す(精度はf64に落ちる)
+fn apply_f64(name: &str, args: &[Value], f: fn(f64) -> f64) -> Result<Value, String> {
+    let x = match args {
+        [Value::Number(x)] => x,
+        [_] => return Err(format!("{} function takes a number as an argument", name)),
+        _ => return Err(format!("{} function takes exactly one argument", name)),
+    };
+    let x = x.to_f64().ok_or_else(|| format!("{} argument cannot be converted to a float: {}", name, x))?;
+    let result = f(x);
+    if !result.is_finite() {
+        return Err(format!("{} result is not a finite number", name));
+    }
+    Ok(Value::Number(Fraction::from(result)))
+}
+
+/// リストの各要素にラムダを適用する(mapメソッドと共通)
+pub fn map_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Value, String> {
+    let mut result = vec![];
+    for value in list {
+        result.push(call_lambda(f, vec![value.clone()], env)?);
+    }
+    Ok(Value::List(result))
+}
+
+/// ラムダがtrueを返す要素だけを残す(filterメソッドと共通)
+pub fn filter_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Value, String> {
+    let mut result = vec![];
+    for value in list {
+        match call_lambda(f, vec![value.clone()], env)? {
+            Value::Bool(true) => result.push(value.clone()),
+            Value::Bool(false) => {}
+            other => return Err(format!("filter predicate must return a bool: {}", other)),
+        }
+    }
+    Ok(Value::List(result))
+}
+
+/// ラムダがtrueを返す要素の数を数える(countメソッドと共通)
+pub fn count_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Value, String> {
+    let mut count = 0;
+    for value in list {
+        match call_lambda(f, vec![value.clone()], env)? {
+            Value::Bool(true) => count += 1,
+            Value::Bool(false) => {}
+            other => return Err(format!("count predicate must return a bool: {}", other)),
+        }
+    }
+    Ok(Value::Number(Fraction::from(count)))
+}
+
+/// ラムダがtrueを返す要素とfalseを返す要素を、元の順序のまま[trueのリスト, falseのリスト]に分ける(partitionメソッドと共通)
+pub fn partition_list(list: &[Value], f: &Value, env: &mut Env) -> Result<Value, String> {
+    let mut matched = vec![];
+    let mut unmatched = vec![];
+    for value in list {
+        match call_lambda(f, vec![value.clone()], env)? {
+            Value::Bool(true) => matched.push(value.clone()),
+            Value::Bool(false) => unmatched.push(value.clone()),
+            other => return Err(format!("partition predicate must return a bool: {}", other)),
+        }
+    }
+    Ok(Value::List(vec![Value::List(matched), Value::List(unmatched)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+    use crate::parsers::Parser;
+    use crate::evals::evals;
+    use crate::token::TokenKind;
+
+    fn numbers(values: &[i64]) -> Value {
+        Value::List(values.iter().map(|v| Value::Number(Fraction::from(*v))).collect())
+    }
+
+    #[test]
+    fn test_format_print_args() {
+        assert_eq!(format_print_args(&[], " "), "");
+        assert_eq!(format_print_args(&[Value::String("a".into()), Value::String("b".into()), Value::String("c".into())], " "), "a b c");
+        assert_eq!(
+            format_print_args(&[Value::String("n =".into()), Value::Number(Fraction::from(1)), Value::Bool(true), numbers(&[1, 2])], " "),
+            "n = 1 true [1, 2]"
+        );
+        assert_eq!(format_print_args(&[Value::String("a".into()), numbers(&[1, 2])], " | "), "a | [1, 2]");
+        assert_eq!(format_print_args(&[Value::String("a".into()), Value::String("b".into())], ""), "ab");
+    }
+
+    #[test]
+    fn test_print_named_argument_errors() {
+        let run = |input: &str| {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines().unwrap();
+            let error = evals(ast, &mut env).unwrap_err();
+            (error.message, error.line, error.column)
+        };
+        assert_eq!(run("print(1, sep: 2)"), ("print sep must be a string: 2".to_string(), 1, 10));
+        assert_eq!(run("print(1, start: \"\")"), ("print does not accept the named argument \"start\"".to_string(), 1, 10));
+        assert_eq!(run("len([1], end: \"\")"), ("named argument \"end\" is only supported by print".to_string(), 1, 10));
+    }
+
+    #[test]
+    fn test_print_accepts_any_number_of_arguments() {
+        let mut env = Env::new();
+        let input = r#"
+        print()
+        print(1, "two", [3])
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result, vec![Value::Void, Value::Void]);
+    }
+
+    #[test]
+    fn test_map_filter_pipe() {
+        let mut env = Env::new();
+        let input = r#"
+        val xs = [1, 2, 3, 4, 5, 6]
+        val is_even = \|x: number| => x % 2 == 0
+        val double = \|x: number| => x * 2
+        xs -> filter(is_even) -> map(double)
+        xs.filter(is_even).map(double)
+        map(filter(xs, is_even), double)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[3], numbers(&[4, 8, 12]));
+        assert_eq!(result[3], result[4]);
+        assert_eq!(result[3], result[5]);
+    }
+
+    #[test]
+    fn test_map_with_function_reference() {
+        let mut env = Env::new();
+        let input = r#"
+        fun triple(x: number): number {
+            return x * 3
+        }
+        map([1, 2, 3], triple)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], numbers(&[3, 6, 9]));
+    }
+
+    #[test]
+    fn test_map_non_list_error() {
+        let mut env = Env::new();
+        let input = r#"
+        val double = \|x: number| => x * 2
+        map(1, double)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().message, "map function takes a list as the first argument");
+    }
+
+    #[test]
+    fn test_count() {
+        let mut env = Env::new();
+        let input = r#"
+        val xs = [3, 0, 5, 0, 1]
+        val empty: List<number> = []
+        xs.count(\|x| => x > 0)
+        count(xs, \|x| => x > 0)
+        count(empty, \|x| => x > 0)
+        empty.count(\|x| => x > 0)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2], Value::Number(Fraction::from(3)));
+        assert_eq!(result[3], Value::Number(Fraction::from(3)));
+        assert_eq!(result[4], Value::Number(Fraction::from(0)));
+        assert_eq!(result[5], Value::Number(Fraction::from(0)));
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut env = Env::new();
+        let input = r#"
+        val xs = [1, 2, 3, 4, 5, 6, 7]
+        val empty: List<number> = []
+        partition(xs, \|x| => x % 2 == 0)
+        xs.partition(\|x| => x % 2 == 1)
+        empty.partition(\|x| => x > 0)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2], Value::List(vec![numbers(&[2, 4, 6]), numbers(&[1, 3, 5, 7])]));
+        assert_eq!(result[3], Value::List(vec![numbers(&[1, 3, 5, 7]), numbers(&[2, 4, 6])]));
+        assert_eq!(result[4], Value::List(vec![numbers(&[]), numbers(&[])]));
+    }
+
+    #[test]
+    fn test_partition_predicate_must_return_bool() {
+        let mut env = Env::new();
+        let input = r#"
+        partition([1, 2], \|x| => x)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "partition predicate must return a bool: 1");
+    }
+
+    #[test]
+    fn test_error_builtin() {
+        let mut env = Env::new();
+        let input = r#"
+        val x = 1
+        error("x")
+        print("unreachable")
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let error_token = tokens.iter().find(|token| token.kind == TokenKind::Identifier("error".into())).unwrap().clone();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error.message.contains("x"));
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, error_token.column);
+    }
+
+    #[test]
+    fn test_error_builtin_inside_function() {
+        let mut env = Env::new();
+        let input = r#"
+        fun check(n: number): number {
+            if (n < 0) {
+                error("negative")
+            }
+            return n
+        }
+        check(1)
+        check(0 - 2)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "negative");
+    }
+
+    #[test]
+    fn test_clamp() {
+        let mut env = Env::new();
+        let input = r#"
+        clamp(0 - 5, 0, 10)
+        clamp(1/2, 0, 10)
+        clamp(15, 0, 10)
+        clamp(10, 0, 10)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Number(Fraction::from(0)));
+        assert_eq!(result[1], Value::Number(Fraction::new(1u64, 2u64)));
+        assert_eq!(result[2], Value::Number(Fraction::from(10)));
+        assert_eq!(result[3], Value::Number(Fraction::from(10)));
+    }
+
+    #[test]
+    fn test_clamp_invalid_range_error() {
+        let mut env = Env::new();
+        let input = r#"
+        clamp(5, 10, 0)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "clamp lower bound 10 is greater than upper bound 0");
+    }
+
+    #[test]
+    fn test_sign_and_abs_diff() {
+        let mut env = Env::new();
+        let input = r#"
+        sign(0 - 3)
+        sign(0)
+        sign(1/3)
+        abs_diff(3, 10)
+        abs_diff(10, 3)
+        abs_diff(0 - 1, 1/2)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Number(Fraction::from(-1)));
+        assert_eq!(result[1], Value::Number(Fraction::from(0)));
+        assert_eq!(result[2], Value::Number(Fraction::from(1)));
+        assert_eq!(result[3], Value::Number(Fraction::from(7)));
+        assert_eq!(result[4], Value::Number(Fraction::from(7)));
+        assert_eq!(result[5], Value::Number(Fraction::new(3u64, 2u64)));
+    }
+
+    fn assert_approx(value: &Value, expected: f64) {
+        match value {
+            Value::Number(n) => assert!((n.to_f64().unwrap() - expected).abs() < 1e-9, "{} != {}", n, expected),
+            _ => panic!("expected number: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_trigonometric_and_logarithmic() {
+        let mut env = Env::new();
+        let input = r#"
+        sin(0)
+        cos(0)
+        exp(0)
+        ln(1)
+        log10(1000)
+        sin(1)
+        tan(1/2)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Number(Fraction::from(0)));
+        assert_eq!(result[1], Value::Number(Fraction::from(1)));
+        assert_eq!(result[2], Value::Number(Fraction::from(1)));
+        assert_eq!(result[3], Value::Number(Fraction::from(0)));
+        assert_approx(&result[4], 3.0);
+        assert_approx(&result[5], 1f64.sin());
+        assert_approx(&result[6], 0.5f64.tan());
+    }
+
+    #[test]
+    fn test_ln_non_positive_error() {
+        let mut env = Env::new();
+        let input = r#"
+        ln(0)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "ln is only defined for positive numbers: 0");
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        let mut env = Env::new();
+        let input = r#"
+        gcd(12, 18)
+        gcd(0 - 12, 18)
+        gcd(0, 5)
+        gcd(0, 0)
+        lcm(4, 6)
+        lcm(0 - 4, 6)
+        lcm(0, 6)
+        lcm(0, 0)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let expected = [6, 6, 5, 0, 12, 12, 0, 0];
+        for (value, expected) in result.iter().zip(expected) {
+            assert_eq!(*value, Value::Number(Fraction::from(expected)));
+        }
+    }
+
+    #[test]
+    fn test_factorial() {
+        let mut env = Env::new();
+        let input = r#"
+        factorial(0)
+        factorial(10)
+        factorial(10) == 3628800
+        factorial(20)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Number(Fraction::from(1)));
+        assert_eq!(result[1], Value::Number(Fraction::from(3628800)));
+        assert_eq!(result[2], Value::Bool(true));
+        assert_eq!(result[3], Value::Number(Fraction::from(2432902008176640000u64)));
+    }
+
+    #[test]
+    fn test_factorial_errors() {
+        let run = |input: &str| {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines().unwrap();
+            evals(ast, &mut env).unwrap_err().message
+        };
+        assert_eq!(run("factorial(0 - 3)"), "factorial is only defined for non-negative integers: -3");
+        assert_eq!(run("factorial(1/2)"), "factorial is only defined for non-negative integers: 1/2");
+        assert_eq!(run("factorial(21)"), "factorial(21) is too large to represent as a number");
+    }
+
+    #[test]
+    fn test_gcd_non_integer_error() {
+        let mut env = Env::new();
+        let input = r#"
+        gcd(1/2, 4)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "gcd function takes integers as arguments: 1/2");
+    }
+
+    #[test]
+    fn test_find_and_find_index() {
+        let mut env = Env::new();
+        let input = r#"
+        val xs = [1, 4, 6, 9]
+        find(xs, \|x| => x % 2 == 0)
+        find_index(xs, \|x| => x % 2 == 0)
+        find(xs, \|x| => x > 100)
+        find_index(xs, \|x| => x > 100)
+        xs -> find(\|x| => x > 5)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Option(Some(Box::new(Value::Number(Fraction::from(4))))));
+        assert_eq!(result[2], Value::Option(Some(Box::new(Value::Number(Fraction::from(1))))));
+        assert_eq!(result[3], Value::Option(None));
+        assert_eq!(result[4], Value::Option(None));
+        assert_eq!(result[5], Value::Option(Some(Box::new(Value::Number(Fraction::from(6))))));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut env = Env::new();
+        let input = r#"
+        val xs = [1, 2, 3, 4, 5, 6, 7]
+        val groups = group_by(xs, \|x| => if (x % 2 == 0) { "even" } else { "odd" })
+        groups["even"]
+        groups["odd"]
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        match &result[1] {
+            Value::Dict(groups) => assert_eq!(groups.len(), 2),
+            other => panic!("expected dict: {:?}", other),
+        }
+        assert_eq!(result[2], numbers(&[2, 4, 6]));
+        assert_eq!(result[3], numbers(&[1, 3, 5, 7]));
+    }
+
+    #[test]
+    fn test_group_by_non_string_key_error() {
+        let mut env = Env::new();
+        let input = r#"
+        group_by([1, 2], \|x| => x % 2)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "group_by key must be a string: 1");
+    }
+
+    #[test]
+    fn test_zip_with() {
+        let mut env = Env::new();
+        let input = r#"
+        val empty: List<number> = []
+        zip_with([1, 2, 3, 4], [10, 20, 30], \|x, y| => x + y)
+        zip_with([1, 2], [3, 4, 5], \|x, y| => x * y)
+        zip_with(empty, [1, 2], \|x, y| => x + y)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], numbers(&[11, 22, 33]));
+        assert_eq!(result[2], numbers(&[3, 8]));
+        assert_eq!(result[3], numbers(&[]));
+    }
+
+    #[test]
+    fn test_checked_add_in_bounds() {
+        let mut env = Env::new();
+        let input = r#"
+        checked_add(2, 3)
+        checked_add(100, 27, 127)
+        checked_add(0 - 100, 0 - 28, 128)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Result(Ok(Box::new(Value::Number(Fraction::from(5))))));
+        assert_eq!(result[1], Value::Result(Ok(Box::new(Value::Number(Fraction::from(127))))));
+        assert_eq!(result[2], Value::Result(Ok(Box::new(Value::Number(Fraction::from(-128))))));
+    }
+
+    #[test]
+    fn test_checked_add_out_of_bounds() {
+        let mut env = Env::new();
+        let input = r#"
+        checked_add(100, 28, 127)
+        checked_add(1/2, 1/3)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Result(Err(Box::new(Value::String("checked_add result 128 exceeds the bound 127".into())))));
+        assert_eq!(result[1], Value::Result(Err(Box::new(Value::String("checked_add result is not an integer: 5/6".into())))));
+    }
+
+    #[test]
+    fn test_checked_add_overflow() {
+        let mut env = Env::new();
+        let input = r#"
+        checked_add(10000000000000000000, 10000000000000000000)
+        checked_add(10000000000000000000, 10000000000000000000, 100)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let overflow = Value::Result(Err(Box::new(Value::String(
+            "checked_add result of 10000000000000000000 + 10000000000000000000 overflows".into(),
+        ))));
+        assert_eq!(result[0], overflow);
+        assert_eq!(result[1], overflow);
+    }
+
+    #[test]
+    fn test_checked_add_negative_bound_error() {
+        let mut env = Env::new();
+        let input = r#"
+        checked_add(1, 2, 0 - 1)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "checked_add bound must not be negative: -1");
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let mut env = Env::new();
+        let input = r#"
+        approx_eq(sin(0), 0, 1/1000000)
+        approx_eq(cos(0), 1, 1/1000000)
+        approx_eq(1, 2, 1/10)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result, vec![Value::Bool(true), Value::Bool(true), Value::Bool(false)]);
+    }
+
+    #[test]
+    fn test_approx_eq_negative_tolerance_error() {
+        let mut env = Env::new();
+        let input = r#"
+        approx_eq(1, 1, 0 - 1)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "approx_eq tolerance must not be negative: -1");
+    }
+
+    #[test]
+    fn test_require() {
+        let mut env = Env::new();
+        let input = r#"
+        fun half(n: number): number {
+            require(n % 2 == 0, "n must be even")
+            return n / 2
+        }
+        require(true, "never shown")
+        half(4)
+        half(3)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast[..3].to_vec(), &mut env).unwrap();
+        assert_eq!(result[1..], [Value::Void, Value::Number(Fraction::from(2))]);
+        let error = evals(ast[3..].to_vec(), &mut env).unwrap_err();
+        assert_eq!(error.message, "requirement failed: n must be even");
+        assert_eq!((error.line, error.column), (3, 13));
+    }
+
+    #[test]
+    fn test_compare_and_equals() {
+        let mut env = Env::new();
+        let input = r#"
+        compare(1, 2)
+        compare(2, 2)
+        compare(5 / 2, 2)
+        compare("apple", "banana")
+        compare("b", "a")
+        equals([[1, 2], [3]], [[1, 2], [3]])
+        equals({: "a" => 1, "b" => 2 :}, {: "b" => 2, "a" => 1 :})
+        equals([1, 2], [2, 1])
+        equals(1, "1")
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let number = |n: i64| Value::Number(Fraction::from(n));
+        assert_eq!(result, vec![
+            number(-1), number(0), number(1), number(-1), number(1),
+            Value::Bool(true), Value::Bool(true), Value::Bool(false), Value::Bool(false),
+        ]);
+    }
+
+    #[test]
+    fn test_same_coincides_with_equals_under_value_semantics() {
+        let mut env = Env::new();
+        let input = r#"
+        val mut a = [1, 2]
+        val b = [1, 2]
+        val c = a
+        same(a, b)
+        equals(a, b)
+        a.push(3)
+        same(a, c)
+        equals(a, c)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        // 別々に作った等しいリストも同じ実体とみなす
+        assert_eq!(result[3], Value::Bool(true));
+        assert_eq!(result[4], Value::Bool(true));
+        // 代入はコピーなので、元のリストを変更しても代入先とは別の値になる
+        assert_eq!(result[6], Value::Bool(false));
+        assert_eq!(result[7], Value::Bool(false));
+    }
+
+    #[test]
+    fn test_compare_incomparable_error() {
+        let mut env = Env::new();
+        let input = r#"compare(1, "a")"#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "cannot compare 1 and a");
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut env = Env::new();
+        let input = r#"
+        sort([3, 1, 5 / 2, 0 - 1])
+        sort(["pear", "apple", "fig"])
+        sort([])
+        [2, 1].sort()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let number = |n: Fraction| Value::Number(n);
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(result, vec![
+            Value::List(vec![number(Fraction::from(-1)), number(Fraction::from(1)), number(Fraction::new(5u64, 2u64)), number(Fraction::from(3))]),
+            Value::List(vec![string("apple"), string("fig"), string("pear")]),
+            Value::List(vec![]),
+            Value::List(vec![number(Fraction::from(1)), number(Fraction::from(2))]),
+        ]);
+    }
+
+    #[test]
+    fn test_sort_mixed_types_error() {
+        for input in [r#"sort([1, "a"])"#, r#"[1, "a"].sort()"#] {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines().unwrap();
+            let error = evals(ast, &mut env).unwrap_err();
+            assert_eq!(error.message, "cannot sort a list mixing number and string: found string at index 1");
+        }
+    }
+
+    #[test]
+    fn test_exit_stops_evaluation() {
+        let mut env = Env::new();
+        let input = r#"
+        fun check(n: number): number {
+            if (n < 0) {
+                exit(2)
+            }
+            return n
+        }
+        check(1)
+        check(0 - 1)
+        check(3)
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.exit_code, Some(2));
+        assert_eq!((error.line, error.column), (4, 17));
+    }
+
+    #[test]
+    fn test_exit_non_integer_error() {
+        let mut env = Env::new();
+        let tokens = tokenize(&"exit(1 / 2)".to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "exit code must be an integer: 1/2");
+        assert_eq!(error.exit_code, None);
+    }
+
+    #[test]
+    fn test_json_stringify() {
+        let mut env = Env::new();
+        let input = r#"
+        json_stringify([[1, 1/4], ["ab"], [true], [None, Some(2)]])
+        json_stringify({: "key" => "value", "n" => 2 :})
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Result(Ok(Box::new(Value::String(
+            r#"[[1,0.25],["ab"],[true],[null,2]]"#.into()
+        )))));
+        assert_eq!(result[1], Value::Result(Ok(Box::new(Value::String(r#"{"key":"value","n":2}"#.into())))));
+    }
+
+    #[test]
+    fn test_json_stringify_fraction_policy() {
+        let mut env = Env::new();
+        let input = r#"
+        json_stringify([3, 5/4, 1/3])
+        json_stringify([3, 5/4, 1/3], "decimal")
+        json_stringify([3, 5/4, 1/3], "string")
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let json = |s: &str| Value::Result(Ok(Box::new(Value::String(s.into()))));
+        assert_eq!(result[0], json("[3,1.25,0.3333333333333333]"));
+        assert_eq!(result[1], json("[3,1.25,0.3333333333333333]"));
+        assert_eq!(result[2], json(r#"[3,1.25,"1/3"]"#));
+    }
+
+    #[test]
+    fn test_json_stringify_rejects_unknown_fraction_policy() {
+        let mut env = Env::new();
+        let input = "json_stringify(1/3, \"round\")";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "json_stringify fraction policy must be \"string\" or \"decimal\": \"round\"");
+    }
+
+    #[test]
+    fn test_json_stringify_rejects_functions() {
+        let mut env = Env::new();
+        let input = r#"
+        val f = \|x| => x + 1
+        json_stringify([1, [f]])
+        [1, f]
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Result(Err(Box::new(Value::String("json_stringify cannot serialize a function".into())))));
+        assert_eq!(format!("{}", result[2]), "[1, <lambda>]");
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let mut env = Env::new();
+        let input = format!(r#"
+        val lines = read_lines("{}/tests/fixtures/lines.txt")
+        match lines {{
+            Suc(lines) => {{ lines.len() }}
+            Fail(message) => {{ -1 }}
+        }}
+        lines
+        read_lines("{}/tests/fixtures/missing.txt")
+        "#, env!("CARGO_MANIFEST_DIR"), env!("CARGO_MANIFEST_DIR"));
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Number(Fraction::from(4)));
+        let strings = |ss: &[&str]| Value::List(ss.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(result[2], Value::Result(Ok(Box::new(strings(&["first line", "second line", "", "fourth line"])))));
+        assert!(matches!(&result[3], Value::Result(Err(message)) if message.to_string().starts_with("read_lines could not read")));
+    }
+
+    #[test]
+    fn test_env() {
+        // 他のテストと衝突しない名前の環境変数を使う
+        unsafe { std::env::set_var("SAG_TEST_ENV_VALUE", "configured") };
+        let mut env = Env::new();
+        let input = r#"
+        env("SAG_TEST_ENV_VALUE")
+        env("SAG_TEST_ENV_UNSET")
+        env("SAG_TEST_ENV_UNSET").ok_or("unset")
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::Option(Some(Box::new(Value::String("configured".into())))));
+        assert_eq!(result[1], Value::Option(None));
+        assert_eq!(result[2], Value::Result(Err(Box::new(Value::String("unset".into())))));
+    }
 }
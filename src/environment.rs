@@ -1,5 +1,7 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::rc::Rc;
 use crate::ast::ASTNode;
 use crate::value::Value;
 use wasm_bindgen::prelude::*;
@@ -7,19 +9,27 @@ use crate::tokenizer::tokenize;
 use crate::parsers::Parser;
 use crate::evals::evals;
 use crate::builtin::register_builtins;
-use crate::evals::runtime_error::RuntimeError;
+use crate::evals::runtime_error::{RuntimeError, CallFrame};
+
+/// `sag install` がパッケージを配置し、importがモジュールを探すディレクトリ
+pub const PACKAGE_DIR: &str = "./.sag_packages";
 
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Env {
-    variable_map: HashMap<VariableKeyInfo, EnvVariableValueInfo>,
-    scope_stack: Vec<String>,
+    // 変数名 -> (スコープ名 -> 変数情報)。名前で1回引けばそのスコープ一覧が得られる
+    variable_map: HashMap<String, HashMap<String, EnvVariableValueInfo>>,
+    scope_stack: Vec<Scope>,
     functions: HashMap<String, FunctionInfo>,
     structs: HashMap<String, Value>,
     builtins: HashMap<String, FunctionInfo>,
     modules: HashMap<String, Env>,
     exported_symbols: HashMap<String, ExportedSymbolType>,
+    // 評価中の関数呼び出しの履歴(エラー時のバックトレースに使う)
+    call_stack: Vec<CallFrame>,
+    // 残りの評価ステップ数(Noneなら無制限)。関数呼び出しなどで複製したEnvとは同じ残り回数を共有する
+    remaining_steps: Rc<Cell<Option<usize>>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -29,12 +39,15 @@ pub enum ExportedSymbolType {
     Struct
 }
 
+/// 組み込み関数のシグネチャ(エラーは呼び出し位置付きのRuntimeErrorに変換される)
+pub type BuiltinFunction = fn(Vec<Value>, &mut Env) -> Result<Value, String>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionInfo {
     pub arguments: Vec<ASTNode>,
     pub return_type: ValueType,
     pub body: Option<ASTNode>,
-    pub builtin: Option<fn(Vec<Value>) -> Value>,
+    pub builtin: Option<BuiltinFunction>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -45,13 +58,29 @@ pub struct MethodInfo {
     pub is_mut: bool,
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
-pub struct VariableKeyInfo {
+/// スコープの名前と、そのスコープで宣言された変数名
+/// スコープを抜けるときは、全変数を調べずに宣言された変数だけを破棄する
+#[derive(Debug, Clone, PartialEq)]
+struct Scope {
     name: String,
-    scope: String,
+    declared: Vec<String>,
+    // 関数やラムダの呼び出しで入ったスコープ。ここより外側はグローバルしか見えない
+    is_frame: bool,
+}
+
+impl Scope {
+    fn new(name: String) -> Self {
+        Self { name, declared: vec![], is_frame: false }
+    }
+}
+
+/// スコープの状態を記録し、ブロックを抜けるときに復元するためのスナップショット
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScopeSnapshot {
+    depth: usize,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, serde::Serialize)]
 pub enum EnvVariableType {
     Immutable,
     Mutable,
@@ -79,6 +108,39 @@ pub enum ValueType {
     ResultType{success: Box<ValueType>, failure: Box<ValueType>},
 }
 
+/// 型をソースコード上の型名にする。Anyのように書けない型はNoneになる
+pub fn type_name(value_type: &ValueType) -> Option<String> {
+    match value_type {
+        ValueType::Number => Some("number".to_string()),
+        ValueType::String => Some("string".to_string()),
+        ValueType::Bool => Some("bool".to_string()),
+        ValueType::Void => Some("void".to_string()),
+        ValueType::List(element) => Some(format!("List<{}>", type_name(element)?)),
+        ValueType::Dict(value) => Some(format!("Dict<string, {}>", type_name(value)?)),
+        ValueType::OptionType(value) => Some(format!("Option<{}>", type_name(value)?)),
+        ValueType::ResultType { success, failure } => Some(format!("Result<{}, {}>", type_name(success)?, type_name(failure)?)),
+        ValueType::Struct { name, .. } | ValueType::StructInstance { name, .. } => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// エラーメッセージ用の型名。type_nameで書けない型もanyやfunctionのような名前にする
+pub fn describe_type(value_type: &ValueType) -> String {
+    if let Some(name) = type_name(value_type) {
+        return name;
+    }
+    match value_type {
+        ValueType::Any => "any".to_string(),
+        ValueType::Function => "function".to_string(),
+        ValueType::Lambda => "lambda".to_string(),
+        ValueType::List(element) => format!("List<{}>", describe_type(element)),
+        ValueType::Dict(value) => format!("Dict<string, {}>", describe_type(value)),
+        ValueType::OptionType(value) => format!("Option<{}>", describe_type(value)),
+        ValueType::ResultType { success, failure } => format!("Result<{}, {}>", describe_type(success), describe_type(failure)),
+        other => format!("{:?}", other),
+    }
+}
+
 impl PartialEq for ValueType {
     fn eq(&self, other: &Self) -> bool {
         match (self.clone(), other.clone()) {
@@ -117,12 +179,31 @@ impl Env {
     pub fn new() -> Self {
         Self {
             variable_map: HashMap::new(),
-            scope_stack: vec!["global".to_string()],
+            scope_stack: vec![Scope::new("global".to_string())],
             functions: HashMap::new(),
             structs: HashMap::new(),
             builtins: HashMap::new(),
             modules: HashMap::new(),
             exported_symbols: HashMap::new(),
+            call_stack: vec![],
+            remaining_steps: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// 評価できるステップ数の上限を設定する(Noneなら無制限)
+    pub fn set_step_limit(&mut self, step_limit: Option<usize>) {
+        self.remaining_steps.set(step_limit);
+    }
+
+    /// ループの1周や関数呼び出しを1ステップとして数え、上限を超えたらエラーにする
+    pub fn count_step(&self, line: usize, column: usize) -> Result<(), RuntimeError> {
+        match self.remaining_steps.get() {
+            Some(0) => Err(RuntimeError::new("execution step limit exceeded", line, column)),
+            Some(steps) => {
+                self.remaining_steps.set(Some(steps - 1));
+                Ok(())
+            }
+            None => Ok(()),
         }
     }
 
@@ -132,28 +213,38 @@ impl Env {
             return Ok(());
         }
 
-        let file_content = if !PathBuf::from(module_path).exists() {
-            let module_path = PathBuf::from(format!("./.sag_packages/{}", module_path));
-            if !module_path.exists() {
-                return Err("missing package".to_string())
+        // カレントディレクトリ、インストール済みパッケージの順に探す
+        let candidates = [PathBuf::from(module_path), PathBuf::from(PACKAGE_DIR).join(module_path)];
+        let found_path = match candidates.iter().find(|path| path.is_file()) {
+            Some(path) => path,
+            None => {
+                let searched = candidates.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ");
+                return Err(format!("module not found (searched {})", searched));
             }
-            std::fs::read_to_string(module_path).unwrap()
-        } else {
-            std::fs::read_to_string(module_path).unwrap()
+        };
+        let file_content = match std::fs::read_to_string(found_path) {
+            Ok(content) => content,
+            Err(e) => return Err(format!("cannot read {}: {}", found_path.display(), e)),
         };
 
-        let tokens = tokenize(&file_content);
+        // run_fileと同じく、エラーはモジュールのソース行を付けて表示する
+        let tokens = match tokenize(&file_content) {
+            Ok(tokens) => tokens,
+            Err(e) => return Err(e.message_with_source(&file_content)),
+        };
         let builtins = register_builtins(self);
         let mut parser = Parser::new(tokens, builtins);
         let ast_nodes = parser.parse_lines();
         if let Err(e) = ast_nodes {
-            return Err(format!("Error: {:?}", e));
+            return Err(e.message_with_source(&file_content));
         }
 
         let mut module_env = Env::new();
+        // モジュールの評価もimportした側と同じステップ数の上限で数える
+        module_env.remaining_steps = Rc::clone(&self.remaining_steps);
         let result = evals(ast_nodes.unwrap(), &mut module_env);
         if let Err(e) = result {
-            return Err(format!("Error: {:?}", e));
+            return Err(e.message_with_source(&file_content));
         }
         self.modules.insert(module_name.to_string(), module_env);
         Ok(())
@@ -164,10 +255,7 @@ impl Env {
     }
 
     pub fn register_exported_symbol(&mut self, name: String) {
-        if let Some(_) = self.variable_map.get(&VariableKeyInfo {
-            name: name.clone(),
-            scope: "global".to_string(),
-        }) {
+        if self.get_with_scope(&name, "global").is_some() {
             self.exported_symbols.insert(name, ExportedSymbolType::Variable);
         } else if let Some(_) = self.get_function(&name) {
             self.exported_symbols.insert(name, ExportedSymbolType::Function);
@@ -216,7 +304,7 @@ impl Env {
         }
     }
 
-    pub fn register_builtin(&mut self, name: String, function: fn(Vec<Value>) -> Value) {
+    pub fn register_builtin(&mut self, name: String, function: BuiltinFunction) {
         let function_info = FunctionInfo {
             arguments: vec![],
             return_type: ValueType::Any,
@@ -231,19 +319,115 @@ impl Env {
     }
 
     pub fn enter_scope(&mut self, scope: String) {
-        self.scope_stack.push(scope);
+        self.scope_stack.push(Scope::new(scope));
     }
     pub fn leave_scope(&mut self) {
-        if self.scope_stack.len() == 1 && self.scope_stack[0] == "global".to_string() {
+        if self.scope_stack.len() == 1 && self.scope_stack[0].name == "global" {
             return;
         }
 
         self.scope_stack.pop();
     }
 
+    /// 内側のスコープから順に条件に合うスコープを探す
+    pub fn find_scope<F: Fn(&String) -> bool>(&self, predicate: F) -> Option<String> {
+        self.visible_scopes().map(|scope| &scope.name).find(|scope| predicate(scope)).cloned()
+    }
+
+    /// 現在のスコープから見えるスコープを内側から順に返す
+    /// 呼び出しの境界より外側(呼び出し元のローカル)は飛ばし、最後にグローバルを返す
+    fn visible_scopes(&self) -> impl Iterator<Item = &Scope> {
+        let frame = self.scope_stack.iter().rposition(|scope| scope.is_frame).unwrap_or(0);
+        self.scope_stack[frame..].iter().rev().chain(self.scope_stack[..frame.min(1)].iter())
+    }
+
+    /// 現在のスコープの深さを記録する
+    pub fn snapshot_scope(&self) -> ScopeSnapshot {
+        ScopeSnapshot { depth: self.scope_stack.len() }
+    }
+
+    /// スナップショット以降に入ったスコープを抜け、そのスコープで宣言された変数を破棄する
+    pub fn restore_scope(&mut self, snapshot: ScopeSnapshot) {
+        while self.scope_stack.len() > snapshot.depth.max(1) {
+            let scope = self.scope_stack.pop().unwrap();
+            // 同名のスコープがまだ残っている場合は変数を残し、そのスコープを抜けるときに破棄する
+            if let Some(outer) = self.scope_stack.iter_mut().rev().find(|outer| outer.name == scope.name) {
+                outer.declared.extend(scope.declared);
+                continue;
+            }
+            for name in scope.declared {
+                if let Some(scopes) = self.variable_map.get_mut(&name) {
+                    scopes.remove(&scope.name);
+                    if scopes.is_empty() {
+                        self.variable_map.remove(&name);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn push_call_frame(&mut self, name: &str, line: usize, column: usize) {
+        self.call_stack.push(CallFrame { name: name.to_string(), line, column });
+    }
+
+    pub fn pop_call_frame(&mut self) {
+        self.call_stack.pop();
+    }
+
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    /// 深さを含む一意な名前のスコープに入る
+    /// 再帰呼び出しで同じ関数やループのスコープが重なっても変数が衝突しない
+    pub fn enter_unique_scope(&mut self, prefix: &str) -> ScopeSnapshot {
+        let snapshot = self.snapshot_scope();
+        self.enter_scope(format!("{}-{}", prefix, snapshot.depth));
+        snapshot
+    }
+
+    /// 関数やラムダの呼び出し用のスコープに入る
+    /// 呼び出された側からは呼び出し元のローカル変数が見えない
+    pub fn enter_frame_scope(&mut self, prefix: &str) -> ScopeSnapshot {
+        let snapshot = self.enter_unique_scope(prefix);
+        if let Some(scope) = self.scope_stack.last_mut() {
+            scope.is_frame = true;
+        }
+        snapshot
+    }
+
+    /// 現在見えているグローバル以外の変数(ラムダが作られたときに捕捉する変数)を返す
+    pub fn captured_variables(&self) -> Vec<(String, EnvVariableValueInfo)> {
+        let mut captured: Vec<(String, EnvVariableValueInfo)> = vec![];
+        for scope in self.visible_scopes().filter(|scope| scope.name != "global") {
+            for name in &scope.declared {
+                if captured.iter().any(|(captured_name, _)| captured_name == name) {
+                    continue;
+                }
+                if let Some(variable_info) = self.get_with_scope(name, &scope.name) {
+                    captured.push((name.clone(), variable_info.clone()));
+                }
+            }
+        }
+        captured
+    }
+
+    /// 捕捉した変数を現在のスコープに宣言する
+    pub fn declare_captured(&mut self, captured: Vec<(String, EnvVariableValueInfo)>) {
+        let scope = self.get_current_scope();
+        for (name, variable_info) in captured {
+            self.declare(name, scope.clone(), variable_info);
+        }
+    }
+
+    /// ブロック用のスコープに入る
+    pub fn enter_block_scope(&mut self) -> ScopeSnapshot {
+        self.enter_unique_scope("block")
+    }
+
     pub fn get_current_scope(&self) -> String {
         match self.scope_stack.last() {
-            Some(scope) => scope.clone(),
+            Some(scope) => scope.name.clone(),
             None => "global".to_string(),
         }
     }
@@ -257,10 +441,11 @@ impl Env {
     }
 
     pub fn update_global_env(&mut self, local_env: &Self) {
-        for (local_key, local_value) in &local_env.variable_map {
-            if local_key.scope == "global" && self.variable_map.contains_key(local_key) {
-                self.variable_map
-                    .insert(local_key.clone(), local_value.clone());
+        for (name, local_scopes) in &local_env.variable_map {
+            if let Some(local_value) = local_scopes.get("global")
+                && let Some(global_value) = self.variable_map.get_mut(name).and_then(|scopes| scopes.get_mut("global"))
+            {
+                *global_value = local_value.clone();
             }
         }
     }
@@ -274,109 +459,122 @@ impl Env {
         is_new: bool,
     ) -> Result<(), String> {
         let latest_scope = match self.scope_stack.last() {
-            Some(scope) => scope.clone(),
+            Some(scope) => scope.name.clone(),
             None => return Err("Missing scope".into()),
         };
+        let value_info = EnvVariableValueInfo {
+            value,
+            variable_type,
+            value_type,
+        };
 
         // 新規の場合はそのまま書き込み
         if is_new {
-            self.variable_map.insert(
-                VariableKeyInfo {
-                    name: name.clone(),
-                    scope: latest_scope,
-                },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
-            );
+            self.declare(name, latest_scope, value_info);
             return Ok(());
         }
 
-        // ローカルスコープの変数をチェックと存在したら更新
-        if let Some(value_info) = self.get_with_scope(name.clone(), latest_scope.clone()) {
-            if value_info.variable_type == EnvVariableType::Immutable {
-                return Err("Cannot reassign to immutable variable".into());
+        // 内側のスコープから順に変数を探し、存在したらそのスコープの変数を更新
+        let visible_scope = self.variable_map.get(&name).and_then(|scopes| {
+            self.visible_scopes().find(|scope| scopes.contains_key(scope.name.as_str())).map(|scope| scope.name.clone())
+        });
+        if let Some(scope) = visible_scope {
+            let current = self.variable_map.get_mut(&name).and_then(|scopes| scopes.get_mut(&scope)).unwrap();
+            if current.variable_type == EnvVariableType::Immutable {
+                return Err(format!("Cannot reassign to immutable variable: {:?}", name));
             }
-            self.variable_map.insert(
-                VariableKeyInfo {
-                    name,
-                    scope: latest_scope,
-                },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
-            );
-            return Ok(());
-        }
-
-        // グローバルスコープの変数をチェックと存在したら更新
-        if let Some(value_info) = self.get_with_scope(name.clone(), "global".to_string()) {
-            if value_info.variable_type == EnvVariableType::Immutable {
-                return Err("Cannot reassign to immutable variable".into());
+            // 数値・文字列・真偽値の間で型が変わる再代入は受け付けない
+            let is_scalar = |value_type: &ValueType| matches!(value_type, ValueType::Number | ValueType::String | ValueType::Bool);
+            if is_scalar(&current.value_type) && is_scalar(&value_info.value_type) && current.value_type != value_info.value_type {
+                return Err(format!(
+                    "type mismatch: cannot change the type of {:?} from {} to {}",
+                    name, describe_type(&current.value_type), describe_type(&value_info.value_type)
+                ));
             }
-            // グローバル変数を更新
-            self.variable_map.insert(
-                VariableKeyInfo {
-                    name,
-                    scope: "global".to_string(),
-                },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
-            );
+            *current = value_info;
             return Ok(());
         }
 
         // どこにも存在しないので新しい変数としてローカルスコープに追加
-        self.variable_map.insert(
-            VariableKeyInfo {
-                name,
-                scope: latest_scope,
-            },
-            EnvVariableValueInfo {
-                value,
-                variable_type,
-                value_type,
-            },
-        );
-
+        self.declare(name, latest_scope, value_info);
         Ok(())
     }
 
-    fn get_with_scope(&self, name: String, scope: String) -> Option<&EnvVariableValueInfo> {
-        if let Some(variable_key_info) = self.variable_map.get(&VariableKeyInfo {
-            name: name.to_string(),
-            scope: scope.clone(),
-        }) {
-            return Some(variable_key_info);
+    /// 現在のスコープに変数を書き込み、初めて宣言した名前ならスコープに記録する
+    fn declare(&mut self, name: String, scope: String, value_info: EnvVariableValueInfo) {
+        let scopes = self.variable_map.entry(name.clone()).or_default();
+        if scopes.insert(scope, value_info).is_none()
+            && let Some(current) = self.scope_stack.last_mut()
+        {
+            current.declared.push(name);
         }
-        None
+    }
+
+    fn get_with_scope(&self, name: &str, scope: &str) -> Option<&EnvVariableValueInfo> {
+        self.variable_map.get(name).and_then(|scopes| scopes.get(scope))
     }
 
     pub fn get(
         &self,
-        name: &String,
+        name: &str,
         value_type: Option<&ValueType>,
     ) -> Option<&EnvVariableValueInfo> {
-        for scope in self.scope_stack.iter().rev() {
-            if let Some(variable_key_info) = self.variable_map.get(&VariableKeyInfo {
-                name: name.to_string(),
-                scope: scope.clone(),
-            }) {
-                if value_type.is_some() {
-                    if variable_key_info.value_type != *value_type.unwrap() {
-                        continue;
-                    }
+        // 名前で1回だけ引き、文字列を確保せずに内側のスコープから探す
+        let scopes = self.variable_map.get(name)?;
+        for scope in self.visible_scopes() {
+            if let Some(variable_info) = scopes.get(scope.name.as_str()) {
+                if let Some(value_type) = value_type
+                    && variable_info.value_type != *value_type
+                {
+                    continue;
                 }
-                return Some(variable_key_info);
+                return Some(variable_info);
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fraction::Fraction;
+
+    fn set_number(env: &mut Env, name: &str, n: i64) {
+        env.set(name.to_string(), Value::Number(Fraction::from(n)), EnvVariableType::Immutable, ValueType::Number, true).unwrap();
+    }
+
+    #[test]
+    fn test_restore_scope_removes_only_declared_variables() {
+        let mut env = Env::new();
+        set_number(&mut env, "x", 1);
+        let outer = env.enter_block_scope();
+        set_number(&mut env, "x", 2);
+        set_number(&mut env, "y", 3);
+        let inner = env.enter_block_scope();
+        set_number(&mut env, "z", 4);
+        env.restore_scope(inner);
+        assert!(!env.variable_map.contains_key("z"));
+        assert_eq!(env.get(&"y".to_string(), None).unwrap().value, Value::Number(Fraction::from(3)));
+        env.restore_scope(outer);
+        assert!(!env.variable_map.contains_key("y"));
+        assert_eq!(env.variable_map["x"].len(), 1);
+        assert_eq!(env.get(&"x".to_string(), None).unwrap().value, Value::Number(Fraction::from(1)));
+    }
+
+    #[test]
+    fn test_restore_scope_keeps_variables_of_same_named_outer_scope() {
+        let mut env = Env::new();
+        let snapshot = env.snapshot_scope();
+        env.enter_scope("f".to_string());
+        set_number(&mut env, "a", 1);
+        let inner = env.snapshot_scope();
+        env.enter_scope("f".to_string());
+        set_number(&mut env, "b", 2);
+        env.restore_scope(inner);
+        // 同名のスコープが残っている間は変数を残す
+        assert!(env.get(&"b".to_string(), None).is_some());
+        env.restore_scope(snapshot);
+        assert!(env.variable_map.is_empty());
+    }
+}
@@ -1,10 +1,35 @@
 use crate::ast::ASTNode;
 use crate::value::Value;
-use crate::environment::{Env, ValueType, EnvVariableType};
+use crate::environment::{Env, ValueType, EnvVariableType, describe_type};
 use crate::evals::eval;
 use crate::evals::runtime_error::RuntimeError;
 
+/// Dict<string, T>と型を付けた辞書なら値がTであることを確かめる
+/// 型注釈のない辞書はDict<string, Any>として扱い、値の型が混ざっていてもよい
+pub fn check_dict_value_type(dict_type: &ValueType, key: &str, value: &Value, line: usize, column: usize) -> Result<(), RuntimeError> {
+    if let ValueType::Dict(expected) = dict_type
+        && !matches!(expected.as_ref(), ValueType::Any)
+        && value.value_type() != **expected
+    {
+        return Err(RuntimeError::new(
+            format!("dict value for key {:?} must be {}, found {}", key, describe_type(expected), describe_type(&value.value_type())).as_str(),
+            line,
+            column,
+        ));
+    }
+    Ok(())
+}
+
 pub fn assign_node(name: String, value: Box<ASTNode>, value_type: ValueType, variable_type: EnvVariableType, is_new: bool, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    // `val x = ...` の再宣言はシャドーイングなので不変変数でも許可し、`x = ...` の再代入だけ不変かどうかを検査する
+    if !is_new
+        && let Some(info) = env.get(&name, None)
+        && info.variable_type == EnvVariableType::Immutable
+    {
+        return Err(RuntimeError::new(format!("Cannot reassign to immutable variable: {:?}", name).as_str(), line, column));
+    }
+    // 変数の評価結果は環境の値のclone()で、Valueは参照を共有しないため、
+    // リストや辞書を代入しても代入先と代入元が同じ値を共有することはない
     let value = eval(*value, env)?;
     //let value_type = match value {
     //    Value::Number(_) => ValueType::Number,
@@ -59,6 +84,11 @@ pub fn assign_node(name: String, value: Box<ASTNode>, value_type: ValueType, var
     //    },
     //    _ => return Err(RuntimeError::new("Unsupported value type", line, column)),
     //};
+    if let Value::Dict(dict) = &value {
+        for (key, dict_value) in dict {
+            check_dict_value_type(&value_type, key, dict_value, line, column)?;
+        }
+    }
     let result = env.set(
         name.to_string(),
         value.clone(),
@@ -71,3 +101,95 @@ pub fn assign_node(name: String, value: Box<ASTNode>, value_type: ValueType, var
     }
     Ok(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fraction::Fraction;
+    use crate::tokenizer::tokenize;
+    use crate::parsers::Parser;
+    use crate::builtin::register_builtins;
+    use crate::evals::evals;
+
+    #[test]
+    fn test_assigning_list_copies_value() {
+        let input = r#"
+        val a = [1, 2]
+        val mut b = a
+        b.push(3)
+        b[0] = 10
+        a
+        b
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let numbers = |ns: &[i64]| Value::List(ns.iter().map(|n| Value::Number(Fraction::from(*n))).collect());
+        assert_eq!(result[4], numbers(&[1, 2]));
+        assert_eq!(result[5], numbers(&[10, 2, 3]));
+    }
+
+    #[test]
+    fn test_reassigning_with_different_type_is_error() {
+        let input = "val mut x = 1\nx = \"a\"".to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "type mismatch: cannot change the type of \"x\" from number to string");
+        assert_eq!((error.line, error.column), (2, 5));
+    }
+
+    #[test]
+    fn test_env_set_rejects_type_change() {
+        let mut env = Env::new();
+        env.set("x".to_string(), Value::Number(Fraction::from(1)), EnvVariableType::Mutable, ValueType::Number, true).unwrap();
+        let error = env.set("x".to_string(), Value::String("a".into()), EnvVariableType::Mutable, ValueType::String, false).unwrap_err();
+        assert_eq!(error, "type mismatch: cannot change the type of \"x\" from number to string");
+        env.set("x".to_string(), Value::Number(Fraction::from(2)), EnvVariableType::Mutable, ValueType::Number, false).unwrap();
+        assert_eq!(env.get(&"x".to_string(), None).unwrap().value, Value::Number(Fraction::from(2)));
+    }
+
+    fn run(input: &str) -> Result<Vec<Value>, RuntimeError> {
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        evals(ast, &mut env)
+    }
+
+    #[test]
+    fn test_typed_dict_literal_with_mismatched_value_is_error() {
+        let error = run("val d: Dict<string, number> = {: \"a\" => 1, \"b\" => \"x\" :}").unwrap_err();
+        assert_eq!(error.message, "dict value for key \"b\" must be number, found string");
+        assert_eq!((error.line, error.column), (1, 1));
+    }
+
+    #[test]
+    fn test_typed_dict_assign_with_mismatched_value_is_error() {
+        let error = run("val mut d: Dict<string, number> = {: \"a\" => 1 :}\nd[\"b\"] = \"x\"").unwrap_err();
+        assert_eq!(error.message, "dict value for key \"b\" must be number, found string");
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_untyped_dict_can_be_heterogeneous() {
+        let result = run("val mut d = {: \"a\" => 1, \"b\" => \"x\" :}\nd[\"c\"] = true\nd.len()").unwrap();
+        assert_eq!(result[2], Value::Number(Fraction::from(3)));
+    }
+
+    #[test]
+    fn test_typed_dict_methods_check_value_type() {
+        let error = run("val mut d: Dict<string, number> = {: \"a\" => 1 :}\nd.insert(\"b\", \"x\")").unwrap_err();
+        assert_eq!(error.message, "dict value for key \"b\" must be number, found string");
+        let error = run("val mut d: Dict<string, number> = {: \"a\" => 1 :}\nd.get_or_insert(\"c\", true)").unwrap_err();
+        assert_eq!(error.message, "dict value for key \"c\" must be number, found bool");
+        let error = run("val mut d: Dict<string, number> = {: \"a\" => 1 :}\nd.update({: \"b\" => 2, \"c\" => \"x\" :})").unwrap_err();
+        assert_eq!(error.message, "dict value for key \"c\" must be number, found string");
+        assert_eq!(error.line, 2);
+        let result = run("val mut d: Dict<string, number> = {: \"a\" => 1 :}\nd.insert(\"b\", 2)\nd.get_or_insert(\"c\", 3)\nd").unwrap();
+        assert_eq!(format!("{}", result[3]), "{:a: 1, b: 2, c: 3:}");
+    }
+}
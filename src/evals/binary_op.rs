@@ -11,6 +11,9 @@ pub fn binary_op(op: TokenKind, left: Box<ASTNode>, right: Box<ASTNode>, line: u
 
     match (left_val.clone(), right_val.clone(), op.clone()) {
         (Value::String(l), Value::String(r), TokenKind::Plus) => Ok(Value::String(l + &r)),
+        // 数値は自動で文字列に変換しない
+        (Value::String(_), Value::Number(_), TokenKind::Plus) => Err(RuntimeError::new("cannot add number to string, call to_string first", line, column)),
+        (Value::Number(_), Value::String(_), TokenKind::Plus) => Err(RuntimeError::new("cannot add string to number, call to_string first", line, column)),
         (Value::Number(l), Value::Number(r), TokenKind::Plus) => Ok(Value::Number(l + r)),
         (Value::Number(l), Value::Number(r), TokenKind::Minus) => Ok(Value::Number(l - r)),
         (Value::Number(l), Value::Number(r), TokenKind::Mul) => Ok(Value::Number(l * r)),
@@ -64,7 +67,7 @@ mod tests {
     fn add() {
         let mut env = Env::new();
         let input = "1 + 1".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -75,7 +78,7 @@ mod tests {
     fn sub() {
         let mut env = Env::new();
         let input = "1 - 1".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -86,7 +89,7 @@ mod tests {
     fn mul() {
         let mut env = Env::new();
         let input = "2 * 3".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -97,7 +100,7 @@ mod tests {
     fn div() {
         let mut env = Env::new();
         let input = "2 / 3".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -109,7 +112,7 @@ mod tests {
         let mut env = Env::new();
         for xy in [(true, true), (true, false), (false, true), (false, false)] {
             let input = format!("{} and {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -118,7 +121,7 @@ mod tests {
 
         for xy in [(1, 1), (1, 0), (0, 1), (0, 0)] {
             let input = format!("{} and {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -131,7 +134,7 @@ mod tests {
         let mut env = Env::new();
         for xy in [(true, true), (true, false), (false, true), (false, false)] {
             let input = format!("{} or {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -139,7 +142,7 @@ mod tests {
         }
         for xy in [(1, 1), (1, 0), (0, 1), (0, 0)] {
             let input = format!("{} or {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -152,7 +155,7 @@ mod tests {
         let mut env = Env::new();
         for xy in [(true, true), (true, false), (false, true), (false, false)] {
             let input = format!("{} xor {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -160,7 +163,7 @@ mod tests {
         }
         for xy in [(1, 1), (1, 0), (0, 1), (0, 0)] {
             let input = format!("{} xor {}", xy.0, xy.1);
-            let tokens = tokenize(&input);
+            let tokens = tokenize(&input).unwrap();
             let mut parser = Parser::new(tokens, register_builtins(&mut env));
             let ast = parser.parse_lines();
             let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -172,10 +175,49 @@ mod tests {
     fn pow() {
         let mut env = Env::new();
         let input = "val h = 188\nval w = 104\n w / (h / 100) ** 2".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
         assert_eq!(result[2], Value::Number((65000, 2209).into()));
     }
+
+    #[test]
+    fn add_strings() {
+        let mut env = Env::new();
+        let input = "val s = \"a\" + \"b\"\ns + 1.to_string()".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[1], Value::String("ab1".to_string()));
+    }
+
+    #[test]
+    fn add_string_and_number_error() {
+        // 位置は演算子を指す
+        for (input, message, column) in [
+            ("\"a\" + 1", "cannot add number to string, call to_string first", 5),
+            ("1 + \"a\"", "cannot add string to number, call to_string first", 3),
+        ] {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines();
+            let error = evals(ast.unwrap(), &mut env).unwrap_err();
+            assert_eq!(error.message, message);
+            assert_eq!((error.line, error.column), (1, column));
+        }
+    }
+
+    #[test]
+    fn add_string_and_number_in_assignment_error() {
+        // 代入では型推論の時点で同じ規則のエラーになる
+        let mut env = Env::new();
+        let tokens = tokenize(&"val s = \"a\" + 1".to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "cannot add number to string, call to_string first");
+        assert_eq!((error.line, error.column), (1, 9));
+    }
 }
@@ -19,6 +19,12 @@ pub fn comparison_op_node(op: TokenKind, left: Box<ASTNode>, right: Box<ASTNode>
         (Value::String(l), Value::String(r), TokenKind::Neq) => Ok(Value::Bool(l != r)),
         (Value::Bool(l), Value::Bool(r), TokenKind::Eq) => Ok(Value::Bool(l == r)),
         (Value::Bool(l), Value::Bool(r), TokenKind::Neq) => Ok(Value::Bool(l != r)),
+        // 辞書の比較はキーの挿入順に依存しない
+        (Value::Dict(l), Value::Dict(r), TokenKind::Eq) => Ok(Value::Bool(l == r)),
+        (Value::Dict(l), Value::Dict(r), TokenKind::Neq) => Ok(Value::Bool(l != r)),
+        // 構造体インスタンスは構造体名と全フィールドが等しいときに等しい(異なる構造体同士は常に異なる)
+        (l @ Value::StructInstance { .. }, r @ Value::StructInstance { .. }, TokenKind::Eq) => Ok(Value::Bool(l == r)),
+        (l @ Value::StructInstance { .. }, r @ Value::StructInstance { .. }, TokenKind::Neq) => Ok(Value::Bool(l != r)),
         _ => Err(RuntimeError::new("Unsupported operation", line, column)),
     }
 }
@@ -0,0 +1,187 @@
+use crate::ast::ASTNode;
+use crate::value::Value;
+use crate::environment::Env;
+use crate::evals::eval;
+
+/// 評価前に、リテラル同士の演算だけでできた式を1つのLiteralに畳み込む
+/// 変数や関数呼び出しを含む式は畳み込まない。評価するとエラーになる式(型の合わない演算など)も実行時にエラーを出せるようそのまま残す
+pub fn fold_constants(ast: ASTNode) -> ASTNode {
+    match ast {
+        ASTNode::BinaryOp { left, op, right, line, column } => fold_if_constant(ASTNode::BinaryOp {
+            left: Box::new(fold_constants(*left)),
+            op,
+            right: Box::new(fold_constants(*right)),
+            line,
+            column,
+        }),
+        ASTNode::PrefixOp { op, expr, line, column } => fold_if_constant(ASTNode::PrefixOp {
+            op,
+            expr: Box::new(fold_constants(*expr)),
+            line,
+            column,
+        }),
+        ASTNode::Eq { left, right, line, column } => fold_if_constant(ASTNode::Eq { left: fold_box(left), right: fold_box(right), line, column }),
+        ASTNode::Gte { left, right, line, column } => fold_if_constant(ASTNode::Gte { left: fold_box(left), right: fold_box(right), line, column }),
+        ASTNode::Gt { left, right, line, column } => fold_if_constant(ASTNode::Gt { left: fold_box(left), right: fold_box(right), line, column }),
+        ASTNode::Lte { left, right, line, column } => fold_if_constant(ASTNode::Lte { left: fold_box(left), right: fold_box(right), line, column }),
+        ASTNode::Lt { left, right, line, column } => fold_if_constant(ASTNode::Lt { left: fold_box(left), right: fold_box(right), line, column }),
+        // 式を含む文は中の式だけを畳み込む
+        ASTNode::Assign { name, value, variable_type, value_type, is_new, line, column } => ASTNode::Assign {
+            name,
+            value: fold_box(value),
+            variable_type,
+            value_type,
+            is_new,
+            line,
+            column,
+        },
+        ASTNode::Block { nodes, line, column } => ASTNode::Block {
+            nodes: nodes.into_iter().map(fold_constants).collect(),
+            line,
+            column,
+        },
+        ASTNode::List { elements, line, column } => ASTNode::List {
+            elements: elements.into_iter().map(fold_constants).collect(),
+            line,
+            column,
+        },
+        ASTNode::Function { name, arguments, body, return_type, line, column } => ASTNode::Function {
+            name,
+            arguments,
+            body: fold_box(body),
+            return_type,
+            line,
+            column,
+        },
+        ASTNode::FunctionCall { name, arguments, line, column } => ASTNode::FunctionCall {
+            name,
+            arguments: fold_box(arguments),
+            line,
+            column,
+        },
+        ASTNode::FunctionCallArgs { args, line, column } => ASTNode::FunctionCallArgs {
+            args: args.into_iter().map(fold_constants).collect(),
+            line,
+            column,
+        },
+        ASTNode::Return { expr, line, column } => ASTNode::Return { expr: fold_box(expr), line, column },
+        ASTNode::If { condition, is_statement, then, else_, value_type, line, column } => ASTNode::If {
+            condition: fold_box(condition),
+            is_statement,
+            then: fold_box(then),
+            else_: else_.map(fold_box),
+            value_type,
+            line,
+            column,
+        },
+        ASTNode::For { variable, iterable, body, label, line, column } => ASTNode::For {
+            variable,
+            iterable: fold_box(iterable),
+            body: fold_box(body),
+            label,
+            line,
+            column,
+        },
+        ASTNode::While { condition, body, label, line, column } => ASTNode::While {
+            condition: fold_box(condition),
+            body: fold_box(body),
+            label,
+            line,
+            column,
+        },
+        ASTNode::DoWhile { body, condition, label, line, column } => ASTNode::DoWhile {
+            body: fold_box(body),
+            condition: fold_box(condition),
+            label,
+            line,
+            column,
+        },
+        ast => ast,
+    }
+}
+
+fn fold_box(ast: Box<ASTNode>) -> Box<ASTNode> {
+    Box::new(fold_constants(*ast))
+}
+
+fn is_constant_literal(ast: &ASTNode) -> bool {
+    match ast {
+        // 無限大やNaNは演算によってはpanicするので畳み込まない
+        ASTNode::Literal { value: Value::Number(value), .. } => value.numer().is_some(),
+        ASTNode::Literal { value: Value::String(_) | Value::Bool(_), .. } => true,
+        _ => false,
+    }
+}
+
+/// 演算対象がすべて数値・文字列・真偽値のリテラルなら評価した結果のLiteralに置き換える
+fn fold_if_constant(ast: ASTNode) -> ASTNode {
+    let (is_constant, line, column) = match &ast {
+        ASTNode::BinaryOp { left, right, line, column, .. }
+        | ASTNode::Eq { left, right, line, column }
+        | ASTNode::Gte { left, right, line, column }
+        | ASTNode::Gt { left, right, line, column }
+        | ASTNode::Lte { left, right, line, column }
+        | ASTNode::Lt { left, right, line, column } => (is_constant_literal(left) && is_constant_literal(right), *line, *column),
+        ASTNode::PrefixOp { expr, line, column, .. } => (is_constant_literal(expr), *line, *column),
+        _ => (false, 0, 0),
+    };
+    if !is_constant {
+        return ast;
+    }
+    match eval(ast.clone(), &mut Env::new()) {
+        Ok(value @ (Value::Number(_) | Value::String(_) | Value::Bool(_))) => ASTNode::Literal { value, line, column },
+        _ => ast,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fraction::Fraction;
+    use crate::tokenizer::tokenize;
+    use crate::parsers::Parser;
+    use crate::builtin::register_builtins;
+
+    fn parse(input: &str) -> Vec<ASTNode> {
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        Parser::new(tokens, builtin).parse_lines().unwrap()
+    }
+
+    #[test]
+    fn test_fold_constant_arithmetic() {
+        let ast = fold_constants(parse("2 + 3 * 4").remove(0));
+        assert_eq!(ast, ASTNode::Literal { value: Value::Number(Fraction::from(14)), line: 1, column: 3 });
+    }
+
+    #[test]
+    fn test_fold_constant_in_assignment() {
+        let ast = fold_constants(parse("val x = (1 < 2) and true").remove(0));
+        match ast {
+            ASTNode::Assign { value, .. } => match *value {
+                ASTNode::Literal { value, .. } => assert_eq!(value, Value::Bool(true)),
+                other => panic!("unexpected ast: {:?}", other),
+            },
+            other => panic!("unexpected ast: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_does_not_fold_variables() {
+        let asts = parse("val x = 1\nx + 2 * 3");
+        let ast = fold_constants(asts[1].clone());
+        match ast {
+            ASTNode::BinaryOp { left, right, .. } => {
+                assert!(matches!(*left, ASTNode::Variable { .. }));
+                assert_eq!(*right, ASTNode::Literal { value: Value::Number(Fraction::from(6)), line: 2, column: 7 });
+            }
+            other => panic!("unexpected ast: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_does_not_fold_errors() {
+        let ast = parse("\"a\" - \"b\"").remove(0);
+        assert_eq!(fold_constants(ast.clone()), ast);
+    }
+}
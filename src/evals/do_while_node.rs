@@ -0,0 +1,89 @@
+use crate::ast::ASTNode;
+use crate::value::Value;
+use crate::environment::Env;
+use crate::evals::eval;
+use crate::evals::runtime_error::RuntimeError;
+use crate::evals::for_node::is_loop_target;
+
+/// 本体を少なくとも1回評価し、その後は条件がtrueの間繰り返す
+pub fn do_while_node(body: Box<ASTNode>, condition: Box<ASTNode>, label: Option<String>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    loop {
+        env.count_step(line, column)?;
+        let snapshot = env.enter_unique_scope("do-while");
+        let result = eval(*body.clone(), env);
+        env.restore_scope(snapshot);
+        match result? {
+            Value::Break(target) if is_loop_target(&target, &label) => return Ok(Value::Void),
+            result @ (Value::Return(_) | Value::Break(_)) => return Ok(result),
+            Value::Continue(target) if !is_loop_target(&target, &label) => return Ok(Value::Continue(target)),
+            _ => {}
+        }
+        match eval(*condition.clone(), env)? {
+            Value::Bool(true) => continue,
+            Value::Bool(false) => return Ok(Value::Void),
+            value => return Err(RuntimeError::new(format!("do-while condition must be a bool: {}", value).as_str(), line, column)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fraction::Fraction;
+    use crate::tokenizer::tokenize;
+    use crate::parsers::Parser;
+    use crate::evals::evals;
+    use crate::builtin::register_builtins;
+
+    fn run(input: &str) -> Result<Vec<Value>, RuntimeError> {
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        evals(ast, &mut env)
+    }
+
+    #[test]
+    fn test_do_while_runs_body_once_when_condition_is_false() {
+        let input = r#"
+        val mut count = 0
+        do {
+            count = count + 1
+        } while (false)
+        count
+        "#;
+        let result = run(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(1))));
+    }
+
+    #[test]
+    fn test_do_while_loops_until_condition_is_false() {
+        let input = r#"
+        val mut i = 0
+        val mut sum = 0
+        do {
+            i = i + 1
+            if (i == 2) {
+                continue
+            }
+            if (i == 5) {
+                break
+            }
+            sum = sum + i
+        } while (i < 10)
+        sum
+        "#;
+        let result = run(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(8))));
+    }
+
+    #[test]
+    fn test_do_while_non_bool_condition_is_error() {
+        let input = r#"
+        do {
+            1
+        } while (1)
+        "#;
+        assert_eq!(run(input).unwrap_err().message, "do-while condition must be a bool: 1");
+    }
+}
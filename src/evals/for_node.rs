@@ -4,29 +4,33 @@ use crate::environment::{Env, EnvVariableType};
 use crate::evals::eval;
 use crate::evals::runtime_error::RuntimeError;
 
-pub fn for_node(variable: String, iterable: Box<ASTNode>, body: Box<ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+/// ラベルなしのbreak/continueと、このループのラベルを指定したものだけを処理する
+/// それ以外は外側のループに伝える
+pub fn is_loop_target(target: &Option<String>, label: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+pub fn for_node(variable: String, iterable: Box<ASTNode>, body: Box<ASTNode>, label: Option<String>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
     let iterable = eval(*iterable, env)?;
     match iterable {
         Value::List(values) => {
             let scope_name = format!("for-{}", variable.clone());
             for value in values {
-                env.enter_scope(scope_name.clone());
+                env.count_step(line, column)?;
+                // ループ変数は繰り返しごとのスコープに置き、毎回破棄する
+                let snapshot = env.enter_unique_scope(&scope_name);
                 let _ = env.set(variable.clone(), value.clone(), EnvVariableType::Immutable, value.value_type(), true);
-                let result = eval(*body.clone(), env)?;
-                if let Value::Return(_) = result {
-                    env.leave_scope();
-                    return Ok(result);
-                }
-                if let Value::Break = result {
-                    env.leave_scope();
-                    return Ok(Value::Void);
-                }
-                if let Value::Continue = result {
-                    env.leave_scope();
-                    continue;
+                let result = eval(*body.clone(), env);
+                env.restore_scope(snapshot);
+                let result = result?;
+                match result {
+                    Value::Return(_) => return Ok(result),
+                    Value::Break(ref target) if is_loop_target(target, &label) => return Ok(Value::Void),
+                    Value::Continue(ref target) if is_loop_target(target, &label) => continue,
+                    Value::Break(_) | Value::Continue(_) => return Ok(result),
+                    _ => {}
                 }
             }
-            env.leave_scope();
             Ok(Value::Void)
         }
         _ => Err(RuntimeError::new(format!("Unexpected iterable: {:?}", iterable).as_str(), line, column)),
@@ -51,7 +55,7 @@ mod tests {
         }
         sum
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let mut env = Env::new();
@@ -76,7 +80,7 @@ mod tests {
         }
         value
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let mut env = Env::new();
@@ -100,7 +104,7 @@ mod tests {
         }
         value
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let mut env = Env::new();
@@ -112,4 +116,67 @@ mod tests {
             Value::Number(Fraction::from(2)),
         ]);
     }
+
+    #[test]
+    fn test_for_descending_range() {
+        let input = r#"
+        val mut values = []
+        for i in range(10, 0, 0 - 2) {
+            values.push(i)
+        }
+        values
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let mut env = Env::new();
+        register_builtins(&mut env);
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[2], Value::List(
+            [10, 8, 6, 4, 2].iter().map(|n| Value::Number(Fraction::from(*n))).collect()
+        ));
+    }
+
+    #[test]
+    fn test_for_zero_step_range_is_error() {
+        let input = r#"
+        for i in range(0, 10, 0) {
+            print(i)
+        }
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let mut env = Env::new();
+        register_builtins(&mut env);
+        let error = evals(asts, &mut env).unwrap_err();
+        assert_eq!(error.message, "range step must not be zero");
+    }
+
+    #[test]
+    fn test_labeled_break_terminates_outer_loop() {
+        let input = r#"
+        val mut total = 0
+        outer: for i in [1, 2, 3] {
+            for j in [1, 2, 3] {
+                if (j == 2) {
+                    continue outer
+                }
+                if (i == 3) {
+                    break outer
+                }
+                total = total + i * 10 + j
+            }
+        }
+        total
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let mut env = Env::new();
+        register_builtins(&mut env);
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[1], Value::Void);
+        assert_eq!(result[2], Value::Number(Fraction::from(11 + 21)));
+    }
 }
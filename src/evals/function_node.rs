@@ -1,8 +1,10 @@
 use crate::ast::ASTNode;
 use crate::value::Value;
 use crate::environment::{Env, ValueType, FunctionInfo, EnvVariableType};
-use crate::evals::eval;
+use crate::evals::{eval, eval_spread_elements};
 use crate::evals::runtime_error::RuntimeError;
+use crate::evals::method_call_node::struct_instance_to_string;
+use crate::builtin::{print_values, DEFAULT_PRINT_SEPARATOR, DEFAULT_PRINT_TERMINATOR};
 
 
 pub fn function_node(name: String, arguments: Vec<ASTNode>, body: Box<ASTNode>, return_type: ValueType, _line: usize, _column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
@@ -16,25 +18,55 @@ pub fn function_node(name: String, arguments: Vec<ASTNode>, body: Box<ASTNode>,
     Ok(Value::Function)
 }
 
+/// ブロックの評価
+/// ブロック内で宣言された変数はブロックを抜けると破棄される
 pub fn block_node(statements: Vec<ASTNode>, _line: usize, _column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    let snapshot = env.enter_block_scope();
+    let result = eval_block_statements(statements, env);
+    env.restore_scope(snapshot);
+    result
+}
+
+fn eval_block_statements(statements: Vec<ASTNode>, env: &mut Env) -> Result<Value, RuntimeError> {
     let mut last_value = Value::Void;
     for statement in statements {
         let value = eval(statement, env)?;
         if let Value::Return(v) = &value {
             return Ok(Value::Return(v.clone()));
         }
-        if let Value::Break = value {
-            return Ok(Value::Break);
-        }
-        if let Value::Continue = value {
-            return Ok(Value::Continue);
+        if let Value::Break(_) | Value::Continue(_) = value {
+            return Ok(value);
         }
         last_value = value;
     }
     Ok(last_value)
 }
 
+/// printの名前付き引数sepとendを評価する。指定のないものは既定値(空白と改行)になる
+fn eval_print_options(named_args: Vec<ASTNode>, env: &mut Env) -> Result<(String, String), RuntimeError> {
+    let mut sep = DEFAULT_PRINT_SEPARATOR.to_string();
+    let mut end = DEFAULT_PRINT_TERMINATOR.to_string();
+    for arg in named_args {
+        if let ASTNode::NamedArgument { name, value, line, column } = arg {
+            let option = match name.as_str() {
+                "sep" => &mut sep,
+                "end" => &mut end,
+                _ => return Err(RuntimeError::new(format!("print does not accept the named argument {:?}", name).as_str(), line, column)),
+            };
+            *option = match eval(*value, env)? {
+                Value::String(string) => string,
+                other => return Err(RuntimeError::new(format!("print {} must be a string: {}", name, other).as_str(), line, column)),
+            };
+        }
+    }
+    Ok((sep, end))
+}
+
+/// 関数呼び出し
+/// 引数は値渡しで、リストや辞書も呼び出し先にはコピーが渡される
+/// 呼び出し先は同じ環境に一意なスコープを積んで評価し、戻るときにそのスコープの変数を破棄する
 pub fn function_call_node(name: String, arguments: Box<ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    env.count_step(line, column)?;
     if env.get_function(&name).is_some()
         || env.get_builtin(&name).is_some()
     {
@@ -63,23 +95,40 @@ pub fn function_call_node(name: String, arguments: Box<ASTNode>, line: usize, co
         };
 
         if let Some(func) = function.builtin {
-            let result = func(args_vec.iter().map(|arg| eval(arg.clone(), env)).collect::<Result<Vec<Value>, RuntimeError>>()?);
-            return Ok(result);
+            // printだけはsepとendの名前付き引数を受け付ける
+            let (args_vec, named_args) = if name == "print" {
+                args_vec.into_iter().partition(|arg| !matches!(arg, ASTNode::NamedArgument { .. }))
+            } else {
+                (args_vec, vec![])
+            };
+            let mut args = eval_spread_elements(args_vec, env)?;
+            // printは構造体のto_stringメソッドを優先して表示する
+            if name == "print" {
+                for arg in args.iter_mut() {
+                    if let Value::StructInstance { .. } = arg {
+                        *arg = Value::String(struct_instance_to_string(arg, env, line, column)?);
+                    }
+                }
+            }
+            if !named_args.is_empty() {
+                let (sep, end) = eval_print_options(named_args, env)?;
+                print_values(&args, &sep, &end);
+                return Ok(Value::Void);
+            }
+            return func(args, env).map_err(|e| RuntimeError::from_builtin(e.as_str(), line, column));
         };
 
-        if args_vec.len() != function.arguments.len() {
+        let arg_values = eval_spread_elements(args_vec, env)?;
+        if arg_values.len() != function.arguments.len() {
             return Err(RuntimeError::new("does not match arguments length", line, column));
         }
 
-        let mut local_env = env.clone();
-
-        local_env.enter_scope(name.to_string());
-
-        for (param, arg) in params_vec.iter().zip(&args_vec) {
-            let arg_value = eval(arg.clone(), env)?;
+        env.push_call_frame(&name, line, column);
+        let snapshot = env.enter_frame_scope(&name);
+        for (param, arg_value) in params_vec.iter().zip(arg_values) {
             let name = param.0.to_string();
             let value_type = param.1.clone();
-            let _ = local_env.set(
+            let _ = env.set(
                 name,
                 arg_value,
                 EnvVariableType::Immutable,
@@ -88,11 +137,14 @@ pub fn function_call_node(name: String, arguments: Box<ASTNode>, line: usize, co
             );
         }
 
-
-        let result = eval(function.body.unwrap(), &mut local_env)?;
-        env.update_global_env(&local_env);
-
-        local_env.leave_scope();
+        let mut result = eval(function.body.unwrap(), env);
+        // 最も深い呼び出しでエラーになった時点の呼び出し履歴を残す
+        if let Err(e) = &mut result && e.backtrace.is_empty() {
+            e.backtrace = env.call_stack().to_vec();
+        }
+        env.restore_scope(snapshot);
+        env.pop_call_frame();
+        let result = result?;
         if let Value::Return(v) = result {
             Ok(*v)
         } else {
@@ -124,19 +176,19 @@ pub fn function_call_node(name: String, arguments: Box<ASTNode>, line: usize, co
             _ => return Err(RuntimeError::new(format!("illigal arguments: {:?}", arguments).as_str(), line, column)),
         };
 
-        if args_vec.len() != lambda.0.len() {
+        let arg_values = eval_spread_elements(args_vec, env)?;
+        if arg_values.len() != lambda.0.len() {
             return Err(RuntimeError::new("does not match arguments length", line, column));
         }
 
-        let mut local_env = env.clone();
-
-        local_env.enter_scope(name.to_string());
-
-        for (param, arg) in params_vec.iter().zip(&args_vec) {
-            let arg_value = eval(arg.clone(), env)?;
+        env.push_call_frame(&name, line, column);
+        let snapshot = env.enter_frame_scope(&name);
+        // ラムダの本体からは、呼び出し元ではなく作られた場所の変数が見える
+        env.declare_captured(lambda.2.captured_variables());
+        for (param, arg_value) in params_vec.iter().zip(arg_values) {
             let name = param.0.to_string();
             let value_type = param.1.clone();
-            let _ = local_env.set(
+            let _ = env.set(
                 name,
                 arg_value,
                 EnvVariableType::Immutable,
@@ -145,12 +197,13 @@ pub fn function_call_node(name: String, arguments: Box<ASTNode>, line: usize, co
             );
         }
 
-        let result = eval(*lambda.1, &mut local_env)?;
-
-        env.update_global_env(&local_env);
-
-        env.leave_scope();
-        Ok(result)
+        let mut result = eval(*lambda.1, env);
+        if let Err(e) = &mut result && e.backtrace.is_empty() {
+            e.backtrace = env.call_stack().to_vec();
+        }
+        env.restore_scope(snapshot);
+        env.pop_call_frame();
+        result
     } else {
         Err(RuntimeError::new(format!("Function is missing: {:?}", name).as_str(), line, column))
     }
@@ -38,7 +38,7 @@ mod tests {
             2
         }
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let result = evals(asts, &mut env).unwrap();
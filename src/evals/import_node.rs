@@ -9,7 +9,7 @@ pub fn import_node(module_name: String, symbols: Vec<String>, line: usize, colum
     let module_path = format!("{}.sag", module_name);
     match env.register_module(&module_name, &module_path) {
         Ok(_) => {}
-        Err(e) => return Err(RuntimeError::new(format!("Failed to import module {}: {:?}", module_name, e).as_str(), line, column)),
+        Err(e) => return Err(RuntimeError::new(format!("Failed to import module {}: {}", module_name, e).as_str(), line, column)),
     }
     
     if let Some(module_env) = env.clone().get_module(&module_name) {
@@ -69,7 +69,14 @@ pub fn public_node(node: Box<ASTNode>, line: usize, column: usize, env: &mut Env
             eval(*node, env)?;
             env.register_exported_symbol(name);
         },
-        _ => return Err(RuntimeError::new(format!("Only variables, struct and functions can be exported").as_str(), line, column))
+        // `pub import` は取り込んだシンボルをこのモジュールからも公開する(再エクスポート)
+        ASTNode::Import{symbols, ..} => {
+            eval(*node, env)?;
+            for symbol in symbols {
+                env.register_exported_symbol(symbol);
+            }
+        },
+        _ => return Err(RuntimeError::new(format!("Only variables, struct, functions and imports can be exported").as_str(), line, column))
     }
     Ok(Value::Void)
 }
@@ -107,4 +114,84 @@ mod tests {
         }, true);
         let _ = std::fs::remove_file(file_path);
     }
+
+    #[test]
+    fn test_import_from_package_dir() {
+        use crate::tokenizer::tokenize;
+        use crate::parsers::Parser;
+        use crate::builtin::register_builtins;
+        use crate::evals::evals;
+        use crate::environment::PACKAGE_DIR;
+        use fraction::Fraction;
+
+        // `sag install` で配置されたパッケージを想定してパッケージディレクトリに直接置く
+        std::fs::create_dir_all(PACKAGE_DIR).unwrap();
+        let package_path = std::path::Path::new(PACKAGE_DIR).join("test_pkg_twice.sag");
+        std::fs::write(&package_path, "pub fun twice(x: number): number {\n    return x * 2\n}").unwrap();
+        let input = "import twice from test_pkg_twice\ntwice(21)";
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        let _ = std::fs::remove_file(&package_path);
+        assert_eq!(result.unwrap()[1], Value::Number(Fraction::from(42)));
+    }
+
+    #[test]
+    fn test_pub_import_reexports_symbols() {
+        use crate::tokenizer::tokenize;
+        use crate::parsers::Parser;
+        use crate::builtin::register_builtins;
+        use crate::evals::evals;
+        use fraction::Fraction;
+
+        // base -> facade -> main の3段で、pub importしたものだけがmainから見える
+        std::fs::write("test_reexport_base.sag", "pub fun bar(): number {\n    return 1\n}\npub fun baz(): number {\n    return 2\n}").unwrap();
+        std::fs::write("test_reexport_facade.sag", "pub import bar from test_reexport_base\nimport baz from test_reexport_base").unwrap();
+        let run = |input: &str| {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines().unwrap();
+            evals(ast, &mut env)
+        };
+        let reexported = run("import bar from test_reexport_facade\nbar()");
+        let private = run("import baz from test_reexport_facade");
+        let _ = std::fs::remove_file("test_reexport_base.sag");
+        let _ = std::fs::remove_file("test_reexport_facade.sag");
+        assert_eq!(reexported.unwrap()[1], Value::Number(Fraction::from(1)));
+        assert_eq!(private.unwrap_err().message, "Symbol baz not found in module test_reexport_facade");
+    }
+
+    #[test]
+    fn test_import_missing_module_error() {
+        let mut env = Env::new();
+        let ast = ASTNode::Import {
+            module_name: "test_missing_module".to_string(),
+            symbols: vec!["f".to_string()],
+            line: 3,
+            column: 16,
+        };
+        let error = eval(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "Failed to import module test_missing_module: module not found (searched test_missing_module.sag, ./.sag_packages/test_missing_module.sag)");
+        assert_eq!((error.line, error.column), (3, 16));
+    }
+
+    #[test]
+    fn test_import_tokenize_error_shows_module_source() {
+        let mut env = Env::new();
+        std::fs::write("test_tokenize_error_module.sag", "pub val a = \"abc").unwrap();
+        let ast = ASTNode::Import {
+            module_name: "test_tokenize_error_module".to_string(),
+            symbols: vec!["a".to_string()],
+            line: 1,
+            column: 1,
+        };
+        let error = eval(ast, &mut env).unwrap_err();
+        let _ = std::fs::remove_file("test_tokenize_error_module.sag");
+        assert!(error.message.starts_with("Failed to import module test_tokenize_error_module: Tokenize Error: unterminated string literal\n --> line 1, column 13\n"), "{}", error.message);
+        assert!(error.message.contains("pub val a = \"abc"));
+        assert!(!error.message.contains("TokenizeError {"));
+    }
 }
@@ -1,7 +1,7 @@
 use crate::ast::ASTNode;
 use crate::value::Value;
 use crate::environment::{Env, EnvVariableType, ValueType};
-use crate::evals::eval;
+use crate::evals::{eval, eval_spread_elements, request_exit};
 use crate::evals::runtime_error::RuntimeError;
 
 pub fn lambda_call_node(lambda: Box<ASTNode>, arguments: Vec<ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
@@ -29,19 +29,19 @@ pub fn lambda_call_node(lambda: Box<ASTNode>, arguments: Vec<ASTNode>, line: usi
             }
         }
     }
-    if args_vec.len() != lambda.0.len() {
-        return Err(RuntimeError::new(format!("does not match arguments length: expected {}, got {}", lambda.0.len(), args_vec.len()).as_str(), line, column));
+    let arg_values = eval_spread_elements(args_vec, env)?;
+    if arg_values.len() != lambda.0.len() {
+        return Err(RuntimeError::new(format!("does not match arguments length: expected {}, got {}", lambda.0.len(), arg_values.len()).as_str(), line, column));
     }
 
-    let mut local_env = env.clone();
-
-    local_env.enter_scope("lambda".to_string());
-
-    for (param, arg) in params_vec.iter().zip(&args_vec) {
-        let arg_value = eval(arg.clone(), env)?;
+    // その場で呼び出すラムダは、呼び出し位置の変数を捕捉する
+    let captured = env.captured_variables();
+    let snapshot = env.enter_frame_scope("lambda");
+    env.declare_captured(captured);
+    for (param, arg_value) in params_vec.iter().zip(arg_values) {
         let name = param.0.to_string();
         let value_type = param.1.clone();
-        let _ = local_env.set(
+        let _ = env.set(
             name,
             arg_value,
             EnvVariableType::Immutable,
@@ -50,12 +50,54 @@ pub fn lambda_call_node(lambda: Box<ASTNode>, arguments: Vec<ASTNode>, line: usi
         );
     }
 
-    let result = eval(*lambda.1, &mut local_env);
+    let result = eval(*lambda.1, env);
+    env.restore_scope(snapshot);
+    result
+}
 
-    env.update_global_env(&local_env);
+/// 評価済みの引数でラムダ値を呼び出す(組み込み関数から高階関数を呼ぶときに使う)
+pub fn call_lambda(lambda: &Value, args: Vec<Value>, env: &mut Env) -> Result<Value, String> {
+    let (arguments, body, lambda_env) = match lambda {
+        Value::Lambda { arguments, body, env: lambda_env } => (arguments, body, lambda_env),
+        _ => return Err(format!("expected a lambda but got {}", lambda)),
+    };
+    if args.len() != arguments.len() {
+        return Err(format!("does not match arguments length: expected {}, got {}", arguments.len(), args.len()));
+    }
 
-    env.leave_scope();
-    result
+    let mut params = vec![];
+    for param in arguments {
+        match param {
+            ASTNode::Variable { name, value_type, .. } => params.push((name, value_type)),
+            _ => return Err(format!("illigal param: {:?}", param)),
+        }
+    }
+
+    let snapshot = env.enter_frame_scope("lambda");
+    env.declare_captured(lambda_env.captured_variables());
+    for ((name, value_type), arg_value) in params.into_iter().zip(args) {
+        let _ = env.set(
+            name.to_string(),
+            arg_value,
+            EnvVariableType::Immutable,
+            value_type.clone().unwrap_or(ValueType::Any),
+            true,
+        );
+    }
+
+    let result = eval(*body.clone(), env);
+    env.restore_scope(snapshot);
+    // エラーはメッセージにしてしまうので、exitの終了コードは呼び出し元で取り出せるよう要求し直す
+    let result = result.map_err(|e| {
+        if let Some(code) = e.exit_code {
+            request_exit(code);
+        }
+        e.message
+    })?;
+    match result {
+        Value::Return(v) => Ok(*v),
+        result => Ok(result),
+    }
 }
 
 #[cfg(test)]
@@ -73,10 +115,29 @@ mod tests {
         let input = r#"
         |3, 4| -> \|x: number, y: number| => x + y
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let result = evals(asts, &mut env).unwrap();
         assert_eq!(*result.last().unwrap(), Value::Number(Fraction::from(7)));
     }
+
+    #[test]
+    fn test_lambda_without_type_annotations() {
+        let mut env = Env::new();
+        let input = r#"
+        val add = \|x, y| => x + y
+        val is_positive = \|x| => x > 0
+        add(3, 4)
+        is_positive(0 - 1)
+        |2, 5| -> \|x: number, y| => x * y
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[2], Value::Number(Fraction::from(7)));
+        assert_eq!(result[3], Value::Bool(false));
+        assert_eq!(result[4], Value::Number(Fraction::from(10)));
+    }
 }
@@ -4,175 +4,195 @@ use crate::evals::eval;
 use crate::environment::{Env, EnvVariableType, ValueType};
 use crate::evals::runtime_error::RuntimeError;
 
+/// パターンマッチの評価
+/// 各ケースは専用のスコープで評価し、パターンで束縛した変数はmatchの外に残さない
 pub fn match_node(expression: Box<ASTNode>, cases: Vec<(ASTNode, ASTNode)>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
     let expression_value = eval(*expression.clone(), env)?;
-    let mut count = 0;
-    for (pattern, body) in cases.clone() {
-        env.enter_scope(format!("match-{:?}", count).to_string());
-        count += 1;
-        match pattern {
-            ASTNode::Variable{name, ..} if name == "_" => {
-                env.leave_scope();
-                return Ok(eval(body, env)?);
-            },
-            ASTNode::Literal{value, ..} => {
-                if value == expression_value {
-                    let result = eval(body, env)?;
-                    env.leave_scope();
-                    return Ok(result);
+    for (count, (pattern, body)) in cases.into_iter().enumerate() {
+        let snapshot = env.enter_unique_scope(&format!("match-{:?}", count));
+        let result = match_case(&expression, expression_value.clone(), pattern, body, line, column, env);
+        env.restore_scope(snapshot);
+        if let Some(value) = result? {
+            return Ok(value);
+        }
+    }
+    Err(RuntimeError::new("No match found", line, column))
+}
+
+/// 一つのケースを評価する。パターンに一致しなければNoneを返す
+fn match_case(expression: &ASTNode, expression_value: Value, pattern: ASTNode, body: ASTNode, line: usize, column: usize, env: &mut Env) -> Result<Option<Value>, RuntimeError> {
+    match pattern {
+        ASTNode::Variable{name, ..} if name == "_" => {
+            return Ok(Some(eval(body, env)?));
+        },
+        ASTNode::TypePattern{name, value_type, ..} => {
+            if value_matches_type(&expression_value, &value_type) {
+                if name != "_" {
+                    let _ = env.set(name, expression_value, EnvVariableType::Immutable, value_type, true);
                 }
+                return Ok(Some(eval(body, env)?));
+            }
+        }
+        ASTNode::Literal{value, ..} => {
+            if value == expression_value {
+                let result = eval(body, env)?;
+                return Ok(Some(result));
             }
-            ASTNode::OptionSome{ref value, ..} => {
-                if let Value::Option(Some(ref some_value)) = expression_value {
-                    match value.as_ref() {
-                        ASTNode::Literal{value, ..} => {
-                            if value == some_value.as_ref() {
-                                let result = eval(body, env)?;
-                                env.leave_scope();
-                                return Ok(result);
-                            }
-                        },
-                        ASTNode::Variable{name, ..} => {
-                            let _ = env.set(name.clone(), *some_value.clone(), EnvVariableType::Immutable, some_value.value_type().clone(), true);
+        }
+        ASTNode::OptionSome{ref value, ..} => {
+            if let Value::Option(Some(ref some_value)) = expression_value {
+                match value.as_ref() {
+                    ASTNode::Literal{value, ..} => {
+                        if value == some_value.as_ref() {
                             let result = eval(body, env)?;
-                            env.leave_scope();
-                            return Ok(result);
-                        },
-                        _ => {
-                            return Err(RuntimeError::new("Unsupported pattern", line, column));
+                            return Ok(Some(result));
                         }
+                    },
+                    ASTNode::Variable{name, ..} => {
+                        let _ = env.set(name.clone(), *some_value.clone(), EnvVariableType::Immutable, some_value.value_type().clone(), true);
+                        let result = eval(body, env)?;
+                        return Ok(Some(result));
+                    },
+                    _ => {
+                        return Err(RuntimeError::new("Unsupported pattern", line, column));
                     }
-                    
                 }
+                
             }
-            ASTNode::OptionNone{..} => {
-                if let Value::Option(None) = expression_value {
-                    let result = eval(body, env)?;
-                    env.leave_scope();
-                    return Ok(result);
-                }
+        }
+        ASTNode::OptionNone{..} => {
+            if let Value::Option(None) = expression_value {
+                let result = eval(body, env)?;
+                return Ok(Some(result));
             }
-            ASTNode::ResultSuccess{ref value, ..} => {
-                let value_type = match expression.as_ref() {
-                    ASTNode::Variable { value_type, .. } => {
-                        match value_type {
-                            Some(ValueType::ResultType { success, .. }) => {
-                                success.clone()
-                            }
-                            _ => {
-                                panic!("Value type not found")
-                            }
+        }
+        ASTNode::ResultSuccess{ref value, ..} => {
+            let value_type = match expression {
+                ASTNode::Variable { value_type, .. } => {
+                    match value_type {
+                        Some(ValueType::ResultType { success, .. }) => {
+                            success.clone()
                         }
-                    }
-                    _ => {
-                        panic!("Value type not found");
-                    }
-                };
-                match value.as_ref() {
-                    ASTNode::Variable{name, ..} => {
-                        let expression_value = match expression_value {
-                            Value::Result(Ok(ref some_value)) => {
-                                some_value.clone()
-                            }
-                            Value::Result(Err(ref some_value)) => {
-                                some_value.clone()
-                            }
-                            _ => {
-                                return Err(RuntimeError::new("Unsupported pattern", line, column));
-                            }
-                        };
-                        if expression_value.value_type() != *value_type {
-                            continue;
+                        _ => {
+                            panic!("Value type not found")
                         }
-                        let _ = env.set(name.clone(), *expression_value.clone(), EnvVariableType::Immutable, *value_type.clone(), true);
-                        let result = eval(body, env)?;
-                        env.leave_scope();
-                        return Ok(result);
-                    }
-                    _ => {
-                        println!("not expected pattern: {:?}", value);
                     }
                 }
-                match expression_value {
-                    Value::Result(Ok(ref some_value)) => {
-                        let evaluated_value = eval(*value.clone(), env)?;
-                        if evaluated_value == *some_value.clone() {
-                            let result = eval(body, env)?;
-                            env.leave_scope();
-                            return Ok(result);
+                _ => {
+                    panic!("Value type not found");
+                }
+            };
+            match value.as_ref() {
+                ASTNode::Variable{name, ..} => {
+                    let expression_value = match expression_value {
+                        Value::Result(Ok(ref some_value)) => {
+                            some_value.clone()
                         }
+                        Value::Result(Err(ref some_value)) => {
+                            some_value.clone()
+                        }
+                        _ => {
+                            return Err(RuntimeError::new("Unsupported pattern", line, column));
+                        }
+                    };
+                    if expression_value.value_type() != *value_type {
+                        return Ok(None);
                     }
-                    _ => {
-                        println!("Pattern: {:?}", value);
-                    }
+                    let _ = env.set(name.clone(), *expression_value.clone(), EnvVariableType::Immutable, *value_type.clone(), true);
+                    let result = eval(body, env)?;
+                    return Ok(Some(result));
+                }
+                _ => {
+                    println!("not expected pattern: {:?}", value);
                 }
             }
-            ASTNode::ResultFailure { ref value, .. } => {
-                let value_type = match expression.as_ref() {
-                    ASTNode::Variable { value_type, .. } => {
-                        match value_type {
-                            Some(ValueType::ResultType { failure, .. }) => {
-                                failure.clone()
-                            }
-                            _ => {
-                                panic!("Failure value type not found");
-                            }
-                        }
-                    }
-                    _ => {
-                        panic!("Value type not found");
-                    }
-                };
-            
-                match value.as_ref() {
-                    ASTNode::Variable { name, .. } => {
-                        let expression_value = match expression_value {
-                            Value::Result(Err(ref some_value)) => {
-                                some_value.clone()
-                            }
-                            Value::Result(Ok(ref some_value)) => {
-                                some_value.clone()
-                            }
-                            _ => {
-                                return Err(RuntimeError::new("Unsupported pattern in ResultFailure", line, column));
-                            }
-                        };
-            
-                        if expression_value.value_type() != *value_type {
-                            continue;
-                        }  
-                        let _ = env.set(name.clone(), *expression_value.clone(), EnvVariableType::Immutable, *value_type.clone(), true);
+            match expression_value {
+                Value::Result(Ok(ref some_value)) => {
+                    let evaluated_value = eval(*value.clone(), env)?;
+                    if evaluated_value == *some_value.clone() {
                         let result = eval(body, env)?;
-                        env.leave_scope();
-                        return Ok(result);
-                    }
-                    _ => {
-                        println!("Unexpected pattern in ResultFailure: {:?}", value);
+                        return Ok(Some(result));
                     }
                 }
-            
-                match expression_value {
-                    Value::Result(Err(ref some_value)) => {
-                        let evaluated_value = eval(*value.clone(), env)?;
-                        if evaluated_value == *some_value.clone() {
-                            let result = eval(body, env)?;
-                            env.leave_scope();
-                            return Ok(result);
+                _ => {
+                    println!("Pattern: {:?}", value);
+                }
+            }
+        }
+        ASTNode::ResultFailure { ref value, .. } => {
+            let value_type = match expression {
+                ASTNode::Variable { value_type, .. } => {
+                    match value_type {
+                        Some(ValueType::ResultType { failure, .. }) => {
+                            failure.clone()
+                        }
+                        _ => {
+                            panic!("Failure value type not found");
                         }
                     }
-                    _ => {
-                        println!("Pattern did not match Err: {:?}", expression_value);
+                }
+                _ => {
+                    panic!("Value type not found");
+                }
+            };
+        
+            match value.as_ref() {
+                ASTNode::Variable { name, .. } => {
+                    let expression_value = match expression_value {
+                        Value::Result(Err(ref some_value)) => {
+                            some_value.clone()
+                        }
+                        Value::Result(Ok(ref some_value)) => {
+                            some_value.clone()
+                        }
+                        _ => {
+                            return Err(RuntimeError::new("Unsupported pattern in ResultFailure", line, column));
+                        }
+                    };
+        
+                    if expression_value.value_type() != *value_type {
+                        return Ok(None);
+                    }  
+                    let _ = env.set(name.clone(), *expression_value.clone(), EnvVariableType::Immutable, *value_type.clone(), true);
+                    let result = eval(body, env)?;
+                    return Ok(Some(result));
+                }
+                _ => {
+                    println!("Unexpected pattern in ResultFailure: {:?}", value);
+                }
+            }
+        
+            match expression_value {
+                Value::Result(Err(ref some_value)) => {
+                    let evaluated_value = eval(*value.clone(), env)?;
+                    if evaluated_value == *some_value.clone() {
+                        let result = eval(body, env)?;
+                        return Ok(Some(result));
                     }
                 }
+                _ => {
+                    println!("Pattern did not match Err: {:?}", expression_value);
+                }
             }
-            _ => {
+        }
+        _ => {
 
-                println!("Pattern");
-            }
+            println!("Pattern");
         }
-        env.leave_scope();
     }
-    Err(RuntimeError::new("No match found", line, column))
+    Ok(None)
+}
+
+/// 値の実行時の型が型パターンに一致するか
+fn value_matches_type(value: &Value, value_type: &ValueType) -> bool {
+    match (value, value_type) {
+        (_, ValueType::Any) => true,
+        (Value::List(values), ValueType::List(element_type)) => {
+            values.iter().all(|value| value_matches_type(value, element_type))
+        }
+        (Value::StructInstance { name, .. }, ValueType::Struct { name: struct_name, .. }) => name == struct_name,
+        _ => value.value_type() == *value_type,
+    }
 }
 
 #[cfg(test)]
@@ -193,7 +213,7 @@ mod tests {
         }
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse().unwrap();
         let result = eval(ast, &mut env).unwrap();
@@ -205,7 +225,7 @@ mod tests {
             _ => { 3 }
         }
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse().unwrap();
         let result = eval(ast, &mut env).unwrap();
@@ -216,7 +236,7 @@ mod tests {
             _ => { 3 }
         }
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse().unwrap();
         let result = eval(ast, &mut env).unwrap();
@@ -229,7 +249,7 @@ mod tests {
             _ => { 4 }
         }
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let result = evals(ast, &mut env).unwrap();
@@ -241,10 +261,99 @@ mod tests {
             _ => { 4 }
         }
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse().unwrap();
         let result = eval(ast, &mut env).unwrap();
         assert_eq!(result, Value::Number(Fraction::from(3)));
     }
+
+    #[test]
+    fn test_match_binding_does_not_leak() {
+        let input = r#"
+        val x: Option<number> = Some(2)
+        match x {
+            Some(inner) => { inner + 10 }
+            None => { 3 }
+        }
+        match x {
+            Some(other) => { inner }
+            None => { 3 }
+        }
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "Variable not found: \"inner\"");
+    }
+
+    fn eval_type_match(subject: &str) -> Value {
+        let input = format!(r#"
+        val values = [1, "two", true]
+        match {} {{
+            n: number => {{ "number " + n.to_string() }}
+            _: string => {{ "string" }}
+            _ => {{ "other" }}
+        }}
+        "#, subject);
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        evals(ast, &mut env).unwrap().pop().unwrap()
+    }
+
+    #[test]
+    fn test_match_type_pattern() {
+        assert_eq!(eval_type_match("values[0]"), Value::String("number 1".into()));
+        assert_eq!(eval_type_match("values[1]"), Value::String("string".into()));
+        assert_eq!(eval_type_match("values[2]"), Value::String("other".into()));
+    }
+
+    #[test]
+    fn test_match_type_pattern_with_literal_pattern() {
+        let input = r#"
+        val values = [0, 5, "zero"]
+        val describe = \|value| => match value {
+            0 => { "zero" }
+            _: number => { "number" }
+            _: string => { "string" }
+        }
+        map(values, describe)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::List(vec![
+            Value::String("zero".into()),
+            Value::String("number".into()),
+            Value::String("string".into()),
+        ])));
+    }
+
+    #[test]
+    fn test_match_negative_literal_pattern() {
+        let input = r#"
+        val describe = \|n| => match n {
+            -1 => { "minus one" }
+            0 => { "zero" }
+            _ => { "other" }
+        }
+        map([0 - 1, 0, 1], describe)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::List(vec![
+            Value::String("minus one".into()),
+            Value::String("zero".into()),
+            Value::String("other".into()),
+        ])));
+    }
 }
@@ -1,10 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+use indexmap::IndexMap;
 use crate::ast::ASTNode;
 use crate::value::Value;
 use crate::environment::{Env, ValueType, EnvVariableType};
 use crate::evals::eval;
 use crate::evals::runtime_error::RuntimeError;
-use fraction::Fraction;
+use crate::evals::assign_node::check_dict_value_type;
+use crate::builtin::{map_list, filter_list, count_list, partition_list};
+use crate::evals::lambda_node::call_lambda;
+use fraction::{CheckedDiv, CheckedMul, Fraction};
+
+// set_atで範囲外の添字を埋めるときの上限。巨大な添字でメモリを使い切らないようにする
+const SET_AT_MAX_INDEX: usize = 10_000_000;
 
 fn extract_arguments(arguments: Box<ASTNode>) -> Vec<ASTNode> {
     match *arguments {
@@ -13,23 +21,249 @@ fn extract_arguments(arguments: Box<ASTNode>) -> Vec<ASTNode> {
     }
 }
 
+/// 分数を小数点以下digits桁の文字列に変換する
+/// 丸めは四捨五入(0.5は絶対値が大きくなる方向に丸める)で、分数のまま正確に計算する
+fn fraction_to_fixed(num: &Fraction, digits: u32) -> Option<String> {
+    let numer = *num.numer()? as u128;
+    let denom = *num.denom()? as u128;
+    let scale = 10u128.checked_pow(digits)?;
+    let scaled = numer.checked_mul(scale)?;
+    let mut quotient = scaled / denom;
+    if (scaled % denom) * 2 >= denom {
+        quotient += 1;
+    }
+    let is_negative = num.is_sign_negative() && quotient != 0;
+    let integer_part = quotient / scale;
+    let mut result = format!("{}{}", if is_negative { "-" } else { "" }, integer_part);
+    if digits > 0 {
+        let fractional_part = quotient % scale;
+        result.push_str(&format!(".{:0width$}", fractional_part, width = digits as usize));
+    }
+    Some(result)
+}
+
+/// "0.12345678901234567890123"のように分母がu64に収まらない小数を、収まる桁数で四捨五入して読む
+fn parse_rounded_decimal(string: &str) -> Option<Fraction> {
+    let (is_negative, digits) = match string.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, string.strip_prefix('+').unwrap_or(string)),
+    };
+    let (integer_part, fractional_part) = digits.split_once('.')?;
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if integer_part.is_empty() && fractional_part.is_empty() || !is_digits(integer_part) || !is_digits(fractional_part) {
+        return None;
+    }
+    // 小数部を1桁ずつ減らし、分子と分母の両方がu64に収まる最大の桁数を探す
+    for kept in (0..fractional_part.len()).rev() {
+        let denominator = 10u64.checked_pow(kept as u32);
+        let numerator = format!("{}{}", integer_part, &fractional_part[..kept]).parse::<u64>().ok();
+        let round_up = fractional_part.as_bytes()[kept] >= b'5';
+        let numerator = numerator.and_then(|numerator| numerator.checked_add(u64::from(round_up)));
+        if let (Some(numerator), Some(denominator)) = (numerator, denominator) {
+            let number = Fraction::new(numerator, denominator);
+            return Some(if is_negative { -number } else { number });
+        }
+    }
+    None
+}
+
+/// 範囲指定の位置を0..=lenの範囲に変換する
+/// 負の値は末尾から数え、範囲外の値は先頭か末尾に切り詰める
+fn clamp_slice_index(index: &Fraction, len: usize) -> usize {
+    let offset = index.numer().map(|n| *n as usize).unwrap_or(usize::MAX);
+    if index.is_sign_negative() {
+        len.saturating_sub(offset)
+    } else {
+        offset.min(len)
+    }
+}
+
+/// 並べ替えや最大・最小の判定に使う比較。数値同士と文字列同士だけを比較できる
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Result<std::cmp::Ordering, String> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b).ok_or(format!("cannot compare {} and {}", a, b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        _ => Err(format!("cannot compare {} and {}", a, b)),
+    }
+}
+
+/// 数値だけ、または文字列だけのリストを昇順に並べ替える(同じ値の順序は保つ)
+/// 型が混ざっていれば最初に型が変わった位置と両方の型をエラーで示す
+pub(crate) fn sort_values(mut list: Vec<Value>) -> Result<Vec<Value>, String> {
+    let kind = |value: &Value| match value {
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::List(_) => "list",
+        Value::Dict(_) => "dict",
+        Value::Option(_) => "option",
+        _ => "other",
+    };
+    if let Some(first) = list.first() {
+        let first_kind = kind(first);
+        if first_kind != "number" && first_kind != "string" {
+            return Err(format!("cannot sort a list of {}: only numbers and strings can be sorted", first_kind));
+        }
+        if let Some((index, value)) = list.iter().enumerate().find(|(_, value)| kind(value) != first_kind) {
+            return Err(format!(
+                "cannot sort a list mixing {} and {}: found {} at index {}",
+                first_kind, kind(value), kind(value), index
+            ));
+        }
+    }
+    list.sort_by(|a, b| compare_values(a, b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(list)
+}
+
 // number builtin method
 fn call_builtin_method_on_number(
     num: Fraction,
     method_name: &str,
-    _args: &[ASTNode],
+    args: &[ASTNode],
+    env: &mut Env,
     line: usize,
     column: usize,
 ) -> Result<Value, RuntimeError> {
     match method_name {
-        "to_string" => Ok(Value::String(num.to_string())),
-        "round" => Ok(Value::Number(num.round().into())),
+        "to_string" => {
+            if args.is_empty() {
+                return Ok(Value::String(num.to_string()));
+            }
+            let radix = match eval(args[0].clone(), env)? {
+                Value::Number(radix) if radix.denom() == Some(&1) && !radix.is_sign_negative() && (2..=36).contains(radix.numer().unwrap()) => *radix.numer().unwrap() as u32,
+                other => return Err(RuntimeError::new(format!("to_string radix must be an integer from 2 to 36: {}", other).as_str(), line, column)),
+            };
+            if num.denom() != Some(&1) {
+                return Err(RuntimeError::new(format!("to_string with a radix requires an integer: {}", num).as_str(), line, column));
+            }
+            // 負の整数は符号を付けて絶対値を変換する(to_hexとは異なり2の補数にはしない)
+            let mut integer = *num.numer().unwrap();
+            let mut digits = vec![];
+            loop {
+                digits.push(std::char::from_digit((integer % radix as u64) as u32, radix).unwrap());
+                integer /= radix as u64;
+                if integer == 0 {
+                    break;
+                }
+            }
+            if num.is_sign_negative() && num != Fraction::from(0) {
+                digits.push('-');
+            }
+            Ok(Value::String(digits.into_iter().rev().collect()))
+        }
+        "to_fixed" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("to_fixed requires a digits argument", line, column));
+            }
+            let digits = match eval(args[0].clone(), env)? {
+                Value::Number(digits) => digits,
+                _ => return Err(RuntimeError::new("to_fixed argument must be a number", line, column)),
+            };
+            if digits.is_sign_negative() || digits.fract() != Fraction::from(0) {
+                return Err(RuntimeError::new(
+                    format!("to_fixed digits must be a non-negative integer: {}", digits).as_str(),
+                    line,
+                    column,
+                ));
+            }
+            let fixed = u32::try_from(*digits.numer().unwrap())
+                .ok()
+                .and_then(|digits| fraction_to_fixed(&num, digits));
+            match fixed {
+                Some(fixed) => Ok(Value::String(fixed)),
+                None => Err(RuntimeError::new(
+                    format!("to_fixed cannot format {} with {} digits", num, digits).as_str(),
+                    line,
+                    column,
+                )),
+            }
+        }
+        "to_hex" | "to_binary" => {
+            // 負数と非整数は2の補数などの表現を選ばずにエラーとする
+            if num.is_sign_negative() || num.fract() != Fraction::from(0) {
+                return Err(RuntimeError::new(
+                    format!("{} requires a non-negative integer: {}", method_name, num).as_str(),
+                    line,
+                    column,
+                ));
+            }
+            let integer = *num.numer().unwrap();
+            match method_name {
+                "to_hex" => Ok(Value::String(format!("{:x}", integer))),
+                _ => Ok(Value::String(format!("{:b}", integer))),
+            }
+        }
+        "round" => {
+            if args.is_empty() {
+                return Ok(Value::Number(num.round()));
+            }
+            let digits = match eval(args[0].clone(), env)? {
+                Value::Number(digits) if digits.denom() == Some(&1) => digits,
+                other => return Err(RuntimeError::new(format!("round digits must be an integer: {}", other).as_str(), line, column)),
+            };
+            // 10の累乗倍して整数に丸めてから戻す。負の桁数は10の位、100の位に丸める
+            let scale = u32::try_from(*digits.numer().unwrap()).ok()
+                .and_then(|exponent| 10u64.checked_pow(exponent))
+                .map(Fraction::from);
+            let scale = match scale {
+                Some(scale) => scale,
+                None => return Err(RuntimeError::new(format!("round digits is too large: {}", digits).as_str(), line, column)),
+            };
+            // 途中の掛け算・割り算も分数の内部表現(u64)で溢れうるので検査する
+            let rounded = if digits.is_sign_negative() {
+                num.checked_div(&scale).and_then(|scaled| scaled.round().checked_mul(&scale))
+            } else {
+                num.checked_mul(&scale).and_then(|scaled| scaled.round().checked_div(&scale))
+            };
+            match rounded {
+                Some(rounded) => Ok(Value::Number(rounded)),
+                None => Err(RuntimeError::new(format!("round digits too large for {}", num).as_str(), line, column)),
+            }
+        }
+        "clamp" => {
+            if args.len() != 2 {
+                return Err(RuntimeError::new("clamp requires lower and upper bound arguments", line, column));
+            }
+            let (lo, hi) = match (eval(args[0].clone(), env)?, eval(args[1].clone(), env)?) {
+                (Value::Number(lo), Value::Number(hi)) => (lo, hi),
+                _ => return Err(RuntimeError::new("clamp bounds must be numbers", line, column)),
+            };
+            // 範囲の指定ミスを黙って丸めないよう、clamp組み込み関数と同じくエラーにする
+            if lo > hi {
+                return Err(RuntimeError::new(
+                    format!("clamp lower bound {} is greater than upper bound {}", lo, hi).as_str(),
+                    line,
+                    column,
+                ));
+            }
+            Ok(Value::Number(num.max(lo).min(hi)))
+        }
+        // 算術演算では暗黙に変換しないので、真偽値として使うときは明示的に呼ぶ
+        "to_bool" => Ok(Value::Bool(num != Fraction::from(0))),
+        // 既約分数の分子と分母を返す。符号は分子に付ける
+        "numerator" | "denominator" => match (num.numer(), num.denom()) {
+            (Some(&numer), Some(&denom)) => match method_name {
+                "numerator" if num.is_sign_negative() && numer != 0 => Ok(Value::Number(-Fraction::from(numer))),
+                "numerator" => Ok(Value::Number(Fraction::from(numer))),
+                _ => Ok(Value::Number(Fraction::from(denom))),
+            },
+            _ => Err(RuntimeError::new(format!("{} is not defined for {}", method_name, num).as_str(), line, column)),
+        },
         "sqrt" => {
-            let num_f64 = *num.numer().unwrap() as f64;
-            let denom_f64 = *num.denom().unwrap() as f64;
-            let fraction_value = num_f64 / denom_f64;
-            let sqrt_value = fraction_value.sqrt();
-            Ok(Value::Number(sqrt_value.into()))
+            let (numer, denom) = match (num.numer(), num.denom()) {
+                (Some(&numer), Some(&denom)) => (numer, denom),
+                _ => return Err(RuntimeError::new(format!("sqrt requires a finite number: {}", num).as_str(), line, column)),
+            };
+            // NaNを返さないよう、負数はエラーにする(-0は0として扱う)
+            if num.is_sign_negative() && numer != 0 {
+                return Err(RuntimeError::new(format!("sqrt is not defined for negative numbers: {}", num).as_str(), line, column));
+            }
+            // 分子と分母がどちらも平方数なら正確な分数を返し、そうでなければ64bit浮動小数点数で近似する
+            let (numer_root, denom_root) = (numer.isqrt(), denom.isqrt());
+            if numer_root * numer_root == numer && denom_root * denom_root == denom {
+                return Ok(Value::Number(Fraction::new(numer_root, denom_root)));
+            }
+            Ok(Value::Number((numer as f64 / denom as f64).sqrt().into()))
         },
         _ => Err(RuntimeError::new(
             format!("{} is not a method of number", method_name).as_str(),
@@ -39,6 +273,42 @@ fn call_builtin_method_on_number(
     }
 }
 
+/// リストのリストを1段階だけ平坦化する
+fn flatten_list(list: Vec<Value>) -> Result<Value, String> {
+    let mut result = vec![];
+    for value in list {
+        match value {
+            Value::List(inner) => result.extend(inner),
+            other => return Err(format!("flatten requires a list of lists but found {}", other)),
+        }
+    }
+    Ok(Value::List(result))
+}
+
+/// 変数に対して呼ばれた辞書の変更メソッドで書き込む値が、変数の型Dict<string, T>に合うかを確かめる
+fn check_dict_variable_value(caller_ast: &ASTNode, key: &str, value: &Value, env: &Env, line: usize, column: usize) -> Result<(), RuntimeError> {
+    if let ASTNode::Variable { name, .. } = caller_ast
+        && let Some(var_info) = env.get(name, None)
+    {
+        check_dict_value_type(&var_info.value_type, key, value, line, column)?;
+    }
+    Ok(())
+}
+
+/// 変数に対して呼ばれたリストの変更メソッドの結果を、その変数に書き戻す
+fn update_list_variable(caller_ast: &ASTNode, list: Vec<Value>, env: &mut Env, line: usize, column: usize) -> Result<(), RuntimeError> {
+    if let ASTNode::Variable { name, value_type, .. } = caller_ast {
+        env.set(
+            name.to_string(),
+            Value::List(list),
+            EnvVariableType::Mutable,
+            value_type.clone().unwrap_or(ValueType::Any),
+            false,
+        ).map_err(|e| RuntimeError::new(e.as_str(), line, column))?;
+    }
+    Ok(())
+}
+
 // list builtin method
 fn call_builtin_method_on_list(
     mut list: Vec<Value>,
@@ -88,9 +358,79 @@ fn call_builtin_method_on_list(
             }
             Ok(Value::Option(popped.map(Box::new)))
         }
+        // xs[i] = vと違い、iが範囲外ならfillで埋めてリストを伸ばしてから代入する
+        "set_at" => {
+            if args.len() != 3 {
+                return Err(RuntimeError::new("set_at requires index, value and fill arguments", line, column));
+            }
+            let index = match eval(args[0].clone(), env)? {
+                Value::Number(index) if index.denom() == Some(&1) && !index.is_sign_negative() => *index.numer().unwrap() as usize,
+                Value::Number(index) if index == Fraction::from(0) => 0,
+                other => return Err(RuntimeError::new(format!("set_at index must be a non-negative integer: {}", other).as_str(), line, column)),
+            };
+            let new_val = eval(args[1].clone(), env)?;
+            let fill = eval(args[2].clone(), env)?;
+            if index >= list.len() {
+                if index > SET_AT_MAX_INDEX {
+                    return Err(RuntimeError::new(
+                        format!("set_at index {} is too large (at most {})", index, SET_AT_MAX_INDEX).as_str(),
+                        line,
+                        column,
+                    ));
+                }
+                list.resize(index + 1, fill);
+            }
+            list[index] = new_val;
+            update_list_variable(caller_ast, list, env, line, column)?;
+            Ok(Value::Void)
+        }
+        "insert" => {
+            if args.len() != 2 {
+                return Err(RuntimeError::new("insert requires index and value arguments", line, column));
+            }
+            let index = match eval(args[0].clone(), env)? {
+                Value::Number(index) if index.denom() == Some(&1) && !index.is_sign_negative() => *index.numer().unwrap() as usize,
+                Value::Number(index) if index == Fraction::from(0) => 0,
+                other => return Err(RuntimeError::new(format!("insert index must be a non-negative integer: {}", other).as_str(), line, column)),
+            };
+            // 末尾(index == len)への挿入はpushと同じになる
+            if index > list.len() {
+                return Err(RuntimeError::new(
+                    format!("insert index {} is out of range for a list of length {}", index, list.len()).as_str(),
+                    line,
+                    column,
+                ));
+            }
+            let new_val = eval(args[1].clone(), env)?;
+            list.insert(index, new_val);
+            update_list_variable(caller_ast, list, env, line, column)?;
+            Ok(Value::Void)
+        }
+        // 範囲外のインデックスはエラーにせずNoneを返す
+        "remove_at" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("remove_at requires an index argument", line, column));
+            }
+            let index = match eval(args[0].clone(), env)? {
+                Value::Number(index) if index.denom() == Some(&1) => index,
+                other => return Err(RuntimeError::new(format!("remove_at index must be an integer: {}", other).as_str(), line, column)),
+            };
+            let index = match index.numer() {
+                Some(numer) if !index.is_sign_negative() || index == Fraction::from(0) => *numer as usize,
+                _ => return Ok(Value::Option(None)),
+            };
+            if index >= list.len() {
+                return Ok(Value::Option(None));
+            }
+            let removed = list.remove(index);
+            update_list_variable(caller_ast, list, env, line, column)?;
+            Ok(Value::Option(Some(Box::new(removed))))
+        }
         "len" => Ok(Value::Number(Fraction::from(list.len()))),
         "is_empty" => Ok(Value::Bool(list.is_empty())),
-        "first" => Ok(Value::Option(list.first().cloned().map(Box::new))),
+        "first" | "head" => Ok(Value::Option(list.first().cloned().map(Box::new))),
+        // 空のリストのtailは空のリストになる
+        "tail" => Ok(Value::List(list.iter().skip(1).cloned().collect())),
         "last" => Ok(Value::Option(list.last().cloned().map(Box::new))),
         "clear" => {
             list.clear();
@@ -115,6 +455,142 @@ fn call_builtin_method_on_list(
             let search_val = eval(args[0].clone(), env)?;
             Ok(Value::Bool(list.contains(&search_val)))
         }
+        "map" | "filter" | "count" | "partition" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new(format!("{} requires a lambda argument", method_name).as_str(), line, column));
+            }
+            let f = eval(args[0].clone(), env)?;
+            let result = match method_name {
+                "map" => map_list(&list, &f, env),
+                "filter" => filter_list(&list, &f, env),
+                "partition" => partition_list(&list, &f, env),
+                _ => count_list(&list, &f, env),
+            };
+            result.map_err(|e| RuntimeError::from_builtin(e.as_str(), line, column))
+        }
+        "take" | "drop" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new(format!("{} requires a count argument", method_name).as_str(), line, column));
+            }
+            let count = match eval(args[0].clone(), env)? {
+                Value::Number(count) => count,
+                _ => return Err(RuntimeError::new(format!("{} count must be a number", method_name).as_str(), line, column)),
+            };
+            if count.is_sign_negative() && count != Fraction::from(0) {
+                return Err(RuntimeError::new(format!("{} count must not be negative: {}", method_name, count).as_str(), line, column));
+            }
+            let count = count.floor().numer().map(|n| *n as usize).unwrap_or(usize::MAX);
+            // 件数が長さを超えるときは take も drop も空リストを返す
+            if count > list.len() {
+                return Ok(Value::List(vec![]));
+            }
+            if method_name == "take" {
+                Ok(Value::List(list[..count].to_vec()))
+            } else {
+                Ok(Value::List(list[count..].to_vec()))
+            }
+        }
+        "max_by" | "min_by" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new(format!("{} requires a lambda argument", method_name).as_str(), line, column));
+            }
+            let f = eval(args[0].clone(), env)?;
+            // キーが同じ要素が複数あるときは最初の要素を返す
+            let wanted = if method_name == "max_by" { std::cmp::Ordering::Greater } else { std::cmp::Ordering::Less };
+            let mut best: Option<(Value, Value)> = None;
+            for value in list {
+                let key = call_lambda(&f, vec![value.clone()], env).map_err(|e| RuntimeError::from_builtin(e.as_str(), line, column))?;
+                let is_better = match &best {
+                    Some((_, best_key)) => compare_values(&key, best_key).map_err(|e| RuntimeError::new(e.as_str(), line, column))? == wanted,
+                    None => true,
+                };
+                if is_better {
+                    best = Some((value, key));
+                }
+            }
+            Ok(Value::Option(best.map(|(value, _)| Box::new(value))))
+        }
+        "sum" | "product" | "average" => {
+            let mut numbers = vec![];
+            for value in &list {
+                match value {
+                    Value::Number(n) => numbers.push(*n),
+                    other => return Err(RuntimeError::new(format!("{} requires a list of numbers but found {}", method_name, other).as_str(), line, column)),
+                }
+            }
+            match method_name {
+                "sum" => Ok(Value::Number(numbers.into_iter().fold(Fraction::from(0), |acc, n| acc + n))),
+                "product" => Ok(Value::Number(numbers.into_iter().fold(Fraction::from(1), |acc, n| acc * n))),
+                _ => {
+                    if numbers.is_empty() {
+                        return Err(RuntimeError::new("average of an empty list", line, column));
+                    }
+                    let count = Fraction::from(numbers.len());
+                    Ok(Value::Number(numbers.into_iter().fold(Fraction::from(0), |acc, n| acc + n) / count))
+                }
+            }
+        }
+        "chunk" | "window" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new(format!("{} requires a size argument", method_name).as_str(), line, column));
+            }
+            let size = match eval(args[0].clone(), env)? {
+                Value::Number(n) if n > Fraction::from(0) && n.denom() == Some(&1) => *n.numer().unwrap() as usize,
+                other => return Err(RuntimeError::new(format!("{} size must be a positive integer: {}", method_name, other).as_str(), line, column)),
+            };
+            // chunkは重ならない区切りで最後だけ短くなり得る。windowは隣り合う要素を1つずつずらして取る
+            let parts: Vec<Value> = if method_name == "chunk" {
+                list.chunks(size).map(|part| Value::List(part.to_vec())).collect()
+            } else {
+                list.windows(size).map(|part| Value::List(part.to_vec())).collect()
+            };
+            Ok(Value::List(parts))
+        }
+        "slice" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(RuntimeError::new("slice requires start and optional end arguments", line, column));
+            }
+            let mut indices = vec![];
+            for arg in args {
+                match eval(arg.clone(), env)? {
+                    Value::Number(n) if n.denom() == Some(&1) => indices.push(n),
+                    other => return Err(RuntimeError::new(format!("slice index must be an integer: {}", other).as_str(), line, column)),
+                }
+            }
+            let start = clamp_slice_index(&indices[0], list.len());
+            let end = indices.get(1).map(|end| clamp_slice_index(end, list.len())).unwrap_or(list.len());
+            if start >= end {
+                return Ok(Value::List(vec![]));
+            }
+            Ok(Value::List(list[start..end].to_vec()))
+        }
+        "sort" => sort_values(list).map(Value::List).map_err(|e| RuntimeError::new(e.as_str(), line, column)),
+        "unique" => {
+            // ラムダなどハッシュできない値は、リストや辞書の中に含まれていても重複除去の対象外としてエラーにする
+            for value in &list {
+                value.check_hashable()
+                    .map_err(|e| RuntimeError::new(format!("unique cannot compare values: {}", e).as_str(), line, column))?;
+            }
+            // ラムダが持つ環境のステップ数は可変だが、ラムダは上で弾いているのでキーのハッシュには関わらない
+            #[allow(clippy::mutable_key_type)]
+            let mut seen = HashSet::new();
+            let mut unique_values: Vec<Value> = vec![];
+            for value in list {
+                if seen.insert(value.clone()) {
+                    unique_values.push(value);
+                }
+            }
+            Ok(Value::List(unique_values))
+        }
+        "flatten" => flatten_list(list).map_err(|e| RuntimeError::new(e.as_str(), line, column)),
+        "flat_map" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("flat_map requires a lambda argument", line, column));
+            }
+            let f = eval(args[0].clone(), env)?;
+            let mapped = map_list(&list, &f, env).map_err(|e| RuntimeError::from_builtin(e.as_str(), line, column))?;
+            flatten_list(mapped.to_list()).map_err(|e| RuntimeError::new(e.as_str(), line, column))
+        }
         "reverse" => {
             list.reverse();
             if let ASTNode::Variable { name, value_type, .. } = caller_ast {
@@ -141,7 +617,7 @@ fn call_builtin_method_on_list(
 
 // dict builtin method
 fn call_builtin_method_on_dict(
-    mut dict: HashMap<String, Value>,
+    mut dict: IndexMap<String, Value>,
     method_name: &str,
     args: &[ASTNode],
     caller_ast: &ASTNode,
@@ -161,6 +637,19 @@ fn call_builtin_method_on_dict(
                 Err(RuntimeError::new("dict key must be a string", line, column))
             }
         }
+        "get_or" => {
+            if args.len() != 2 {
+                return Err(RuntimeError::new("get_or requires key and default arguments", line, column));
+            }
+            let key = match eval(args[0].clone(), env)? {
+                Value::String(key) => key,
+                _ => return Err(RuntimeError::new("dict key must be a string", line, column)),
+            };
+            match dict.get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => eval(args[1].clone(), env),
+            }
+        }
         "insert" => {
             if args.len() < 2 {
                 return Err(RuntimeError::new("insert requires key and value arguments", line, column));
@@ -168,6 +657,7 @@ fn call_builtin_method_on_dict(
             let key_val = eval(args[0].clone(), env)?;
             let value_val = eval(args[1].clone(), env)?;
             if let Value::String(key) = key_val {
+                check_dict_variable_value(caller_ast, &key, &value_val, env, line, column)?;
                 let old_value = dict.insert(key, value_val);
                 if let ASTNode::Variable { name, value_type, .. } = caller_ast {
                     let result = env.set(
@@ -192,7 +682,7 @@ fn call_builtin_method_on_dict(
             }
             let key_val = eval(args[0].clone(), env)?;
             if let Value::String(key) = key_val {
-                let removed_value = dict.remove(&key);
+                let removed_value = dict.shift_remove(&key);
                 if let ASTNode::Variable { name, value_type, .. } = caller_ast {
                     let result = env.set(
                         name.to_string(),
@@ -229,6 +719,11 @@ fn call_builtin_method_on_dict(
             let values: Vec<Value> = dict.values().cloned().collect();
             Ok(Value::List(values))
         }
+        "sort_keys" => {
+            let mut sorted = dict.clone();
+            sorted.sort_keys();
+            Ok(Value::Dict(sorted))
+        }
         "len" => Ok(Value::Number(Fraction::from(dict.len()))),
         "is_empty" => Ok(Value::Bool(dict.is_empty())),
         "clear" => {
@@ -254,6 +749,7 @@ fn call_builtin_method_on_dict(
             let other_dict_val = eval(args[0].clone(), env)?;
             if let Value::Dict(other_dict) = other_dict_val {
                 for (key, value) in other_dict {
+                    check_dict_variable_value(caller_ast, &key, &value, env, line, column)?;
                     dict.insert(key, value);
                 }
                 if let ASTNode::Variable { name, value_type, .. } = caller_ast {
@@ -273,6 +769,22 @@ fn call_builtin_method_on_dict(
                 Err(RuntimeError::new("update argument must be a dictionary", line, column))
             }
         }
+        "merge" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("merge requires a dictionary argument", line, column));
+            }
+            match eval(args[0].clone(), env)? {
+                Value::Dict(other_dict) => {
+                    // 元の辞書は変更しない。同じキーは引数の値で上書きし、順序は元の辞書のキーの後に新しいキーが続く
+                    let mut merged = dict.clone();
+                    for (key, value) in other_dict {
+                        merged.insert(key, value);
+                    }
+                    Ok(Value::Dict(merged))
+                }
+                _ => Err(RuntimeError::new("merge argument must be a dictionary", line, column)),
+            }
+        }
         "entry" => {
             if args.len() < 1 {
                 return Err(RuntimeError::new("entry requires a key argument", line, column));
@@ -298,6 +810,7 @@ fn call_builtin_method_on_dict(
                 let result_value = if dict.contains_key(&key) {
                     dict.get(&key).unwrap().clone()
                 } else {
+                    check_dict_variable_value(caller_ast, &key, &default_val, env, line, column)?;
                     dict.insert(key.clone(), default_val.clone());
                     if let ASTNode::Variable { name, value_type, .. } = caller_ast {
                         let result = env.set(
@@ -341,6 +854,21 @@ fn call_builtin_method_on_string(
         "to_uppercase" => Ok(Value::String(string.to_uppercase())),
         "to_lowercase" => Ok(Value::String(string.to_lowercase())),
         "trim" => Ok(Value::String(string.trim().to_string())),
+        // 見た目の1文字(拡張書記素クラスタ)ごとに逆順にする
+        "reverse" => Ok(Value::String(string.graphemes(true).rev().collect())),
+        "to_number" => {
+            // "42"、"-1.5"、"3/4" のような表記を受け付け、解釈できなければNoneを返す
+            // 分母が0の分数はパース時にpanicするので先に除外する
+            let has_zero_denominator = string.split_once('/')
+                .is_some_and(|(_, denominator)| denominator.parse::<u64>() == Ok(0));
+            let number = if has_zero_denominator {
+                None
+            } else {
+                string.parse::<Fraction>().ok().filter(|number| number.numer().is_some())
+                    .or_else(|| parse_rounded_decimal(&string))
+            };
+            Ok(Value::Option(number.map(|number| Box::new(Value::Number(number)))))
+        }
         "contains" => {
             if args.len() < 1 {
                 return Err(RuntimeError::new("contains requires a substring argument", line, column));
@@ -400,6 +928,66 @@ fn call_builtin_method_on_string(
                 Err(RuntimeError::new("replace arguments must be strings", line, column))
             }
         }
+        "char_at" | "code_at" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new(format!("{} requires an index argument", method_name).as_str(), line, column));
+            }
+            let index = match eval(args[0].clone(), env)? {
+                Value::Number(n) if n.denom() == Some(&1) => n,
+                other => return Err(RuntimeError::new(format!("{} index must be an integer: {}", method_name, other).as_str(), line, column)),
+            };
+            // 負の位置や文字数以上の位置はNoneにする
+            let c = if index.is_sign_negative() && index != Fraction::from(0) {
+                None
+            } else {
+                string.chars().nth(*index.numer().unwrap() as usize)
+            };
+            let value = c.map(|c| match method_name {
+                "char_at" => Value::String(c.to_string()),
+                _ => Value::Number(Fraction::from(c as u32)),
+            });
+            Ok(Value::Option(value.map(Box::new)))
+        }
+        "substring" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(RuntimeError::new("substring requires start and optional end arguments", line, column));
+            }
+            let mut indices = vec![];
+            for arg in args {
+                match eval(arg.clone(), env)? {
+                    Value::Number(n) if n.denom() == Some(&1) => indices.push(n),
+                    other => return Err(RuntimeError::new(format!("substring index must be an integer: {}", other).as_str(), line, column)),
+                }
+            }
+            // 位置はバイトではなく文字(Unicodeスカラー値)単位で数える
+            let chars = string.chars().collect::<Vec<_>>();
+            let start = clamp_slice_index(&indices[0], chars.len());
+            let end = indices.get(1).map(|end| clamp_slice_index(end, chars.len())).unwrap_or(chars.len());
+            if start >= end {
+                return Ok(Value::String(String::new()));
+            }
+            Ok(Value::String(chars[start..end].iter().collect()))
+        }
+        "pad_left" | "pad_right" => {
+            if args.len() != 2 {
+                return Err(RuntimeError::new(format!("{} requires width and pad arguments", method_name).as_str(), line, column));
+            }
+            let width = match eval(args[0].clone(), env)? {
+                Value::Number(n) if n >= Fraction::from(0) && n.denom() == Some(&1) => *n.numer().unwrap() as usize,
+                other => return Err(RuntimeError::new(format!("{} width must be a non-negative integer: {}", method_name, other).as_str(), line, column)),
+            };
+            let pad = match eval(args[1].clone(), env)? {
+                Value::String(pad) if pad.chars().count() == 1 => pad,
+                other => return Err(RuntimeError::new(format!("{} pad must be a single character: {:?}", method_name, other.to_string()).as_str(), line, column)),
+            };
+            // 幅は文字数で数え、すでに幅以上ならそのまま返す
+            let padding = pad.repeat(width.saturating_sub(string.chars().count()));
+            if method_name == "pad_left" {
+                Ok(Value::String(padding + &string))
+            } else {
+                Ok(Value::String(string + &padding))
+            }
+        }
         _ => Err(RuntimeError::new(
             format!("{} is not a method of string", method_name).as_str(),
             line,
@@ -408,6 +996,96 @@ fn call_builtin_method_on_string(
     }
 }
 
+// lambda builtin method
+fn call_builtin_method_on_lambda(
+    lambda: Value,
+    method_name: &str,
+    args: &[ASTNode],
+    env: &mut Env,
+    line: usize,
+    column: usize,
+) -> Result<Value, RuntimeError> {
+    match method_name {
+        "arity" => match &lambda {
+            Value::Lambda { arguments, .. } => Ok(Value::Number(Fraction::from(arguments.len()))),
+            _ => Err(RuntimeError::new("arity requires a lambda", line, column)),
+        },
+        "call" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("call requires a list of arguments", line, column));
+            }
+            match eval(args[0].clone(), env)? {
+                Value::List(call_args) => call_lambda(&lambda, call_args, env).map_err(|e| RuntimeError::from_builtin(e.as_str(), line, column)),
+                other => Err(RuntimeError::new(format!("call argument must be a list: {}", other).as_str(), line, column)),
+            }
+        }
+        _ => Err(RuntimeError::new(
+            format!("{} is not a method of lambda", method_name).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+// option builtin method
+fn call_builtin_method_on_option(
+    option: Option<Box<Value>>,
+    method_name: &str,
+    args: &[ASTNode],
+    env: &mut Env,
+    line: usize,
+    column: usize,
+) -> Result<Value, RuntimeError> {
+    match method_name {
+        "ok_or" => {
+            if args.len() != 1 {
+                return Err(RuntimeError::new("ok_or requires an error value argument", line, column));
+            }
+            let error = eval(args[0].clone(), env)?;
+            Ok(Value::Result(option.ok_or(Box::new(error))))
+        }
+        _ => Err(RuntimeError::new(
+            format!("{} is not a method of option", method_name).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+// bool builtin method
+fn call_builtin_method_on_bool(
+    value: bool,
+    method_name: &str,
+    line: usize,
+    column: usize,
+) -> Result<Value, RuntimeError> {
+    match method_name {
+        "to_number" => Ok(Value::Number(Fraction::from(if value { 1 } else { 0 }))),
+        _ => Err(RuntimeError::new(
+            format!("{} is not a method of bool", method_name).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+// result builtin method
+fn call_builtin_method_on_result(
+    result: Result<Box<Value>, Box<Value>>,
+    method_name: &str,
+    line: usize,
+    column: usize,
+) -> Result<Value, RuntimeError> {
+    match method_name {
+        "to_option" => Ok(Value::Option(result.ok())),
+        _ => Err(RuntimeError::new(
+            format!("{} is not a method of result", method_name).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
 /// Valueに応じた builtin メソッドの呼び出し
 fn call_builtin_method(
     value: Value,
@@ -420,7 +1098,7 @@ fn call_builtin_method(
 ) -> Result<Value, RuntimeError> {
     match value {
         Value::Number(num) => {
-            call_builtin_method_on_number(num, method_name, args, line, column)
+            call_builtin_method_on_number(num, method_name, args, env, line, column)
         }
         Value::List(list) => {
             call_builtin_method_on_list(list, method_name, args, caller_ast, env, line, column)
@@ -431,6 +1109,18 @@ fn call_builtin_method(
         Value::String(string) => {
             call_builtin_method_on_string(string, method_name, args, env, line, column)
         }
+        Value::Lambda { .. } => {
+            call_builtin_method_on_lambda(value, method_name, args, env, line, column)
+        }
+        Value::Option(option) => {
+            call_builtin_method_on_option(option, method_name, args, env, line, column)
+        }
+        Value::Result(result) => {
+            call_builtin_method_on_result(result, method_name, line, column)
+        }
+        Value::Bool(value) => {
+            call_builtin_method_on_bool(value, method_name, line, column)
+        }
         _ => Err(RuntimeError::new(
             format!("Method {} is not supported for this type", method_name).as_str(),
             line,
@@ -516,6 +1206,11 @@ pub fn method_call_node(
         }
     };
 
+    // to_stringが定義されていない場合はデフォルトの表示を返す
+    if method_name == "to_string" && !methods.contains_key(&method_name) {
+        return Ok(Value::String(format!("{}", variable_info.value)));
+    }
+
     // 対象のメソッド情報を取得する
     let method_info = methods.get(&method_name).ok_or_else(|| {
         RuntimeError::new(
@@ -620,7 +1315,7 @@ pub fn method_call_node(
     };
 
     // メソッド呼び出し後、self の変更があればグローバル環境に反映する
-    if let Some(self_var) = local_env.get(&"self".to_string(), None) {
+    if let Some(self_var) = local_env.get("self", None) {
         if let Value::StructInstance { .. } = self_var.value.clone() {
             let result = local_env.set(
                 caller_name.to_string(),
@@ -635,10 +1330,63 @@ pub fn method_call_node(
         }
     }
     env.update_global_env(&local_env);
-    env.leave_scope();
+    local_env.leave_scope();
     Ok(unwrapped_result)
 }
 
+/// 構造体インスタンスを表示用の文字列に変換する
+/// ユーザー定義のto_stringメソッドがあればそれを優先し、なければデフォルトの表示を使う
+pub fn struct_instance_to_string(value: &Value, env: &Env, line: usize, column: usize) -> Result<String, RuntimeError> {
+    let (struct_name, fields) = match value {
+        Value::StructInstance { name, fields } => (name, fields),
+        _ => return Ok(format!("{}", value)),
+    };
+    let method_info = match env.get_struct(struct_name) {
+        Some(Value::Struct { methods, .. }) => match methods.get("to_string") {
+            Some(method_info) => method_info.clone(),
+            None => return Ok(format!("{}", value)),
+        },
+        _ => return Ok(format!("{}", value)),
+    };
+
+    let mut local_env = env.clone();
+    local_env.enter_scope("to_string".to_string());
+    let result = local_env.set(
+        "self".to_string(),
+        value.clone(),
+        EnvVariableType::Immutable,
+        value.value_type(),
+        true,
+    );
+    if let Err(e) = result {
+        return Err(RuntimeError::new(e.as_str(), line, column));
+    }
+    for (field_name, field_value) in fields {
+        let result = local_env.set(
+            field_name.to_string(),
+            field_value.clone(),
+            EnvVariableType::Immutable,
+            field_value.value_type(),
+            true,
+        );
+        if let Err(e) = result {
+            return Err(RuntimeError::new(e.as_str(), line, column));
+        }
+    }
+    let result = match eval(method_info.body.unwrap(), &mut local_env)? {
+        Value::Return(inner) => *inner,
+        other => other,
+    };
+    match result {
+        Value::String(s) => Ok(s),
+        other => Err(RuntimeError::new(
+            format!("to_string must return a string: {:?}", other).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -651,7 +1399,7 @@ mod tests {
     fn test_to_string_method_call_node() {
         let mut env = Env::new();
         let input = "1.to_string()".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -662,7 +1410,7 @@ mod tests {
     fn test_round_method_call_node() {
         let mut env = Env::new();
         let input = "(1.5).round()".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse();
         let result = eval(ast.unwrap(), &mut env).unwrap();
@@ -670,69 +1418,409 @@ mod tests {
     }
 
     #[test]
-    fn test_sqrt_method_call_node() {
+    fn test_to_fixed_method_call_node() {
         let mut env = Env::new();
-        let input = "(2 + 2).sqrt()".to_string();
-        let tokens = tokenize(&input);
+        let input = r#"
+        (1 / 3).to_fixed(2)
+        (2 / 3).to_fixed(2)
+        (1 / 8).to_fixed(2)
+        (1 / 4).to_fixed(4)
+        (0 - 5 / 2).to_fixed(0)
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
-        let ast = parser.parse();
-        let result = eval(ast.unwrap(), &mut env).unwrap();
-        assert_eq!(result, Value::Number(2.into()));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[0], Value::String("0.33".to_string()));
+        assert_eq!(result[1], Value::String("0.67".to_string()));
+        assert_eq!(result[2], Value::String("0.13".to_string()));
+        assert_eq!(result[3], Value::String("0.2500".to_string()));
+        assert_eq!(result[4], Value::String("-3".to_string()));
     }
 
     #[test]
-    fn test_new_method_call_node() {
+    fn test_to_fixed_negative_digits_error() {
         let mut env = Env::new();
-        let input = r#"
-        struct Point {
-            x: number,
-            y: number,
-        }
-        impl Point {
-          fun new(x: number, y: number): Point {
-            return Point { x: x, y: y }
-          }
-          fun get_x(self): number {
-            return self.x
-          }
-        }
-        val mut p = Point{x: 3, y: 2}
-        p.get_x()
-        "#.to_string();
-        let tokens = tokenize(&input);
+        let input = "(1 / 3).to_fixed(0 - 1)".to_string();
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env);
-        assert_eq!(result.unwrap().last(), Some(&Value::Number(3.into())));
+        assert!(result.is_err());
     }
+
     #[test]
-    fn test_push_method_call_node() {
+    fn test_round_with_digits_method_call_node() {
         let mut env = Env::new();
-        
-        let input = "val mut xs = []\nxs.push(1)\n".to_string();
-        let tokens = tokenize(&input);
+        let input = r#"
+        (1 / 3).round(2)
+        (2 / 3).round(2)
+        (5 / 2).round(0)
+        1234.round(0 - 1)
+        1250.round(0 - 2)
+        (7 / 4).round()
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
-        assert_eq!(result[1], Value::List(vec![Value::Number(1.into())]));
+        assert_eq!(result, vec![
+            Value::Number(Fraction::new(33u64, 100u64)),
+            Value::Number(Fraction::new(67u64, 100u64)),
+            Value::Number(Fraction::from(3)),
+            Value::Number(Fraction::from(1230)),
+            Value::Number(Fraction::from(1300)),
+            Value::Number(Fraction::from(2)),
+        ]);
     }
 
     #[test]
-    fn test_push_method_call_node_with_variable() {
+    fn test_round_digits_too_large_error() {
         let mut env = Env::new();
-        let input = "val mut xs = [1,2]\nval x = 3\nxs.push(x)\n".to_string();
-        let tokens = tokenize(&input);
-        let builtin = register_builtins(&mut env);
-        let mut parser = Parser::new(tokens, builtin);
-        let ast = parser.parse_lines();
-        let result = evals(ast.unwrap(), &mut env).unwrap();
-        assert_eq!(result[2], Value::List(vec![Value::Number(1.into()), Value::Number(2.into()), Value::Number(3.into())]));
+        let input = "1000.round(17)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse();
+        let error = eval(ast.unwrap(), &mut env).unwrap_err();
+        assert_eq!(error.message, "round digits too large for 1000");
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn test_to_string_with_radix_method_call_node() {
+        let mut env = Env::new();
+        let input = r#"
+        255.to_string()
+        (1 / 2).to_string()
+        255.to_string(16)
+        10.to_string(2)
+        0.to_string(8)
+        (0 - 35).to_string(36)
+        255.to_string(10)
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        let strings = ["255", "1/2", "ff", "1010", "0", "-z", "255"];
+        assert_eq!(result, strings.iter().map(|s| Value::String(s.to_string())).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_to_string_with_radix_errors() {
+        for (input, message) in [
+            ("(1 / 2).to_string(2)", "to_string with a radix requires an integer: 1/2"),
+            ("10.to_string(1)", "to_string radix must be an integer from 2 to 36: 1"),
+            ("10.to_string(37)", "to_string radix must be an integer from 2 to 36: 37"),
+        ] {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines();
+            let result = evals(ast.unwrap(), &mut env);
+            assert_eq!(result.unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_clamp_method_call_node() {
+        let mut env = Env::new();
+        let input = r#"
+        (0 - 5).clamp(0, 100)
+        42.clamp(0, 100)
+        150.clamp(0, 100)
+        (1 / 2).clamp(0, 1)
+        fun compute(): number { return 120 }
+        compute().clamp(0, 100)
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[0], Value::Number(Fraction::from(0)));
+        assert_eq!(result[1], Value::Number(Fraction::from(42)));
+        assert_eq!(result[2], Value::Number(Fraction::from(100)));
+        assert_eq!(result[3], Value::Number(Fraction::new(1u64, 2u64)));
+        assert_eq!(result[5], Value::Number(Fraction::from(100)));
+    }
+
+    #[test]
+    fn test_clamp_inverted_bounds_error() {
+        let mut env = Env::new();
+        let input = "5.clamp(10, 0)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap_err().message, "clamp lower bound 10 is greater than upper bound 0");
     }
+
+    #[test]
+    fn test_bool_to_number_and_number_to_bool() {
+        let mut env = Env::new();
+        let input = r#"
+        val flag = 1 < 2
+        true.to_number()
+        false.to_number()
+        flag.to_number() + 1
+        0.to_bool()
+        1.to_bool()
+        (0 - 1/2).to_bool()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[1..], [
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(0)),
+            Value::Number(Fraction::from(2)),
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Bool(true),
+        ]);
+    }
+
+    #[test]
+    fn test_bool_is_not_implicitly_a_number() {
+        let mut env = Env::new();
+        let input = "true + 1".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        assert!(evals(ast.unwrap(), &mut env).is_err());
+    }
+
+    #[test]
+    fn test_to_hex_and_to_binary_method_call_node() {
+        let mut env = Env::new();
+        let input = r#"
+        255.to_hex()
+        0.to_hex()
+        4096.to_hex()
+        10.to_binary()
+        0.to_binary()
+        (6 / 2).to_binary()
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[0], Value::String("ff".to_string()));
+        assert_eq!(result[1], Value::String("0".to_string()));
+        assert_eq!(result[2], Value::String("1000".to_string()));
+        assert_eq!(result[3], Value::String("1010".to_string()));
+        assert_eq!(result[4], Value::String("0".to_string()));
+        assert_eq!(result[5], Value::String("11".to_string()));
+    }
+
+    #[test]
+    fn test_to_hex_non_integer_error() {
+        let mut env = Env::new();
+        let input = "(1 / 2).to_hex()".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap_err().message, "to_hex requires a non-negative integer: 1/2");
+    }
+
+    #[test]
+    fn test_to_binary_negative_error() {
+        let mut env = Env::new();
+        let input = "(0 - 3).to_binary()".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap_err().message, "to_binary requires a non-negative integer: -3");
+    }
+
+    #[test]
+    fn test_numerator_and_denominator() {
+        let mut env = Env::new();
+        let input = r#"
+        val n: number = (3/4).numerator()
+        n
+        (3/4).denominator()
+        (0 - 6/8).numerator()
+        (0 - 6/8).denominator()
+        5.numerator()
+        5.denominator()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        let numbers = |ns: &[i64]| ns.iter().map(|n| Value::Number(Fraction::from(*n))).collect::<Vec<_>>();
+        assert_eq!(result[1..], numbers(&[3, 4, -3, 4, 5, 1])[..]);
+    }
+
+    #[test]
+    fn test_sqrt_method_call_node() {
+        let mut env = Env::new();
+        let input = "(2 + 2).sqrt()".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse();
+        let result = eval(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result, Value::Number(2.into()));
+    }
+
+    #[test]
+    fn test_sqrt_is_exact_for_perfect_squares() {
+        let mut env = Env::new();
+        let input = r#"
+        4.sqrt() == 2
+        (9/4).sqrt()
+        0.sqrt()
+        2.sqrt()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[0], Value::Bool(true));
+        assert_eq!(result[1], Value::Number(Fraction::new(3u64, 2u64)));
+        assert_eq!(result[2], Value::Number(Fraction::from(0)));
+        // 平方数でなければ浮動小数点数の精度で近似する
+        match &result[3] {
+            Value::Number(root) => assert!((fraction::ToPrimitive::to_f64(root).unwrap() - std::f64::consts::SQRT_2).abs() < 1e-12),
+            other => panic!("unexpected value: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sqrt_of_negative_number_is_error() {
+        let mut env = Env::new();
+        let input = "(0 - 4).sqrt()".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let error = evals(ast.unwrap(), &mut env).unwrap_err();
+        assert_eq!(error.message, "sqrt is not defined for negative numbers: -4");
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn test_new_method_call_node() {
+        let mut env = Env::new();
+        let input = r#"
+        struct Point {
+            x: number,
+            y: number,
+        }
+        impl Point {
+          fun new(x: number, y: number): Point {
+            return Point { x: x, y: y }
+          }
+          fun get_x(self): number {
+            return self.x
+          }
+        }
+        val mut p = Point{x: 3, y: 2}
+        p.get_x()
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap().last(), Some(&Value::Number(3.into())));
+    }
+    #[test]
+    fn test_push_method_call_node() {
+        let mut env = Env::new();
+        
+        let input = "val mut xs = []\nxs.push(1)\n".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[1], Value::List(vec![Value::Number(1.into())]));
+    }
+
+    #[test]
+    fn test_push_method_call_node_with_variable() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1,2]\nval x = 3\nxs.push(x)\n".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut env);
+        let mut parser = Parser::new(tokens, builtin);
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        assert_eq!(result[2], Value::List(vec![Value::Number(1.into()), Value::Number(2.into()), Value::Number(3.into())]));
+    }
+    #[test]
+    fn test_set_at_grows_list() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1, 2]\nxs.set_at(4, 9, 0)\nxs\nxs.set_at(0, 7, 0)\nxs".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        let numbers = |values: &[u64]| Value::List(values.iter().map(|v| Value::Number(Fraction::from(*v))).collect());
+        assert_eq!(result[2], numbers(&[1, 2, 0, 0, 9]));
+        assert_eq!(result[4], numbers(&[7, 2, 0, 0, 9]));
+    }
+
+    #[test]
+    fn test_set_at_negative_index_error() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1]\nxs.set_at(0 - 1, 9, 0)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap_err().message, "set_at index must be a non-negative integer: -1");
+    }
+
+    #[test]
+    fn test_set_at_too_large_index_error() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1]\nxs.set_at(10000000000000, 0, 0)\nxs".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let error = evals(ast.unwrap(), &mut env).unwrap_err();
+        assert_eq!(error.message, "set_at index 10000000000000 is too large (at most 10000000)");
+        assert_eq!(error.line, 2);
+        // 失敗したset_atはリストを変更しない
+        let xs = env.get("xs", None).unwrap();
+        assert_eq!(xs.value, Value::List(vec![Value::Number(Fraction::from(1))]));
+    }
+
+    #[test]
+    fn test_insert_and_remove_at() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1, 2, 4]\nxs.insert(2, 3)\nxs\nxs.insert(4, 5)\nxs.remove_at(1)\nxs\nxs.remove_at(10)\nxs.remove_at(0 - 1)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env).unwrap();
+        let numbers = |values: &[u64]| Value::List(values.iter().map(|v| Value::Number(Fraction::from(*v))).collect());
+        assert_eq!(result[2], numbers(&[1, 2, 3, 4]));
+        assert_eq!(result[4], Value::Option(Some(Box::new(Value::Number(Fraction::from(2))))));
+        assert_eq!(result[5], numbers(&[1, 3, 4, 5]));
+        assert_eq!(result[6], Value::Option(None));
+        assert_eq!(result[7], Value::Option(None));
+    }
+
+    #[test]
+    fn test_insert_out_of_range_error() {
+        let mut env = Env::new();
+        let input = "val mut xs = [1, 2]\nxs.insert(3, 9)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines();
+        let result = evals(ast.unwrap(), &mut env);
+        assert_eq!(result.unwrap_err().message, "insert index 3 is out of range for a list of length 2");
+    }
+
     #[test]
     fn method_chaining_with_round_and_to_string() {
         let mut env = Env::new();
         let input = "fun add(x: number): number {\n return x + 1\n}\n add(1.5).round().to_string()".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let result = evals(ast.unwrap(), &mut env).unwrap();
@@ -746,7 +1834,7 @@ mod tests {
         xs.len()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         // 最初に変数を定義
@@ -772,7 +1860,7 @@ mod tests {
         xs.pop()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         // 最初に変数を定義
@@ -788,6 +1876,277 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_list_pop_updates_variable_and_empty_list() {
+        let input = r#"
+        val mut xs = [1, 2]
+        xs.pop()
+        xs
+        xs.pop()
+        xs.pop()
+        xs
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1..], [
+            Value::Option(Some(Box::new(Value::Number(Fraction::from(2))))),
+            Value::List(vec![Value::Number(Fraction::from(1))]),
+            Value::Option(Some(Box::new(Value::Number(Fraction::from(1))))),
+            Value::Option(None),
+            Value::List(vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_list_flatten_method() {
+        let input = r#"
+        [[1, 2], [3]].flatten()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(3)),
+        ]));
+    }
+
+    #[test]
+    fn test_list_chunk_window_slice_methods() {
+        let input = r#"
+        val xs = [1, 2, 3, 4, 5]
+        xs.chunk(2)
+        xs.window(2)
+        xs.window(6)
+        xs.slice(1, 3)
+        xs.slice(0 - 2)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let list = |values: &[i32]| Value::List(values.iter().map(|n| Value::Number(Fraction::from(*n))).collect());
+        assert_eq!(result[1], Value::List(vec![list(&[1, 2]), list(&[3, 4]), list(&[5])]));
+        assert_eq!(result[2], Value::List(vec![list(&[1, 2]), list(&[2, 3]), list(&[3, 4]), list(&[4, 5])]));
+        assert_eq!(result[3], Value::List(vec![]));
+        assert_eq!(result[4], list(&[2, 3]));
+        assert_eq!(result[5], list(&[4, 5]));
+    }
+
+    #[test]
+    fn test_list_sum_product_average_methods() {
+        let input = r#"
+        val xs = [1, 2, 3, 4]
+        val empty = [1].drop(1)
+        xs.sum()
+        xs.product()
+        xs.average()
+        empty.sum()
+        empty.product()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2..], [
+            Value::Number(Fraction::from(10)),
+            Value::Number(Fraction::from(24)),
+            Value::Number(Fraction::new(5u64, 2u64)),
+            Value::Number(Fraction::from(0)),
+            Value::Number(Fraction::from(1)),
+        ]);
+    }
+
+    #[test]
+    fn test_list_max_by_min_by_methods() {
+        let input = r#"
+        val words = ["bb", "a", "cc", "ddd", "e"]
+        words.max_by(\|w| => len(w))
+        words.min_by(\|w| => len(w))
+        words.drop(5).max_by(\|w| => len(w))
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1..], [
+            Value::Option(Some(Box::new(Value::String("ddd".into())))),
+            Value::Option(Some(Box::new(Value::String("a".into())))),
+            Value::Option(None),
+        ]);
+    }
+
+    #[test]
+    fn test_list_average_errors() {
+        let cases = [
+            ("[1].drop(1).average()", "average of an empty list"),
+            ("[1, \"a\"].sum()", "sum requires a list of numbers but found a"),
+        ];
+        for (input, message) in cases {
+            let mut env = Env::new();
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut env));
+            let ast = parser.parse_lines().unwrap();
+            let result = evals(ast, &mut env);
+            assert_eq!(result.unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_list_chunk_invalid_size_error() {
+        let input = r#"
+        [1, 2, 3].chunk(0)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "chunk size must be a positive integer: 0");
+    }
+
+    #[test]
+    fn test_list_flatten_non_list_element_error() {
+        let input = r#"
+        val xs = [[1], 2]
+        xs.flatten()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        let error = result.unwrap_err();
+        assert_eq!(error.message, "flatten requires a list of lists but found 2");
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn test_list_flat_map_method() {
+        let input = r#"
+        val xs = [1, 2]
+        xs.flat_map(\|x| => [x, x * 10])
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(10)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(20)),
+        ]));
+    }
+
+    #[test]
+    fn test_list_unique_method() {
+        let input = r#"
+        [1, 1, 2, 3, 3].unique()
+        ["b", "a", "b", "c", "a"].unique()
+        [3, 1, 3, 2, 1, 3].unique()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(3)),
+        ]));
+        assert_eq!(result[1], Value::List(vec![
+            Value::String("b".to_string()),
+            Value::String("a".to_string()),
+            Value::String("c".to_string()),
+        ]));
+        assert_eq!(result[2], Value::List(vec![
+            Value::Number(Fraction::from(3)),
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+        ]));
+    }
+
+    #[test]
+    fn test_list_unique_lambda_error() {
+        let input = r#"
+        val f = \|x| => x
+        [f, f].unique()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "unique cannot compare values: a function cannot be hashed");
+    }
+
+    #[test]
+    fn test_list_unique_nested_lambda_error() {
+        let input = r#"
+        val f = \|x| => x
+        [[1, f], [1, f]].unique()
+        [Some(f), Some(f)].unique()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast[..2].to_vec(), &mut env).unwrap_err();
+        assert_eq!(error.message, "unique cannot compare values: a function cannot be hashed");
+        assert_eq!(error.line, 3);
+        let error = evals(ast[2..].to_vec(), &mut env).unwrap_err();
+        assert_eq!(error.message, "unique cannot compare values: a function cannot be hashed");
+    }
+
+    #[test]
+    fn test_list_take_drop_method() {
+        let input = r#"
+        val xs = [1, 2, 3]
+        xs.take(2)
+        xs.drop(2)
+        xs.take(5)
+        xs.drop(5)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+        ]));
+        assert_eq!(result[2], Value::List(vec![Value::Number(Fraction::from(3))]));
+        assert_eq!(result[3], Value::List(vec![]));
+        assert_eq!(result[4], Value::List(vec![]));
+    }
+
+    #[test]
+    fn test_list_take_negative_count_error() {
+        let input = r#"
+        val xs = [1, 2, 3]
+        xs.take(0 - 1)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "take count must not be negative: -1");
+    }
+
     #[test]
     fn test_dict_len_method() {
         let input = r#"
@@ -795,7 +2154,7 @@ mod tests {
         d.len()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         
@@ -818,6 +2177,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_head_and_tail() {
+        let input = r#"
+        val xs = [1, 2, 3]
+        val empty: List<number> = []
+        xs.head()
+        xs.tail()
+        [1].tail()
+        empty.head()
+        empty.tail()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2..], [
+            Value::Option(Some(Box::new(Value::Number(Fraction::from(1))))),
+            Value::List(vec![Value::Number(Fraction::from(2)), Value::Number(Fraction::from(3))]),
+            Value::List(vec![]),
+            Value::Option(None),
+            Value::List(vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_recursion_with_head_and_tail() {
+        let input = r#"
+        fun total(xs: List<number>): number {
+            val rest = xs.tail()
+            return match (xs.head()) {
+                Some(x) => { x + total(rest) }
+                None => { 0 }
+            }
+        }
+        total([1, 2, 3, 4])
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Number(Fraction::from(10)));
+    }
+
+    #[test]
+    fn test_is_empty_method() {
+        let input = r#"
+        val empty_dict = {::}
+        val dict = {: "a" => 1 :}
+        "".is_empty()
+        "abc".is_empty()
+        [].is_empty()
+        [1, 2].is_empty()
+        empty_dict.is_empty()
+        dict.is_empty()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2..], [
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Bool(false),
+        ]);
+    }
+
     #[test]
     fn test_dict_keys_method() {
         let input = r#"
@@ -825,7 +2256,7 @@ mod tests {
         d.keys()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         // 最初に変数を定義
@@ -851,6 +2282,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dict_keys_keep_insertion_order() {
+        let input = r#"
+        val mut d = {: "b" => 1 :}
+        d["a"] = 2
+        d["c"] = 3
+        d.keys()
+        d.remove("a")
+        d.keys()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let keys = |names: &[&str]| Value::List(names.iter().map(|n| Value::String(n.to_string())).collect());
+        assert_eq!(result[3], keys(&["b", "a", "c"]));
+        assert_eq!(result[5], keys(&["b", "c"]));
+    }
+
+    #[test]
+    fn test_dict_sort_keys_method() {
+        let input = r#"
+        val d = {: "b" => 1, "c" => 2, "a" => 3 :}
+        d.sort_keys()
+        d.sort_keys().keys()
+        d.keys()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let keys = |names: &[&str]| Value::List(names.iter().map(|n| Value::String(n.to_string())).collect());
+        assert_eq!(format!("{}", result[1]), "{:a: 3, b: 1, c: 2:}");
+        assert_eq!(result[2], keys(&["a", "b", "c"]));
+        // 元の辞書の順序は変わらない
+        assert_eq!(result[3], keys(&["b", "c", "a"]));
+    }
+
+    #[test]
+    fn test_option_ok_or_method() {
+        let input = r#"
+        val some: Option<number> = Some(1)
+        val none: Option<number> = None
+        some.ok_or("missing")
+        none.ok_or("missing")
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2], Value::Result(Ok(Box::new(Value::Number(Fraction::from(1))))));
+        assert_eq!(result[3], Value::Result(Err(Box::new(Value::String("missing".into())))));
+    }
+
+    #[test]
+    fn test_result_to_option_method() {
+        let input = r#"
+        val suc: Result<number, string> = Suc(1)
+        val fail: Result<number, string> = Fail("error")
+        suc.to_option()
+        fail.to_option()
+        val converted = Some(2).ok_or("missing")
+        converted.to_option()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2], Value::Option(Some(Box::new(Value::Number(Fraction::from(1))))));
+        assert_eq!(result[3], Value::Option(None));
+        assert_eq!(result[5], Value::Option(Some(Box::new(Value::Number(Fraction::from(2))))));
+    }
+
     #[test]
     fn test_string_len_method() {
         let input = r#"
@@ -858,7 +2366,7 @@ mod tests {
         s.len()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         // 最初に変数を定義
@@ -874,6 +2382,155 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_to_number_method() {
+        let input = r#"
+        "42".to_number()
+        "3/4".to_number()
+        "-1.5".to_number()
+        " 7 ".trim().to_number()
+        "abc".to_number()
+        "".to_number()
+        "1/0".to_number()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let some = |number: Fraction| Value::Option(Some(Box::new(Value::Number(number))));
+        assert_eq!(result, vec![
+            some(Fraction::from(42)),
+            some(Fraction::new(3u64, 4u64)),
+            some(Fraction::new_neg(3u64, 2u64)),
+            some(Fraction::from(7)),
+            Value::Option(None),
+            Value::Option(None),
+            Value::Option(None),
+        ]);
+    }
+
+    #[test]
+    fn test_string_to_number_rounds_long_decimals() {
+        let input = r#"
+        "0.12345678901234567890123".to_number()
+        "-2.99999999999999999999".to_number()
+        "99999999999999999999999".to_number()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let some = |number: Fraction| Value::Option(Some(Box::new(Value::Number(number))));
+        assert_eq!(result, vec![
+            // 分母が10^19までになるよう20桁目で四捨五入する
+            some(Fraction::new(1234567890123456789u64, 10000000000000000000u64)),
+            some(Fraction::from(-3)),
+            // 整数部が収まらない場合は丸めようがないのでNone
+            Value::Option(None),
+        ]);
+    }
+
+    #[test]
+    fn test_string_substring_method() {
+        let input = r#"
+        "hello".substring(1, 3)
+        "hello".substring(2)
+        "hello".substring(3, 100)
+        "hello".substring(4, 1)
+        "hello".substring(0 - 3, 0 - 1)
+        "hello".substring(0 - 100, 2)
+        "日本語です".substring(1, 3)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result, vec![
+            Value::String("el".into()),
+            Value::String("llo".into()),
+            Value::String("lo".into()),
+            Value::String("".into()),
+            Value::String("ll".into()),
+            Value::String("he".into()),
+            Value::String("本語".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_string_reverse_method() {
+        // 結合文字やZWJはRustのエスケープで埋め込む
+        let input = [
+            "\"abc\".reverse()",
+            "\"日本語\".reverse()",
+            "\"\".reverse()",
+            "\"cafe\u{0301}!\".reverse()",
+            "\"a👍🏽b\".reverse()",
+            "\"👨\u{200D}👩\u{200D}👧x\".reverse()",
+            "\"🇯🇵🇺🇸\".reverse()",
+            "\"\u{1100}\u{1161}\u{11A8}a\".reverse()",
+        ].join("\n");
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result, vec![
+            Value::String("cba".into()),
+            Value::String("語本日".into()),
+            Value::String("".into()),
+            // 結合文字のアクセントはeから切り離さない
+            Value::String("!e\u{0301}fac".into()),
+            // 肌の色の修飾子やZWJで連結した絵文字は1文字として扱う
+            Value::String("b👍🏽a".into()),
+            Value::String("x👨\u{200D}👩\u{200D}👧".into()),
+            // 国旗は2つの地域指示記号の組で1文字になる
+            Value::String("🇺🇸🇯🇵".into()),
+            // ハングルの字母(初声・中声・終声)の並びは1文字にまとまる
+            Value::String("a\u{1100}\u{1161}\u{11A8}".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_string_char_at_and_code_at_methods() {
+        let input = r#"
+        "abc".char_at(1)
+        "abc".char_at(3)
+        "abc".char_at(0 - 1)
+        "abc".code_at(0)
+        "aあ".code_at(1)
+        "aあ".char_at(1)
+        "abc".code_at(10)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let some = |value: Value| Value::Option(Some(Box::new(value)));
+        assert_eq!(result, vec![
+            some(Value::String("b".into())),
+            Value::Option(None),
+            Value::Option(None),
+            some(Value::Number(Fraction::from(97))),
+            some(Value::Number(Fraction::from(0x3042))),
+            some(Value::String("あ".into())),
+            Value::Option(None),
+        ]);
+    }
+
+    #[test]
+    fn test_string_substring_non_integer_error() {
+        let mut env = Env::new();
+        let tokens = tokenize(&"\"hello\".substring(1 / 2, 3)".to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "substring index must be an integer: 1/2");
+    }
+
     #[test]
     fn test_string_to_uppercase_method() {
         let input = r#"
@@ -881,7 +2538,7 @@ mod tests {
         s.to_uppercase()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         // 最初に変数を定義
@@ -897,6 +2554,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lambda_arity_and_call_method() {
+        let input = r#"
+        val add = \|x: number, y: number| => x + y
+        val answer = \|| => 42
+        add.arity()
+        answer.arity()
+        add.call([1, 2])
+        answer.call([])
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2], Value::Number(Fraction::from(2)));
+        assert_eq!(result[3], Value::Number(Fraction::from(0)));
+        assert_eq!(result[4], Value::Number(Fraction::from(3)));
+        assert_eq!(result[5], Value::Number(Fraction::from(42)));
+    }
+
+    #[test]
+    fn test_lambda_call_method_wrong_length_error() {
+        let input = r#"
+        val add = \|x: number, y: number| => x + y
+        add.call([1])
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "does not match arguments length: expected 2, got 1");
+    }
+
+    #[test]
+    fn test_string_pad_method() {
+        let input = r#"
+        "7".pad_left(3, "0")
+        "ab".pad_right(5, ".")
+        "hello".pad_left(3, "0")
+        "hello".pad_right(5, " ")
+        "あ".pad_left(3, "*")
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[0], Value::String("007".into()));
+        assert_eq!(result[1], Value::String("ab...".into()));
+        assert_eq!(result[2], Value::String("hello".into()));
+        assert_eq!(result[3], Value::String("hello".into()));
+        assert_eq!(result[4], Value::String("**あ".into()));
+    }
+
+    #[test]
+    fn test_string_pad_multi_char_error() {
+        let input = r#"
+        "7".pad_left(3, "00")
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "pad_left pad must be a single character: \"00\"");
+    }
+
     #[test]
     fn test_dict_index_assignment() {
         let input = r#"
@@ -905,7 +2631,7 @@ mod tests {
         d["a"]
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         
@@ -932,7 +2658,7 @@ mod tests {
         d["a"]
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         
@@ -951,6 +2677,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dict_get_method() {
+        let input = r#"
+        val d = {: "a" => 1 :}
+        d.get("a")
+        d.get("missing")
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Option(Some(Box::new(Value::Number(Fraction::from(1))))));
+        assert_eq!(result[2], Value::Option(None));
+    }
+
+    #[test]
+    fn test_dict_get_or_method() {
+        let input = r#"
+        val d = {: "a" => 1 :}
+        d.get_or("a", 0)
+        d.get_or("missing", 0)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[1..], [Value::Number(Fraction::from(1)), Value::Number(Fraction::from(0))]);
+    }
+
+    #[test]
+    fn test_dict_merge_method() {
+        let input = r#"
+        val d1 = {: "a" => 1, "b" => 2 :}
+        val d2 = {: "b" => 20, "c" => 30 :}
+        val merged = d1.merge(d2)
+        merged.keys()
+        merged.values()
+        d1.values()
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        let numbers = |values: &[i32]| Value::List(values.iter().map(|n| Value::Number(Fraction::from(*n))).collect());
+        assert_eq!(result[3], Value::List(vec![
+            Value::String("a".into()),
+            Value::String("b".into()),
+            Value::String("c".into()),
+        ]));
+        assert_eq!(result[4], numbers(&[1, 20, 30]));
+        assert_eq!(result[5], numbers(&[1, 2]));
+    }
+
     #[test]
     fn test_dict_update_method() {
         let input = r#"
@@ -960,7 +2742,7 @@ mod tests {
         d1.len()
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         
@@ -987,7 +2769,7 @@ mod tests {
         d.get_or_insert("b", 42)
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         
@@ -8,10 +8,13 @@ pub mod lambda_node;
 pub mod variable_node;
 pub mod binary_op;
 pub mod for_node;
+pub mod while_node;
+pub mod do_while_node;
 pub mod import_node;
 pub mod method_call_node;
 pub mod runtime_error;
 pub mod match_node;
+pub mod constant_fold;
 use fraction::Fraction;
 
 use crate::environment::Env;
@@ -19,11 +22,80 @@ use crate::ast::ASTNode;
 use crate::value::Value;
 use crate::token::TokenKind;
 use crate::evals::runtime_error::RuntimeError;
+use std::cell::Cell;
+
+thread_local! {
+    // exit組み込み関数で要求された終了コード
+    static EXIT_CODE: Cell<Option<i32>> = const { Cell::new(None) };
+}
+
+/// 評価の終了を要求する。exit組み込み関数がエラーを返す直前に呼び、呼び出し側で終了として扱う
+pub fn request_exit(code: i32) {
+    EXIT_CODE.with(|exit_code| exit_code.set(Some(code)));
+}
+
+/// 要求された終了コードを取り出す
+pub fn take_exit_code() -> Option<i32> {
+    EXIT_CODE.with(|exit_code| exit_code.take())
+}
 
 pub fn evals(asts: Vec<ASTNode>, env: &mut Env) -> Result<Vec<Value>, RuntimeError> {
+    // トップレベルの関数を先に登録し、定義より前の行から呼び出せるようにする
+    fn is_function_definition(ast: &ASTNode) -> bool {
+        match ast {
+            ASTNode::Function { .. } => true,
+            ASTNode::Public { node, .. } => is_function_definition(node),
+            _ => false,
+        }
+    }
+    for ast in asts.iter().filter(|ast| is_function_definition(ast)) {
+        eval(constant_fold::fold_constants(ast.clone()), env)?;
+    }
     let mut values = vec![];
     for ast in asts {
-        values.push(eval(ast, env)?);
+        values.push(eval(constant_fold::fold_constants(ast), env)?);
+    }
+    Ok(values)
+}
+
+/// ファイル全体を実行する。トップレベルを評価したあと、引数なしの`main`関数が定義されていれば呼び出してその戻り値を結果にする
+/// `main`がなければ最後のトップレベル式の値が結果になる
+pub fn eval_program(asts: Vec<ASTNode>, env: &mut Env) -> Result<Value, RuntimeError> {
+    fn main_position(ast: &ASTNode) -> Option<(usize, usize)> {
+        match ast {
+            ASTNode::Function { name, arguments, line, column, .. } if name == "main" && arguments.is_empty() => Some((*line, *column)),
+            ASTNode::Public { node, .. } => main_position(node),
+            _ => None,
+        }
+    }
+    let position = asts.iter().find_map(main_position);
+    let values = evals(asts, env)?;
+    match position {
+        Some((line, column)) => {
+            let call = ASTNode::FunctionCall {
+                name: "main".to_string(),
+                arguments: Box::new(ASTNode::FunctionCallArgs { args: vec![], line, column }),
+                line,
+                column,
+            };
+            eval(call, env)
+        }
+        None => Ok(values.into_iter().last().unwrap_or(Value::Void)),
+    }
+}
+
+/// リストの要素や関数の引数を評価する
+/// `...xs` はリストの要素を展開して並べる
+pub fn eval_spread_elements(elements: Vec<ASTNode>, env: &mut Env) -> Result<Vec<Value>, RuntimeError> {
+    let mut values = vec![];
+    for element in elements {
+        match element {
+            ASTNode::Spread { expr, line, column } => match eval(*expr, env)? {
+                Value::List(list) => values.extend(list),
+                other => return Err(RuntimeError::new(format!("spread value must be a list: {}", other).as_str(), line, column)),
+            },
+            _ => values.push(eval(element, env)?),
+        }
     }
     Ok(values)
 }
@@ -38,6 +110,16 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
         } => import_node::import_node(module_name, symbols, line, column, env),
         ASTNode::Public { node, line, column } => import_node::public_node(node, line, column, env),
         ASTNode::Literal{value, ..} => Ok(value.clone()),
+        ASTNode::List { elements, .. } => {
+            let values = eval_spread_elements(elements, env)?;
+            Ok(Value::List(values))
+        }
+        ASTNode::Spread { line, column, .. } => {
+            Err(RuntimeError::new("spread is only allowed in list literals and function arguments", line, column))
+        }
+        ASTNode::NamedArgument { name, line, column, .. } => {
+            Err(RuntimeError::new(format!("named argument {:?} is only supported by print", name).as_str(), line, column))
+        }
         ASTNode::PrefixOp { op, expr, line, column } => prefix_op::prefix_op(op, expr, line, column, env),
         ASTNode::Struct {
             name,
@@ -70,6 +152,9 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
         } => {
             struct_node::struct_instance_node(name, fields, line, column, env)
         }
+        ASTNode::StructUpdate { base, fields, line, column } => {
+            struct_node::struct_update_node(base, fields, line, column, env)
+        }
         ASTNode::StructFieldAssign { instance, field_name: updated_field_name, value: updated_value_ast, line, column } => {
             struct_node::struct_field_assign_node(instance, updated_field_name, updated_value_ast, line, column, env)
         }
@@ -97,8 +182,8 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
         ASTNode::Return{expr: value, line: _, column: _} => {
             Ok(Value::Return(Box::new(eval(*value, env)?)))
         }
-        ASTNode::Break { line: _, column: _ } => Ok(Value::Break),
-        ASTNode::Continue { line: _, column: _ } => Ok(Value::Continue),
+        ASTNode::Break { label, line: _, column: _ } => Ok(Value::Break(label)),
+        ASTNode::Continue { label, line: _, column: _ } => Ok(Value::Continue(label)),
         ASTNode::Eq { left, right, line, column } => {
             comparison_op::comparison_op_node(TokenKind::Eq, left, right, line, column, env)
         }
@@ -118,10 +203,29 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             variable,
             iterable,
             body,
+            label,
+            line,
+            column
+        } => {
+            for_node::for_node(variable, iterable, body, label, line, column, env)
+        }
+        ASTNode::While {
+            condition,
+            body,
+            label,
             line,
             column
         } => {
-            for_node::for_node(variable, iterable, body, line, column, env)
+            while_node::while_node(condition, body, label, line, column, env)
+        }
+        ASTNode::DoWhile {
+            body,
+            condition,
+            label,
+            line,
+            column
+        } => {
+            do_while_node::do_while_node(body, condition, label, line, column, env)
         }
         ASTNode::Match {
             expression,
@@ -256,6 +360,8 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             let new_value = eval(*value, env)?;
 
             if let Value::String(key_str) = key_value {
+                let var_info = env.get(&dict_name, None).unwrap();
+                assign_node::check_dict_value_type(&var_info.value_type, &key_str, &new_value, line, column)?;
                 let mut updated_dict = current_dict;
                 updated_dict.insert(key_str, new_value.clone());
 
@@ -346,6 +452,7 @@ mod tests {
     use crate::tokenizer::tokenize;
     use crate::parsers::Parser;
     use crate::builtin::register_builtins;
+    use crate::environment::{EnvVariableType, ValueType};
     use fraction::Fraction;
 
     #[test]
@@ -355,7 +462,7 @@ mod tests {
         val mut x = 5
         val mut y = x + 5
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -368,7 +475,7 @@ mod tests {
         val mut x = 10
         x = 20
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -381,20 +488,69 @@ mod tests {
         x = 300
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         assert!(ast.is_err());
         assert_eq!(ast.err().unwrap().message, "It is an immutable variable and cannot be reassigned: \"x\"");
     }
 
+    #[test]
+    fn test_shadowing_immutable_variable() {
+        let mut env = Env::new();
+        let input = r#"
+        val x = 1
+        val x = 2
+        x
+        val x = "two"
+        x
+        "#.to_string();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[2], Value::Number(Fraction::from(2)));
+        assert_eq!(results[4], Value::String("two".into()));
+    }
+
+    #[test]
+    fn test_reassign_immutable_variable_is_runtime_error() {
+        let mut env = Env::new();
+        let ast = vec![
+            ASTNode::Assign {
+                name: "x".into(),
+                value: Box::new(ASTNode::Literal { value: Value::Number(Fraction::from(1)), line: 1, column: 9 }),
+                variable_type: EnvVariableType::Immutable,
+                value_type: ValueType::Number,
+                is_new: true,
+                line: 1,
+                column: 1,
+            },
+            ASTNode::Assign {
+                name: "x".into(),
+                value: Box::new(ASTNode::Literal { value: Value::Number(Fraction::from(2)), line: 2, column: 5 }),
+                variable_type: EnvVariableType::Immutable,
+                value_type: ValueType::Number,
+                is_new: false,
+                line: 2,
+                column: 1,
+            },
+        ];
+        let result = evals(ast, &mut env);
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert_eq!(error.message, "Cannot reassign to immutable variable: \"x\"");
+        assert_eq!(error.line, 2);
+        assert_eq!(env.get(&"x".to_string(), None).unwrap().value, Value::Number(Fraction::from(1)));
+    }
+
     #[test]
     fn test_unsupported_prefix_operation() {
         let mut env = Env::new();
         let input = r#"
         +5
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse();
         assert!(ast.is_err());
@@ -408,7 +564,7 @@ mod tests {
         let input = r#"
         5 * "hello"
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         let results = evals(ast.unwrap(), &mut env);
@@ -422,7 +578,7 @@ mod tests {
         x.push(4)
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -442,7 +598,7 @@ mod tests {
         x["b"]
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -453,6 +609,74 @@ mod tests {
         assert_eq!(results[2], Value::Number(Fraction::from(2)));
     }
 
+    #[test]
+    fn test_dict_insertion_order_does_not_matter() {
+        let input = r#"
+        val a = {: "b" => 2, "a" => 1, "c" => 3 :}
+        val b = {: "c" => 3, "a" => 1, "b" => 2 :}
+        a == b
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[0], results[1]);
+        assert_eq!(format!("{}", results[0]), "{:b: 2, a: 1, c: 3:}");
+        assert_eq!(format!("{}", results[1]), "{:c: 3, a: 1, b: 2:}");
+        assert_eq!(results[2], Value::Bool(true));
+    }
+
+    #[test]
+    fn test_spread_list_in_the_middle() {
+        let input = r#"
+        val xs = [1, 2, 3]
+        [0, ...xs, 9]
+        [...xs, ...[4, 5]]
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        let numbers = |ns: &[i64]| Value::List(ns.iter().map(|n| Value::Number(Fraction::from(*n))).collect());
+        assert_eq!(results[1], numbers(&[0, 1, 2, 3, 9]));
+        assert_eq!(results[2], numbers(&[1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn test_spread_function_arguments() {
+        let input = r#"
+        fun add3(a: number, b: number, c: number): number {
+            return a * 100 + b * 10 + c
+        }
+        val args = [2, 3]
+        add3(1, ...args)
+        add3(...[4, 5, 6])
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[2], Value::Number(Fraction::from(123)));
+        assert_eq!(results[3], Value::Number(Fraction::from(456)));
+    }
+
+    #[test]
+    fn test_spread_non_list_is_error() {
+        let input = r#"
+        val x = 1
+        [0, ...x]
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "spread value must be a list: 1");
+    }
+
     #[test]
     #[should_panic(expected = "does not match arguments length")]
     fn test_function_call_argument_mismatch() {
@@ -463,7 +687,7 @@ mod tests {
         foo(1, 2)
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env);
@@ -471,6 +695,306 @@ mod tests {
         assert_eq!(results.err().unwrap().message, "Function foo does not match arguments length");
     }
 
+    #[test]
+    fn test_list_argument_is_passed_by_value() {
+        let input = r#"
+        val mut xs = [1, 2, 3]
+        fun append_nine(ys: List<number>): List<number> {
+            val mut copied = ys
+            copied.push(9)
+            return copied
+        }
+        append_nine(xs)
+        xs
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[2], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(3)),
+            Value::Number(Fraction::from(9)),
+        ]));
+        // 呼び出し元のリストは変更されない
+        assert_eq!(results[3], Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(3)),
+        ]));
+    }
+
+    #[test]
+    fn test_mutating_list_argument_is_error() {
+        let input = r#"
+        val mut xs = [1, 2, 3]
+        fun append_nine(ys: List<number>) {
+            ys.push(9)
+        }
+        append_nine(xs)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        // 引数は不変なので、変更しようとするとその引数名を含むエラーになる
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.message, "Cannot reassign to immutable variable: \"ys\"");
+        assert_eq!(env.get(&"xs".to_string(), None).unwrap().value, Value::List(vec![
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(3)),
+        ]));
+    }
+
+    #[test]
+    fn test_global_counter_incremented_by_function() {
+        let input = r#"
+        val mut counter = 0
+        fun increment() {
+            counter = counter + 1
+        }
+        increment()
+        increment()
+        increment()
+        counter
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(*results.last().unwrap(), Value::Number(Fraction::from(3)));
+    }
+
+    #[test]
+    fn test_lambda_sees_current_global_value() {
+        let input = r#"
+        val mut base = 1
+        val add_base = \|x: number| => x + base
+        base = 10
+        add_base(5)
+        fun bump_base() {
+            base = 100
+        }
+        bump_base()
+        add_base(5)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[3], Value::Number(Fraction::from(15)));
+        assert_eq!(*results.last().unwrap(), Value::Number(Fraction::from(105)));
+    }
+
+    #[test]
+    fn test_lambda_called_inside_function_keeps_local_scope() {
+        let input = r#"
+        val mut total = 0
+        val add_total = \|x: number| => x + total
+        fun run(n: number): number {
+            val local = 2
+            total = 5
+            val r = add_total(n)
+            return r + local
+        }
+        run(1)
+        total
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[3], Value::Number(Fraction::from(8)));
+        assert_eq!(results[4], Value::Number(Fraction::from(5)));
+    }
+
+    #[test]
+    fn test_function_call_cannot_see_caller_locals() {
+        // 呼び出し元に同名のローカル変数があっても、関数はグローバルを更新する
+        let input = r#"
+        val mut counter = 0
+        fun bump() {
+            counter = counter + 1
+        }
+        fun outer(): number {
+            val mut counter = 100
+            bump()
+            return counter
+        }
+        outer()
+        counter
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(results[3], Value::Number(Fraction::from(100)));
+        assert_eq!(results[4], Value::Number(Fraction::from(1)));
+    }
+
+    #[test]
+    fn test_lambda_resolves_variables_where_it_was_created() {
+        // グローバルで作ったラムダは呼び出し元のローカルxではなくグローバルのxを見る
+        // 関数内で作ったラムダはその関数のローカルyを捕捉する
+        let input = r#"
+        val x = 1
+        val add_x = \|n: number| => n + x
+        fun run(): number {
+            val x = 1000
+            val y = 10
+            val add_y = \|n: number| => n + y
+            val ys = map([1], add_y)
+            return add_x(0) + add_y(0) + ys[0]
+        }
+        run()
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(*results.last().unwrap(), Value::Number(Fraction::from(22)));
+    }
+
+    #[test]
+    fn test_nested_block_scope() {
+        let input = r#"
+        val mut total = 0
+        {
+            val mut x = 10
+            {
+                val y = 20
+                total = x + y
+            }
+            y
+        }
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = eval(ast[0].clone(), &mut env);
+        assert!(result.is_ok());
+        let result = eval(ast[1].clone(), &mut env);
+        // 内側のブロックで宣言したyはブロックの外から見えない
+        assert_eq!(result.unwrap_err().message, "Variable not found: \"y\"");
+        // ブロック内での外側の変数の更新は残り、ブロック内の変数は破棄される
+        assert_eq!(env.get(&"total".to_string(), None).unwrap().value, Value::Number(Fraction::from(30)));
+        assert!(env.get(&"x".to_string(), None).is_none());
+        assert!(env.get(&"y".to_string(), None).is_none());
+    }
+
+    #[test]
+    fn test_variable_lookup_in_tight_loop() {
+        let input = r#"
+        val step = 1
+        val mut total = 0
+        fun add_step(step: number): number {
+            return step * 2
+        }
+        for i in range(20000) {
+            total = total + step + step
+        }
+        {
+            val step = 100
+            total = total + add_step(step)
+        }
+        total
+        step
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        // ループ内で何度参照しても値は変わらず、シャドーイングした変数はブロックの外に影響しない
+        assert_eq!(results[5], Value::Number(Fraction::from(40200)));
+        assert_eq!(results[6], Value::Number(Fraction::from(1)));
+    }
+
+    #[test]
+    fn test_recursion_with_many_globals() {
+        // 呼び出しごとに環境全体を複製しないので、グローバル変数が多くても再帰が遅くならない
+        let mut input = String::new();
+        for i in 0..200 {
+            input.push_str(&format!("val global_{} = [{}, {}, {}]\n", i, i, i + 1, i + 2));
+        }
+        input.push_str(r#"
+        fun sum_to(n: number): number {
+            if (n == 0) {
+                return 0
+            }
+            return n + sum_to(n - 1)
+        }
+        val mut total = 0
+        for i in range(200) {
+            total = total + sum_to(5)
+        }
+        total
+        "#);
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(*results.last().unwrap(), Value::Number(Fraction::from(3000)));
+        // 呼び出しのスコープはすべて破棄されている
+        assert_eq!(env.get_current_scope(), "global");
+        assert!(env.get(&"n".to_string(), None).is_none());
+    }
+
+    #[test]
+    fn test_recursive_call_inside_loop_keeps_loop_variable() {
+        let input = r#"
+        fun walk(depth: number): number {
+            val mut total = 0
+            for i in [1, 2] {
+                val sub = if (depth > 0) {
+                    walk(depth - 1)
+                } else {
+                    0
+                }
+                total = total + sub + i
+            }
+            return total
+        }
+        walk(2)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        // walk(0) = 3, walk(1) = 2 * 3 + 3 = 9, walk(2) = 2 * 9 + 3 = 21
+        assert_eq!(results[1], Value::Number(Fraction::from(21)));
+    }
+
+    #[test]
+    fn test_failed_call_restores_scope() {
+        let input = r#"
+        fun fail(x: number): number {
+            val y = x
+            return error("failed")
+        }
+        fail(1)
+        "#.to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env);
+        assert_eq!(result.unwrap_err().message, "failed");
+        assert_eq!(env.get_current_scope(), "global");
+        assert!(env.get(&"y".to_string(), None).is_none());
+    }
+
     #[test]
     fn test_scope_management_in_function() {
         let input = r#"
@@ -481,7 +1005,7 @@ mod tests {
         add_and_return(5)
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -511,7 +1035,7 @@ mod tests {
         f1(2, 0)
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -559,7 +1083,7 @@ mod tests {
         |2, 0| -> f1
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -575,7 +1099,7 @@ mod tests {
         x
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -591,7 +1115,7 @@ mod tests {
         x
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -601,7 +1125,7 @@ mod tests {
         x
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -627,7 +1151,7 @@ mod tests {
             complex_test()
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines().unwrap();
         let results = evals(ast, &mut env).unwrap();
@@ -677,4 +1201,89 @@ mod tests {
         };
         assert_eq!(Value::Bool(false), eval(ast, &mut env).unwrap());
     }
+
+    #[test]
+    fn test_eval_program_calls_main() {
+        let input = std::fs::read_to_string("example/main_function.sag").unwrap();
+        let mut env = Env::new();
+        let tokens = tokenize(&input).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        assert_eq!(eval_program(ast, &mut env).unwrap(), Value::Number(Fraction::from(42)));
+    }
+
+    #[test]
+    fn test_eval_program_without_main() {
+        let input = "fun double(x: number): number {\n    return x * 2\n}\ndouble(4)";
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        assert_eq!(eval_program(ast, &mut env).unwrap(), Value::Number(Fraction::from(8)));
+    }
+
+    #[test]
+    fn test_call_function_before_definition() {
+        let input = r#"
+        val result = a(4)
+        fun a(x: number): number {
+            return b(x) + 1
+        }
+        fun b(x: number): number {
+            return x * 10
+        }
+        result
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result.last().unwrap(), &Value::Number(Fraction::from(41)));
+    }
+
+    #[test]
+    fn test_mutual_recursion() {
+        let input = r#"
+        fun is_even(n: number): bool {
+            if (n == 0) {
+                return true
+            }
+            return is_odd(n - 1)
+        }
+        fun is_odd(n: number): bool {
+            if (n == 0) {
+                return false
+            }
+            return is_even(n - 1)
+        }
+        is_even(10)
+        is_even(7)
+        is_odd(7)
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let result = evals(ast, &mut env).unwrap();
+        assert_eq!(result[2..], [Value::Bool(true), Value::Bool(false), Value::Bool(true)]);
+    }
+
+    #[test]
+    fn test_mutual_recursion_return_type_is_checked() {
+        // 後で定義する関数の戻り値の型も、呼び出し側の戻り値の検査に使われる
+        let input = r#"
+        fun f(n: number): bool {
+            return g(n)
+        }
+        fun g(n: number): number {
+            return n
+        }
+        "#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "Return type mismatch Expected type: Bool, Actual type: Number");
+    }
 }
@@ -1,8 +1,23 @@
+use crate::parsers::parse_error::render_source_line;
+use crate::evals::take_exit_code;
+
 #[derive(Debug, Clone)]
 pub struct RuntimeError {
     pub message: String,
     pub line: usize,
     pub column: usize,
+    // エラー発生時に評価中だった関数呼び出し(外側から順に並ぶ)
+    pub backtrace: Vec<CallFrame>,
+    // exit組み込み関数で評価を終了した場合の終了コード
+    pub exit_code: Option<i32>,
+}
+
+/// 関数の呼び出し元の位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
 }
 
 impl RuntimeError {
@@ -11,15 +26,117 @@ impl RuntimeError {
             message: message.to_string(),
             line,
             column,
+            backtrace: vec![],
+            exit_code: None,
+        }
+    }
+
+    /// exit組み込み関数による評価の終了
+    pub fn exit(code: i32, line: usize, column: usize) -> Self {
+        Self {
+            exit_code: Some(code),
+            ..Self::new(format!("exit({})", code).as_str(), line, column)
+        }
+    }
+
+    /// 組み込み関数やそこから呼んだラムダのエラーを位置付きのエラーにする
+    /// 途中でexitが呼ばれていれば、メッセージではなくその終了コードで終了する
+    pub fn from_builtin(message: &str, line: usize, column: usize) -> Self {
+        match take_exit_code() {
+            Some(code) => Self::exit(code, line, column),
+            None => Self::new(message, line, column),
         }
     }
 
     pub fn message_with_source(&self, source: &str) -> String {
-        let lines: Vec<&str> = source.lines().collect();
-        let error_line = lines.get(self.line - 1).unwrap_or(&"");
-        format!(
-            "Runtime Error: {}\n --> line {}, column {}\n | {}\n | {}^",
-            self.message, self.line, self.column, error_line, " ".repeat(self.column)
-        )
+        let mut message = format!(
+            "Runtime Error: {}\n --> line {}, column {}\n{}",
+            self.message, self.line, self.column, render_source_line(source, self.line, self.column, self.column + 1)
+        );
+        if !self.backtrace.is_empty() {
+            message.push_str("\nBacktrace (most recent call first):");
+            for frame in self.backtrace.iter().rev() {
+                message.push_str(&format!("\n  at {} (line {}, column {})", frame.name, frame.line, frame.column));
+            }
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::evals::evals;
+    use crate::parsers::Parser;
+    use crate::tokenizer::tokenize;
+
+    #[test]
+    fn test_caret_after_tab() {
+        let error = RuntimeError::new("Variable not found: \"x\"", 1, 2);
+        let message = error.message_with_source("\tx");
+        assert!(message.ends_with(" |     x\n |     ^"));
+    }
+
+    #[test]
+    fn test_error_line_after_lone_carriage_return() {
+        let input = "val x = 1\rlen(1)";
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        assert_eq!(error.line, 2);
+        let message = error.message_with_source(input);
+        assert!(message.contains(" --> line 2, column 1\n | len(1)\n | ^"));
+    }
+
+    #[test]
+    fn test_backtrace_lists_function_calls() {
+        let input = r#"
+fun inner(x: number): number {
+    return x + missing
+}
+fun middle(x: number): number {
+    return inner(x)
+}
+fun outer(x: number): number {
+    return middle(x)
+}
+outer(1)
+"#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let error = evals(ast, &mut env).unwrap_err();
+        let names = error.backtrace.iter().map(|frame| frame.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["outer", "middle", "inner"]);
+        let message = error.message_with_source(input);
+        assert!(message.contains("Backtrace (most recent call first):\n  at inner (line 6, column 12)\n  at middle (line 9, column 12)\n  at outer (line 11, column 1)"));
+        assert!(env.call_stack().is_empty());
+    }
+
+    #[test]
+    fn test_lambda_call_pops_call_frame() {
+        let input = r#"
+val inc = \|x| => x + 1
+inc(1)
+inc(2)
+val broken = \|x| => x + missing
+broken(3)
+"#;
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let (succeeded, failed) = ast.split_at(3);
+        evals(succeeded.to_vec(), &mut env).unwrap();
+        assert!(env.call_stack().is_empty());
+        let error = evals(failed.to_vec(), &mut env).unwrap_err();
+        let names = error.backtrace.iter().map(|frame| frame.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["broken"]);
+        assert!(env.call_stack().is_empty());
     }
 }
@@ -70,7 +70,36 @@ pub fn struct_instance_node(name: String, fields: HashMap<String, ASTNode>, _lin
     })
 }
 
+/// baseの構造体インスタンスを複製し、指定されたフィールドだけを置き換えた新しいインスタンスを返す
+pub fn struct_update_node(base: Box<ASTNode>, fields: HashMap<String, ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    let base_value = eval(*base, env)?;
+    let (name, mut struct_fields) = match base_value {
+        Value::StructInstance { name, fields } => (name, fields),
+        _ => return Err(RuntimeError::new(format!("with requires a struct instance: {:?}", base_value).as_str(), line, column)),
+    };
+    for (field_name, field_value) in fields {
+        let current_value = match struct_fields.get(&field_name) {
+            Some(value) => value,
+            None => return Err(RuntimeError::new(format!("Field not found: {:?}", field_name).as_str(), line, column)),
+        };
+        let updated_value = eval(field_value, env)?;
+        if current_value.value_type() != updated_value.value_type() {
+            return Err(RuntimeError::new(format!("Field type mismatch: {:?}", field_name).as_str(), line, column));
+        }
+        struct_fields.insert(field_name, updated_value);
+    }
+    Ok(Value::StructInstance {
+        name,
+        fields: struct_fields,
+    })
+}
+
 pub fn struct_field_assign_node(instance: Box<ASTNode>, updated_field_name: String, updated_value_ast: Box<ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    if let ASTNode::StructFieldAccess { instance: ref parent, .. } = *instance
+        && let ASTNode::StructFieldAccess { .. } = **parent
+    {
+        return nested_struct_field_assign_node(*instance, updated_value_ast, line, column, env);
+    }
     match *instance {
         ASTNode::StructFieldAccess { instance, field_name: _, line, column  } => {
             match *instance {
@@ -79,7 +108,10 @@ pub fn struct_field_assign_node(instance: Box<ASTNode>, updated_field_name: Stri
                         Some(ValueType::Struct{name, fields, ..}) if variable_name == "self" => {
                             match env.get_struct(&name) {
                                 Some(Value::Struct { fields: _, methods, .. }) => {
-                                    let scope = env.get_current_scope();
+                                    // ブロックのスコープを飛ばしてメソッドのスコープを探す
+                                    let scope = env
+                                        .find_scope(|scope| methods.contains_key(scope))
+                                        .unwrap_or_else(|| env.get_current_scope());
                                     match methods.get(&scope) {
                                         Some(MethodInfo {arguments, ..}) => {
                                             let first_argument = arguments.first();
@@ -197,6 +229,56 @@ pub fn struct_field_assign_node(instance: Box<ASTNode>, updated_field_name: Stri
     }
 }
 
+/// a.b.x = 1 のような入れ子のフィールドへの代入
+/// 途中の構造体も値なので、末端のフィールドを書き換えた後に根の変数まで組み立て直して保存する
+fn nested_struct_field_assign_node(instance: ASTNode, updated_value_ast: Box<ASTNode>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    let mut path = vec![];
+    let mut node = instance;
+    let variable_name = loop {
+        match node {
+            ASTNode::StructFieldAccess { instance, field_name, .. } => {
+                path.push(field_name);
+                node = *instance;
+            }
+            ASTNode::Variable { name, .. } => break name,
+            _ => return Err(RuntimeError::new(format!("Unexpected assignment target: {:?}", node).as_str(), line, column)),
+        }
+    };
+    path.reverse();
+    let variable = match env.get(&variable_name, None) {
+        Some(variable) => variable.clone(),
+        None => return Err(RuntimeError::new(format!("Variable not found: {:?}", variable_name).as_str(), line, column)),
+    };
+    let updated_value = eval(*updated_value_ast, env)?;
+    let root = replace_field_path(variable.value, &path, updated_value).map_err(|e| RuntimeError::new(e.as_str(), line, column))?;
+    env.set(variable_name, root.clone(), EnvVariableType::Mutable, variable.value_type, false)
+        .map_err(|e| RuntimeError::new(e.as_str(), line, column))?;
+    Ok(root)
+}
+
+/// pathで辿った先のフィールドを置き換えた構造体インスタンスを返す
+fn replace_field_path(value: Value, path: &[String], updated_value: Value) -> Result<Value, String> {
+    let (field_name, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(updated_value),
+    };
+    match value {
+        Value::StructInstance { name, mut fields } => {
+            let field_value = match fields.remove(field_name) {
+                Some(field_value) => field_value,
+                None => return Err(format!("Field not found: {:?}", field_name)),
+            };
+            let replaced = replace_field_path(field_value.clone(), rest, updated_value)?;
+            if rest.is_empty() && field_value.value_type() != replaced.value_type() {
+                return Err(format!("Struct field type mismatch: {}.{}:{:?} = {:?}", name, field_name, field_value.value_type(), replaced.value_type()));
+            }
+            fields.insert(field_name.clone(), replaced);
+            Ok(Value::StructInstance { name, fields })
+        }
+        _ => Err(format!("Unexpected value type: {:?}", value)),
+    }
+}
+
 pub fn struct_field_access_node(instance: Box<ASTNode>, field_name: String, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
     let struct_obj = match *instance {
         ASTNode::Variable { name: variable_name, value_type, line, column } => {
@@ -218,9 +300,8 @@ pub fn struct_field_access_node(instance: Box<ASTNode>, field_name: String, line
                 _ => return Err(RuntimeError::new(format!("Unexpected value type: {:?}", value_type).as_str(), line, column)),
             }
         },
-        _ => {
-            return Err(RuntimeError::new(format!("Unexpected value type: {:?}", instance).as_str(), line, column));
-        }
+        // a.b.cのaやa.bのように、変数以外の式は評価した値のフィールドを読む
+        instance => eval(instance, env)?,
     };
     match struct_obj {
         Value::Struct {fields, ..} => {
@@ -249,6 +330,7 @@ mod tests {
     use crate::builtin::register_builtins;
     use fraction::Fraction;
     use crate::evals::evals;
+    use crate::evals::method_call_node::struct_instance_to_string;
 
 
     #[test]
@@ -269,7 +351,7 @@ mod tests {
             foo.value
         "#;
 
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut env = Env::new();
         let builtins = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtins).parse_lines();
@@ -295,7 +377,7 @@ mod tests {
             foo.value
         "#;
 
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut env = Env::new();
         let builtins = register_builtins(&mut env);
         let asts = Parser::new(tokens.to_vec(), builtins).parse_lines();
@@ -321,7 +403,7 @@ mod tests {
             foo.value
         "#;
 
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut env = Env::new();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens.to_vec(), builtin).parse_lines();
@@ -360,7 +442,7 @@ point.move(5, 2)
 point.clear()
 "#;
 
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut env = Env::new();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines();
@@ -394,7 +476,7 @@ point.clear()
             point.x = "hello"
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         assert_eq!(evals(asts, &mut env).is_err(), true);
@@ -413,7 +495,7 @@ point.clear()
             point.x
         "#;
         let mut env = Env::new();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let result = evals(asts, &mut env).unwrap();
@@ -431,7 +513,7 @@ point.clear()
             val mut point = Point{x: 1, y: 2}
             point
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let result = evals(asts, &mut env).unwrap();
@@ -453,11 +535,211 @@ point.clear()
                 y: number
             }
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut env);
         let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
         let _ = evals(asts, &mut env);
         assert_eq!(env.get_struct(&"Point".to_string()).is_some(), true);
         assert_eq!(env.get_struct(&"DummuStruct".to_string()).is_some(), false);
     }
+
+    #[test]
+    fn test_struct_instance_default_display() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Point {
+                x: number,
+                y: number
+            }
+            val mut point = Point{x: 1, y: 2}
+            point.to_string()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::String("Point { x: 1, y: 2 }".to_string())));
+        assert_eq!(format!("{}", result[1]), "Point { x: 1, y: 2 }");
+    }
+
+    #[test]
+    fn test_struct_instance_custom_to_string() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Point {
+                x: number,
+                y: number
+            }
+            impl Point {
+                fun to_string(self): string {
+                    val x = self.x
+                    val y = self.y
+                    "(" + x.to_string() + ", " + y.to_string() + ")"
+                }
+            }
+            val mut point = Point{x: 1, y: 2}
+            point.to_string()
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::String("(1, 2)".to_string())));
+        let point = env.get(&"point".to_string(), None).unwrap().value.clone();
+        assert_eq!(struct_instance_to_string(&point, &env, 0, 0).unwrap(), "(1, 2)");
+    }
+
+    #[test]
+    fn test_nested_struct_field_access() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Leaf {
+                value: number
+            }
+            struct Branch {
+                leaf: Leaf
+            }
+            struct Tree {
+                branch: Branch
+            }
+            val tree = Tree{branch: Branch{leaf: Leaf{value: 5}}}
+            val doubled = tree.branch.leaf.value * 2
+            doubled
+            tree.branch.leaf
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[5], Value::Number(Fraction::from(10)));
+        assert_eq!(result[6], Value::StructInstance {
+            name: "Leaf".to_string(),
+            fields: HashMap::from([("value".to_string(), Value::Number(Fraction::from(5)))]),
+        });
+    }
+
+    #[test]
+    fn test_nested_struct_field_assign() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Inner {
+                x: number
+            }
+            struct Outer {
+                b: Inner,
+                label: string
+            }
+            val mut a = Outer{b: Inner{x: 1}, label: "a"}
+            a.b.x = 42
+            a.b.x
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(42))));
+        let a = env.get(&"a".to_string(), None).unwrap().value.clone();
+        assert_eq!(a, Value::StructInstance {
+            name: "Outer".to_string(),
+            fields: HashMap::from([
+                ("b".to_string(), Value::StructInstance {
+                    name: "Inner".to_string(),
+                    fields: HashMap::from([("x".to_string(), Value::Number(Fraction::from(42)))]),
+                }),
+                ("label".to_string(), Value::String("a".to_string())),
+            ]),
+        });
+    }
+
+    #[test]
+    fn test_nested_struct_field_assign_to_immutable_is_error() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Inner {
+                x: number
+            }
+            struct Outer {
+                b: Inner
+            }
+            val a = Outer{b: Inner{x: 1}}
+            a.b.x = 42
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let error = evals(asts, &mut env).unwrap_err();
+        assert_eq!(error.message, "Cannot reassign to immutable variable: \"a\"");
+    }
+
+    #[test]
+    fn test_struct_instance_equality() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Point {
+                x: number,
+                y: number
+            }
+            struct Size {
+                x: number,
+                y: number
+            }
+            val a = Point{x: 1, y: 2}
+            val b = Point{y: 2, x: 1}
+            val c = Point{x: 1, y: 3}
+            val d = Size{x: 1, y: 2}
+            a == b
+            a == c
+            a == d
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[6..], [Value::Bool(true), Value::Bool(false), Value::Bool(false)]);
+    }
+
+    #[test]
+    fn test_struct_update_with() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Point {
+                x: number,
+                y: number
+            }
+            val p = Point{x: 1, y: 2}
+            val q = p with { x: 5 }
+            q.x
+            q.y
+            p.x
+            p.y
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result[3..], [
+            Value::Number(Fraction::from(5)),
+            Value::Number(Fraction::from(2)),
+            Value::Number(Fraction::from(1)),
+            Value::Number(Fraction::from(2)),
+        ]);
+    }
+
+    #[test]
+    fn test_struct_update_with_unknown_field() {
+        let mut env = Env::new();
+        let input = r#"
+            struct Point {
+                x: number,
+                y: number
+            }
+            val p = Point{x: 1, y: 2}
+            p with { z: 5 }
+        "#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let error = evals(asts, &mut env).unwrap_err();
+        assert_eq!(error.message, "Field not found: \"z\"");
+    }
 }
@@ -4,9 +4,18 @@ use crate::evals::runtime_error::RuntimeError;
 
 pub fn variable_node(name: String, _value_type: Option<ValueType>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
     let value = env.get(&name, None);
-    if value.is_none() {
-        Err(RuntimeError::new(format!("Variable not found: {:?}", name).as_str(), line, column))
-    } else {
-        Ok(value.unwrap().value.clone())
+    if let Some(value) = value {
+        return Ok(value.value.clone());
     }
+    // 関数名を値として参照した場合はラムダとして扱う
+    if let Some(function) = env.get_function(&name).cloned()
+        && let Some(body) = function.body
+    {
+        return Ok(Value::Lambda {
+            arguments: function.arguments,
+            body: Box::new(body),
+            env: env.clone(),
+        });
+    }
+    Err(RuntimeError::new(format!("Variable not found: {:?}", name).as_str(), line, column))
 }
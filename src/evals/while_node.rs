@@ -0,0 +1,122 @@
+use crate::ast::ASTNode;
+use crate::value::Value;
+use crate::environment::Env;
+use crate::evals::eval;
+use crate::evals::runtime_error::RuntimeError;
+use crate::evals::for_node::is_loop_target;
+
+/// 条件を評価し、trueの間は本体を繰り返す
+pub fn while_node(condition: Box<ASTNode>, body: Box<ASTNode>, label: Option<String>, line: usize, column: usize, env: &mut Env) -> Result<Value, RuntimeError> {
+    loop {
+        env.count_step(line, column)?;
+        match eval(*condition.clone(), env)? {
+            Value::Bool(true) => {}
+            Value::Bool(false) => return Ok(Value::Void),
+            value => return Err(RuntimeError::new(format!("while condition must be a bool: {}", value).as_str(), line, column)),
+        }
+        let snapshot = env.enter_unique_scope("while");
+        let result = eval(*body.clone(), env);
+        env.restore_scope(snapshot);
+        match result? {
+            Value::Break(target) if is_loop_target(&target, &label) => return Ok(Value::Void),
+            result @ (Value::Return(_) | Value::Break(_)) => return Ok(result),
+            Value::Continue(target) if !is_loop_target(&target, &label) => return Ok(Value::Continue(target)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fraction::Fraction;
+    use crate::tokenizer::tokenize;
+    use crate::parsers::Parser;
+    use crate::evals::evals;
+    use crate::builtin::register_builtins;
+
+    fn run(input: &str) -> Result<Vec<Value>, RuntimeError> {
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        evals(ast, &mut env)
+    }
+
+    #[test]
+    fn test_while_skips_body_when_condition_is_false() {
+        let input = r#"
+        val mut count = 0
+        while false {
+            count = count + 1
+        }
+        count
+        "#;
+        let result = run(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(0))));
+    }
+
+    #[test]
+    fn test_while_loops_until_condition_is_false() {
+        let input = r#"
+        val mut i = 0
+        val mut sum = 0
+        while (i < 10) {
+            i = i + 1
+            if (i == 2) {
+                continue
+            }
+            if (i == 5) {
+                break
+            }
+            sum = sum + i
+        }
+        sum
+        "#;
+        let result = run(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(8))));
+    }
+
+    #[test]
+    fn test_while_non_bool_condition_is_error() {
+        let input = r#"
+        while 1 {
+            1
+        }
+        "#;
+        assert_eq!(run(input).unwrap_err().message, "while condition must be a bool: 1");
+    }
+
+    #[test]
+    fn test_infinite_while_stops_at_step_limit() {
+        let input = r#"
+        while true {
+        }
+        "#;
+        let mut env = Env::new();
+        env.set_step_limit(Some(100));
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        assert_eq!(evals(ast, &mut env).unwrap_err().message, "execution step limit exceeded");
+    }
+
+    #[test]
+    fn test_step_limit_does_not_leak_into_another_env() {
+        let limited = {
+            let mut env = Env::new();
+            env.set_step_limit(Some(0));
+            env
+        };
+        let input = r#"
+        val mut i = 0
+        while i < 1000 {
+            i = i + 1
+        }
+        i
+        "#;
+        let result = run(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(1000))));
+        assert_eq!(limited.count_step(1, 0).unwrap_err().message, "execution step limit exceeded");
+    }
+}
@@ -0,0 +1,533 @@
+use crate::ast::ASTNode;
+use crate::environment::{ValueType, type_name};
+use crate::token::TokenKind;
+use crate::value::Value;
+use fraction::Fraction;
+
+const INDENT: &str = "    ";
+
+/// ASTから正規化したソースコードを生成する
+/// 文は1行に1つ、ブロックの中は4スペースで字下げし、関数や構造体の定義の前後には空行を入れる
+pub fn format_nodes(nodes: &[ASTNode]) -> Result<String, String> {
+    let mut output = String::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 && (is_function(node) || is_function(&nodes[i - 1])) {
+            output.push('\n');
+        }
+        output.push_str(&format_node(node, 0)?);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// トークナイザはコメントを捨ててしまうため、コメントを含むソースは整形しない
+/// 文字列の中はトークナイザと同じく\の次の文字を読み飛ばす
+pub fn contains_comment(source: &str) -> bool {
+    let chars: Vec<char> = source.chars().collect();
+    let mut in_string = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string && c == '\\' {
+            i += 2;
+            continue;
+        }
+        if c == '"' {
+            in_string = !in_string;
+        } else if !in_string && (chars[i..].starts_with(&['/', '/']) || chars[i..].starts_with(&['`', '`', '`'])) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+fn is_function(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::Function { .. } | ASTNode::Struct { .. } | ASTNode::Impl { .. } => true,
+        ASTNode::Public { node, .. } => is_function(node),
+        _ => false,
+    }
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
+
+/// ノードを整形する。2行目以降はdepthに合わせて字下げする
+fn format_node(node: &ASTNode, depth: usize) -> Result<String, String> {
+    match node {
+        ASTNode::Literal { value, .. } => format_literal(value),
+        ASTNode::Variable { name, .. } => Ok(name.clone()),
+        ASTNode::List { elements, .. } => Ok(format!("[{}]", format_list(elements, depth)?)),
+        ASTNode::Spread { expr, .. } => Ok(format!("...{}", format_operand(expr, depth)?)),
+        ASTNode::NamedArgument { name, value, .. } => Ok(format!("{}: {}", name, format_node(value, depth)?)),
+        ASTNode::PrefixOp { op, expr, .. } => Ok(format!("{}{}", operator(op)?, format_operand(expr, depth)?)),
+        ASTNode::BinaryOp { left, op, right, .. } => format_binary(node, left, operator(op)?, right, depth),
+        ASTNode::Eq { left, right, .. } => format_binary(node, left, "==", right, depth),
+        ASTNode::Gte { left, right, .. } => format_binary(node, left, ">=", right, depth),
+        ASTNode::Gt { left, right, .. } => format_binary(node, left, ">", right, depth),
+        ASTNode::Lte { left, right, .. } => format_binary(node, left, "<=", right, depth),
+        ASTNode::Lt { left, right, .. } => format_binary(node, left, "<", right, depth),
+        ASTNode::Assign { name, value, variable_type, value_type, is_new, .. } => {
+            let value_source = format_node(value, depth)?;
+            if !is_new {
+                return Ok(format!("{} = {}", name, value_source));
+            }
+            let keyword = match variable_type {
+                crate::environment::EnvVariableType::Mutable => "val mut",
+                crate::environment::EnvVariableType::Immutable => "val",
+            };
+            match assign_annotation(value, value_type) {
+                Some(type_name) => Ok(format!("{} {}: {} = {}", keyword, name, type_name, value_source)),
+                None => Ok(format!("{} {} = {}", keyword, name, value_source)),
+            }
+        }
+        ASTNode::Function { name, arguments, body, return_type, .. } => {
+            let return_type = match return_type {
+                ValueType::Void => String::new(),
+                _ => format!(": {}", type_name(return_type).ok_or(format!("cannot format return type of {}", name))?),
+            };
+            Ok(format!("fun {}({}){} {}", name, format_params(arguments, true)?, return_type, format_node(body, depth)?))
+        }
+        ASTNode::FunctionCall { name, arguments, .. } => Ok(format!("{}({})", name, format_arguments(arguments, depth)?)),
+        ASTNode::MethodCall { method_name, caller, arguments, .. } => {
+            Ok(format!("{}.{}({})", format_operand(caller, depth)?, method_name, format_arguments(arguments, depth)?))
+        }
+        ASTNode::FunctionCallArgs { args, .. } => Ok(format!("|{}|", format_list(args, depth)?)),
+        ASTNode::Return { expr, .. } => Ok(format!("return {}", format_node(expr, depth)?)),
+        ASTNode::Break { label, .. } => Ok(format_jump("break", label)),
+        ASTNode::Continue { label, .. } => Ok(format_jump("continue", label)),
+        ASTNode::Lambda { arguments, body, .. } => {
+            Ok(format!("\\|{}| => {}", format_params(arguments, false)?, format_node(body, depth)?))
+        }
+        ASTNode::LambdaCall { lambda, arguments, .. } => {
+            let argument = match arguments.as_slice() {
+                [argument] => format_node(argument, depth)?,
+                _ => return Err("cannot format lambda call with multiple arguments".to_string()),
+            };
+            Ok(format!("{} -> ({})", argument, format_node(lambda, depth)?))
+        }
+        ASTNode::Block { nodes, .. } => {
+            if nodes.is_empty() {
+                return Ok("{}".to_string());
+            }
+            let mut output = "{\n".to_string();
+            for statement in nodes {
+                output.push_str(&indent(depth + 1));
+                output.push_str(&format_node(statement, depth + 1)?);
+                output.push('\n');
+            }
+            output.push_str(&indent(depth));
+            output.push('}');
+            Ok(output)
+        }
+        ASTNode::If { condition, then, else_, .. } => {
+            let mut output = format!("if ({}) {}", format_node(condition, depth)?, format_node(then, depth)?);
+            if let Some(else_) = else_ {
+                output.push_str(&format!(" else {}", format_node(else_, depth)?));
+            }
+            Ok(output)
+        }
+        ASTNode::For { variable, iterable, body, label, .. } => {
+            Ok(format!("{}for {} in {} {}", format_label(label), variable, format_node(iterable, depth)?, format_node(body, depth)?))
+        }
+        ASTNode::While { condition, body, label, .. } => {
+            Ok(format!("{}while {} {}", format_label(label), format_node(condition, depth)?, format_node(body, depth)?))
+        }
+        ASTNode::DoWhile { body, condition, label, .. } => {
+            Ok(format!("{}do {} while ({})", format_label(label), format_node(body, depth)?, format_node(condition, depth)?))
+        }
+        ASTNode::TypePattern { name, value_type, .. } => {
+            Ok(format!("{}: {}", name, type_name(value_type).ok_or(format!("cannot format type pattern of {}", name))?))
+        }
+        ASTNode::OptionSome { value, .. } => Ok(format!("Some({})", format_node(value, depth)?)),
+        ASTNode::OptionNone { .. } => Ok("None".to_string()),
+        ASTNode::ResultSuccess { value, .. } => Ok(format!("Suc({})", format_node(value, depth)?)),
+        ASTNode::ResultFailure { value, .. } => Ok(format!("Fail({})", format_node(value, depth)?)),
+        ASTNode::Match { expression, cases, .. } => {
+            let mut output = format!("match {} {{\n", format_operand(expression, depth)?);
+            for (pattern, body) in cases {
+                output.push_str(&format!("{}{} => {}\n", indent(depth + 1), format_node(pattern, depth + 1)?, format_node(body, depth + 1)?));
+            }
+            output.push_str(&indent(depth));
+            output.push('}');
+            Ok(output)
+        }
+        ASTNode::DictKeyAccess { dict: collection, key: index, .. }
+        | ASTNode::ListIndexAccess { list: collection, index, .. } => {
+            Ok(format!("{}[{}]", format_operand(collection, depth)?, format_node(index, depth)?))
+        }
+        ASTNode::DictAssign { dict: collection, key: index, value, .. }
+        | ASTNode::ListIndexAssign { list: collection, index, value, .. } => {
+            Ok(format!("{}[{}] = {}", format_operand(collection, depth)?, format_node(index, depth)?, format_node(value, depth)?))
+        }
+        ASTNode::StructFieldAccess { instance, field_name, .. } => Ok(format!("{}.{}", format_operand(instance, depth)?, field_name)),
+        // instanceは代入先のフィールドまで含んだアクセスになっている
+        ASTNode::StructFieldAssign { instance, value, .. } => {
+            Ok(format!("{} = {}", format_node(instance, depth)?, format_node(value, depth)?))
+        }
+        ASTNode::Struct { name, fields, .. } => {
+            let mut field_names = fields.keys().collect::<Vec<_>>();
+            field_names.sort();
+            let mut output = format!("struct {} {{\n", name);
+            for (i, field_name) in field_names.iter().enumerate() {
+                let (value_type, is_public) = match &fields[*field_name] {
+                    ASTNode::StructField { value_type, is_public, .. } => (value_type, *is_public),
+                    field => return Err(format!("unexpected struct field: {}", node_name(field))),
+                };
+                let type_name = type_name(value_type).ok_or(format!("cannot format type of field {}", field_name))?;
+                let separator = if i + 1 < field_names.len() { "," } else { "" };
+                let public = if is_public { "pub " } else { "" };
+                output.push_str(&format!("{}{}{}: {}{}\n", indent(depth + 1), public, field_name, type_name, separator));
+            }
+            output.push_str(&indent(depth));
+            output.push('}');
+            Ok(output)
+        }
+        ASTNode::StructInstance { name, fields, .. } => {
+            let mut field_names = fields.keys().collect::<Vec<_>>();
+            field_names.sort();
+            let fields = field_names.iter()
+                .map(|field_name| Ok(format!("{}: {}", field_name, format_node(&fields[*field_name], depth)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("{} {{ {} }}", name, fields.join(", ")))
+        }
+        ASTNode::StructUpdate { base, fields, .. } => {
+            let mut field_names = fields.keys().collect::<Vec<_>>();
+            field_names.sort();
+            let fields = field_names.iter()
+                .map(|field_name| Ok(format!("{}: {}", field_name, format_node(&fields[*field_name], depth)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("{} with {{ {} }}", format_node(base, depth)?, fields.join(", ")))
+        }
+        ASTNode::Impl { base_struct, methods, .. } => {
+            let name = type_name(base_struct).ok_or("cannot format impl target".to_string())?;
+            let methods = methods.iter()
+                .map(|method| Ok(format!("{}{}", indent(depth + 1), format_node(method, depth + 1)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("impl {} {{\n{}\n{}}}", name, methods.join("\n\n"), indent(depth)))
+        }
+        ASTNode::Method { name, arguments, body, return_type, .. } => {
+            let return_type = match return_type {
+                ValueType::Void => String::new(),
+                _ => format!(": {}", type_name(return_type).ok_or(format!("cannot format return type of {}", name))?),
+            };
+            Ok(format!("fun {}({}){} {}", name, format_params(arguments, true)?, return_type, format_node(body, depth)?))
+        }
+        ASTNode::Import { module_name, symbols, .. } => Ok(format!("import {} from {}", symbols.join(", "), module_name)),
+        ASTNode::Public { node, .. } => Ok(format!("pub {}", format_node(node, depth)?)),
+        _ => Err(format!("fmt does not support this syntax yet: {}", node_name(node))),
+    }
+}
+
+fn format_label(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{}: ", label),
+        None => String::new(),
+    }
+}
+
+fn format_jump(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} {}", keyword, label),
+        None => keyword.to_string(),
+    }
+}
+
+fn node_name(node: &ASTNode) -> String {
+    let debug = format!("{:?}", node);
+    debug.split([' ', '{', '(']).next().unwrap_or_default().to_string()
+}
+
+fn format_list(elements: &[ASTNode], depth: usize) -> Result<String, String> {
+    Ok(elements.iter().map(|element| format_node(element, depth)).collect::<Result<Vec<_>, _>>()?.join(", "))
+}
+
+fn format_arguments(arguments: &ASTNode, depth: usize) -> Result<String, String> {
+    match arguments {
+        ASTNode::FunctionCallArgs { args, .. } => format_list(args, depth),
+        _ => Err(format!("unexpected arguments: {}", node_name(arguments))),
+    }
+}
+
+/// 関数の引数は型注釈が必須で、ラムダの引数は型注釈を省略できる
+fn format_params(params: &[ASTNode], require_type: bool) -> Result<String, String> {
+    let mut formatted = vec![];
+    for param in params {
+        match param {
+            ASTNode::Variable { value_type: Some(ValueType::SelfType), .. } => formatted.push("self".to_string()),
+            ASTNode::Variable { value_type: Some(ValueType::MutSelfType), .. } => formatted.push("mut self".to_string()),
+            ASTNode::Variable { name, value_type, .. } => match value_type.as_ref().and_then(type_name) {
+                Some(type_name) => formatted.push(format!("{}: {}", name, type_name)),
+                None if !require_type => formatted.push(name.clone()),
+                None => return Err(format!("cannot format type of parameter {}", name)),
+            },
+            _ => return Err(format!("unexpected parameter: {}", node_name(param))),
+        }
+    }
+    Ok(formatted.join(", "))
+}
+
+/// 型注釈で結果が変わるのはOption/Resultのリテラルと、値の型を制約するDictだけなので、それ以外は推論に任せて省略する
+fn assign_annotation(value: &ASTNode, value_type: &ValueType) -> Option<String> {
+    let is_option_or_result_literal = matches!(
+        value,
+        ASTNode::OptionSome { .. } | ASTNode::OptionNone { .. } | ASTNode::ResultSuccess { .. } | ASTNode::ResultFailure { .. }
+    );
+    match value_type {
+        ValueType::OptionType(_) | ValueType::ResultType { .. } if is_option_or_result_literal => type_name(value_type),
+        // 型注釈のない辞書はDict<string, Any>なので、type_nameがNoneになり注釈は付かない
+        ValueType::Dict(_) => type_name(value_type),
+        _ => None,
+    }
+}
+
+fn format_literal(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Number(number) => Ok(format_number(number)),
+        Value::String(string) => Ok(quote_string(string)),
+        Value::Bool(bool) => Ok(bool.to_string()),
+        Value::Void => Ok("Void".to_string()),
+        Value::List(values) => {
+            Ok(format!("[{}]", values.iter().map(format_literal).collect::<Result<Vec<_>, _>>()?.join(", ")))
+        }
+        Value::Dict(dict) => {
+            let entries = dict.iter()
+                .map(|(key, value)| Ok(format!("{} => {}", quote_string(key), format_literal(value)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("{{: {} :}}", entries.join(", ")))
+        }
+        _ => Err(format!("cannot format literal: {}", value)),
+    }
+}
+
+/// 文字列リテラルを、トークナイザが解釈するエスケープシーケンスに戻して引用符で囲む
+fn quote_string(string: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 数値リテラルは小数で書き出す。有限小数にならない分数は割り算の式にする
+fn format_number(number: &Fraction) -> String {
+    let sign = if number.is_sign_negative() && *number != Fraction::from(0) { "-" } else { "" };
+    let (numer, denom) = match (number.numer(), number.denom()) {
+        (Some(numer), Some(denom)) => (*numer, *denom),
+        _ => return format!("{}", number),
+    };
+    if denom == 1 {
+        return format!("{}{}", sign, numer);
+    }
+    let mut rest = denom;
+    while rest % 2 == 0 {
+        rest /= 2;
+    }
+    while rest % 5 == 0 {
+        rest /= 5;
+    }
+    if rest != 1 {
+        return format!("({}{} / {})", sign, numer, denom);
+    }
+    let mut digits = String::new();
+    let mut remainder = numer % denom;
+    while remainder != 0 {
+        remainder *= 10;
+        digits.push_str(&(remainder / denom).to_string());
+        remainder %= denom;
+    }
+    format!("{}{}.{}", sign, numer / denom, digits)
+}
+
+fn operator(op: &TokenKind) -> Result<&'static str, String> {
+    match op {
+        TokenKind::Plus => Ok("+"),
+        TokenKind::Minus => Ok("-"),
+        TokenKind::Mul => Ok("*"),
+        TokenKind::Div => Ok("/"),
+        TokenKind::Mod => Ok("%"),
+        TokenKind::Pow => Ok("**"),
+        TokenKind::And => Ok("and"),
+        TokenKind::Or => Ok("or"),
+        TokenKind::Xor => Ok("xor"),
+        _ => Err(format!("cannot format operator: {:?}", op)),
+    }
+}
+
+/// パーサーのget_priorityと同じ (左の優先度, 右の優先度)
+fn priority(node: &ASTNode) -> Option<(u8, u8)> {
+    match node {
+        ASTNode::Lt { .. } | ASTNode::Gt { .. } | ASTNode::Lte { .. } | ASTNode::Gte { .. } => Some((1, 2)),
+        ASTNode::Eq { .. } => Some((2, 3)),
+        ASTNode::BinaryOp { op, .. } => match op {
+            TokenKind::And => Some((3, 4)),
+            TokenKind::Xor => Some((4, 5)),
+            TokenKind::Or => Some((5, 6)),
+            TokenKind::Plus | TokenKind::Minus => Some((6, 7)),
+            TokenKind::Mul | TokenKind::Div | TokenKind::Mod => Some((7, 8)),
+            TokenKind::Pow => Some((8, 8)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// 演算子の両辺は、括弧がないと結合の仕方が変わる場合だけ括弧で囲む
+fn format_binary(node: &ASTNode, left: &ASTNode, op: &str, right: &ASTNode, depth: usize) -> Result<String, String> {
+    let (left_priority, right_priority) = priority(node).ok_or(format!("cannot format operator of {}", node_name(node)))?;
+    let left_source = match priority(left) {
+        Some((_, child_right)) if left_priority >= child_right => format!("({})", format_node(left, depth)?),
+        Some(_) => format_node(left, depth)?,
+        None => format_operand(left, depth)?,
+    };
+    let right_source = match priority(right) {
+        Some((child_left, _)) if child_left < right_priority => format!("({})", format_node(right, depth)?),
+        Some(_) => format_node(right, depth)?,
+        None => format_operand(right, depth)?,
+    };
+    Ok(format!("{} {} {}", left_source, op, right_source))
+}
+
+/// 演算子の項やメソッドの呼び出し元になる式。後ろの式を取り込んでしまう構文は括弧で囲む
+fn format_operand(node: &ASTNode, depth: usize) -> Result<String, String> {
+    match node {
+        ASTNode::BinaryOp { .. }
+        | ASTNode::Eq { .. }
+        | ASTNode::Gte { .. }
+        | ASTNode::Gt { .. }
+        | ASTNode::Lte { .. }
+        | ASTNode::Lt { .. }
+        | ASTNode::PrefixOp { .. }
+        | ASTNode::Lambda { .. }
+        | ASTNode::LambdaCall { .. }
+        | ASTNode::If { .. }
+        | ASTNode::Match { .. } => Ok(format!("({})", format_node(node, depth)?)),
+        _ => format_node(node, depth),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::parsers::Parser;
+    use crate::tokenizer::tokenize;
+
+    fn parse(source: &str) -> Vec<ASTNode> {
+        let mut env = Env::new();
+        let tokens = tokenize(&source.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        parser.parse_lines().unwrap()
+    }
+
+    /// 位置情報(line, column)を除いたASTの構造
+    fn structure(nodes: &[ASTNode]) -> String {
+        let mut rest = format!("{:?}", nodes);
+        for label in ["line: ", "column: "] {
+            let mut result = String::new();
+            while let Some(start) = rest.find(label) {
+                result.push_str(&rest[..start]);
+                rest = rest[start + label.len()..].trim_start_matches(|c: char| c.is_ascii_digit()).to_string();
+            }
+            result.push_str(&rest);
+            rest = result;
+        }
+        rest
+    }
+
+    #[test]
+    fn test_format_escapes_strings() {
+        let source = r#"print("a\"b\n")
+val d = {: "tab\tkey" => "back\\slash\r" :}
+"#;
+        let ast = parse(source);
+        let formatted = format_nodes(&ast).unwrap();
+        assert_eq!(formatted, source);
+        assert_eq!(structure(&parse(&formatted)), structure(&ast));
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let source = r#"
+fun add(a:number,b:number):number{
+return a+b*2
+}
+val mut total=0
+for i in range(1,4) { total = total + add(i, (i - 1) * 3) }
+val xs=[1,  2.5,total]
+val doubled = xs.map(\|x| => x*2)
+val message = if (total>10) {"big"} else {"small"}
+val o: Option<number> = None
+val found = match Some(3) {
+    Some(v) => { v + 1 }
+    _ => { 0 }
+}
+"#;
+        let ast = parse(source);
+        let formatted = format_nodes(&ast).unwrap();
+        assert_eq!(formatted, r#"fun add(a: number, b: number): number {
+    return a + b * 2
+}
+
+val mut total = 0
+for i in range(1, 4) {
+    total = total + add(i, (i - 1) * 3)
+}
+val xs = [1, 2.5, total]
+val doubled = xs.map(\|x| => x * 2)
+val message = if (total > 10) {
+    "big"
+} else {
+    "small"
+}
+val o: Option<number> = None
+val found = match Some(3) {
+    Some(v) => {
+        v + 1
+    }
+    _ => {
+        0
+    }
+}
+"#);
+        let reparsed = parse(&formatted);
+        assert_eq!(structure(&reparsed), structure(&ast));
+        assert_eq!(format_nodes(&reparsed).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_struct_field_assign() {
+        let source = "struct Inner {\n    x: number\n}\nstruct Outer {\n    b: Inner\n}\nval mut a = Outer{b: Inner{x: 1}}\na.b.x=42\n";
+        let formatted = format_nodes(&parse(source)).unwrap();
+        assert!(formatted.ends_with("val mut a = Outer { b: Inner { x: 1 } }\na.b.x = 42\n"));
+    }
+
+    #[test]
+    fn test_format_keeps_needed_parentheses() {
+        let ast = parse("(1 + 2) * 3 - (4 - 5)");
+        let formatted = format_nodes(&ast).unwrap();
+        assert_eq!(formatted, "(1 + 2) * 3 - (4 - 5)\n");
+        assert_eq!(structure(&parse(&formatted)), structure(&ast));
+    }
+
+    #[test]
+    fn test_contains_comment() {
+        assert!(contains_comment("val x = 1 // one"));
+        assert!(contains_comment("```\nblock\n```\nval x = 1"));
+        assert!(!contains_comment("val url = \"http://example.com\""));
+        // エスケープした"で文字列は終わらない
+        assert!(!contains_comment(r#"val s = "a\"//""#));
+        assert!(contains_comment(r#"val s = "a\\" // c"#));
+    }
+}
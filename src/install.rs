@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use std::fs;
+use crate::environment::PACKAGE_DIR;
 
 fn install_local_package(source_path: PathBuf, target_dir: PathBuf) {
     let file_name = source_path.file_name().unwrap();
@@ -17,7 +18,7 @@ fn install_remote_package(_package_name: String, _target_dir: PathBuf) {
 
 pub fn install_package(package_or_path: String) {
     let path = PathBuf::from(&package_or_path);
-    let target_dir = PathBuf::from("./.sag_packages/");
+    let target_dir = PathBuf::from(PACKAGE_DIR);
 
     if !target_dir.exists() {
         fs::create_dir_all(&target_dir).expect("Failed to create package directory");
@@ -8,4 +8,4 @@ mod ast;
 mod value;
 mod token;
 
-pub use wasm::evaluate;
+pub use wasm::{evaluate, set_fraction_display_digits};
@@ -8,13 +8,18 @@ mod ast;
 mod value;
 mod token;
 mod install;
+mod formatter;
 
-use crate::builtin::register_builtins;
-use crate::environment::Env;
-use crate::evals::{eval, evals};
+use crate::builtin::{register_builtins, set_script_args};
+use crate::environment::{Env, ValueType, type_name};
+use std::collections::HashMap;
+use crate::evals::{eval, eval_program};
 use crate::parsers::Parser as SagParser;
 use crate::tokenizer::tokenize;
 use crate::install::install_package;
+use crate::formatter::{contains_comment, format_nodes};
+use crate::value::{Value, set_fraction_display_digits};
+use fraction::ToPrimitive;
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -31,16 +36,45 @@ enum Commands {
     },
     Run {
         file_path: String,
+        /// `--`より後ろの引数はスクリプトにargs()で渡す
+        #[arg(last = true)]
+        script_args: Vec<String>,
     },
     Repl,
+    Fmt {
+        file_path: String,
+    },
+}
+
+/// REPLの`:type <expr>`で、式を評価せずに推論した型を型名で返す
+fn infer_expression_type(source: &str, builtins: &HashMap<(String, String), ValueType>) -> Result<String, String> {
+    let tokens = tokenize(&source.to_string()).map_err(|e| e.message_with_source(source))?;
+    let mut parser = SagParser::new(tokens, builtins.clone());
+    let ast_node = parser.parse().map_err(|e| e.message_with_source(source))?;
+    let value_type = parser.infer_type(&ast_node)?;
+    Ok(type_name(&value_type).unwrap_or_else(|| format!("{:?}", value_type)))
 }
 
-fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
+/// REPLを実行し、exitが呼ばれたらその終了コードを返す
+fn run_repl() -> Result<i32, Box<dyn std::error::Error>> {
     let mut env = Env::new();
     let builtins = register_builtins(&mut env);
     for line in std::io::stdin().lines() {
         let line = line?;
-        let tokens = tokenize(&line);
+        if let Some(expression) = line.trim_start().strip_prefix(":type") {
+            match infer_expression_type(expression.trim(), &builtins) {
+                Ok(type_name) => println!("{}", type_name),
+                Err(message) => eprintln!("{}", message),
+            }
+            continue;
+        }
+        let tokens = match tokenize(&line) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprint!("{}", e.message_with_source(&line));
+                continue;
+            }
+        };
         let mut parser = SagParser::new(tokens.to_vec(), builtins.clone());
         let ast_node = parser.parse();
         if let Err(e) = ast_node {
@@ -52,16 +86,28 @@ fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
         println!("---------");
         match result {
             Ok(value) => println!("res: {:?}", value),
-            Err(e) => eprint!("{}", e.message_with_source(&line)),
+            Err(e) => match e.exit_code {
+                Some(code) => return Ok(code),
+                None => eprint!("{}", e.message_with_source(&line)),
+            },
         }
     }
-    Ok(())
+    Ok(0)
 }
 
-fn run_file(file_path: String) -> Result<(), Box<dyn std::error::Error>> {
+/// ファイルを実行して終了コードを返す
+/// exitが呼ばれたらそのコード、引数なしのmain関数が整数を返したらその値、エラーなら1、それ以外は0になる
+fn run_file(file_path: String, script_args: Vec<String>) -> Result<i32, Box<dyn std::error::Error>> {
     let file = std::fs::read_to_string(file_path)?;
+    set_script_args(script_args);
 
-    let tokens = tokenize(&file);
+    let tokens = match tokenize(&file) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            eprint!("{}", e.message_with_source(&file));
+            return Ok(1);
+        }
+    };
     println!("tokens: {:?}", tokens);
     let mut env = Env::new();
     let builtins = register_builtins(&mut env);
@@ -69,33 +115,111 @@ fn run_file(file_path: String) -> Result<(), Box<dyn std::error::Error>> {
     let ast_nodes = parser.parse_lines();
     if let Err(e) = ast_nodes {
         eprint!("{}", e.message_with_source(&file));
-        return Ok(());
+        return Ok(1);
     }
     println!("ast: {:?}", ast_nodes);
-    let result = evals(ast_nodes.unwrap(), &mut env);
-    if let Err(e) = result {
-        eprint!("{}", e.message_with_source(&file));
-        return Ok(());
+    let result = eval_program(ast_nodes.unwrap(), &mut env);
+    let value = match result {
+        Ok(value) => value,
+        Err(e) => {
+            if let Some(code) = e.exit_code {
+                return Ok(code);
+            }
+            eprint!("{}", e.message_with_source(&file));
+            return Ok(1);
+        }
+    };
+    println!("result: {:?}", value);
+    let has_main = env.get_function(&"main".to_string()).is_some_and(|main| main.arguments.is_empty());
+    match value {
+        Value::Number(code) if has_main && code.denom() == Some(&1) => Ok(code.to_i32().unwrap_or(1)),
+        _ => Ok(0),
     }
-    println!("result: {:?}", result);
-    Ok(())
+}
+
+/// ファイルを正規化した書式で上書きし、終了コードを返す(トークン化や構文解析のエラーなら1)
+fn format_file(file_path: String) -> Result<i32, Box<dyn std::error::Error>> {
+    let file = std::fs::read_to_string(&file_path)?;
+    if contains_comment(&file) {
+        return Err("fmt cannot format files that contain comments yet (known limitation: the tokenizer drops comments, so formatting would delete them)".into());
+    }
+
+    let tokens = match tokenize(&file) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            eprint!("{}", e.message_with_source(&file));
+            return Ok(1);
+        }
+    };
+    let mut env = Env::new();
+    let builtins = register_builtins(&mut env);
+    let mut parser = SagParser::new(tokens.to_vec(), builtins);
+    let ast_nodes = match parser.parse_lines() {
+        Ok(ast_nodes) => ast_nodes,
+        Err(e) => {
+            eprint!("{}", e.message_with_source(&file));
+            return Ok(1);
+        }
+    };
+    let formatted = format_nodes(&ast_nodes)?;
+    if formatted != file {
+        std::fs::write(&file_path, formatted)?;
+    }
+    Ok(0)
 }
 
 fn main() {
     let args = Cli::parse();
+    // 分数を近似表示に切り替える桁数は環境変数で変更できる
+    if let Ok(digits) = std::env::var("SAG_FRACTION_DISPLAY_DIGITS") {
+        match digits.parse::<usize>() {
+            Ok(digits) => set_fraction_display_digits(digits),
+            Err(_) => eprintln!("Warning: invalid SAG_FRACTION_DISPLAY_DIGITS: {}", digits),
+        }
+    }
     match args.command {
         Commands::Install {package_or_path} => {
             install_package(package_or_path);
         }
-        Commands::Run {file_path} => {
-            if let Err(e) = run_file(file_path) {
-                eprintln!("Error: {}", e);
+        Commands::Run {file_path, script_args} => {
+            match run_file(file_path, script_args) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
         Commands::Repl => {
-            if let Err(e) = run_repl() {
-                eprintln!("Error: {}", e);
+            match run_repl() {
+                Ok(code) => std::process::exit(code),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        Commands::Fmt {file_path} => {
+            match format_file(file_path) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_expression_type() {
+        let builtins = register_builtins(&mut Env::new());
+        let infer = |source: &str| infer_expression_type(source, &builtins).unwrap();
+        assert_eq!(infer("1 + 2"), "number");
+        assert_eq!(infer("\"a\""), "string");
+        assert_eq!(infer("1 < 2"), "bool");
+        assert_eq!(infer("[1, 2]"), "List<number>");
+        assert_eq!(infer("Some(\"a\")"), "Option<string>");
+    }
+}
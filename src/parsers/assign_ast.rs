@@ -6,15 +6,15 @@ use crate::parsers::parse_error::ParseError;
 
 impl Parser {
 
-    fn get_result_value_type(&mut self) -> Result<ValueType, ParseError> {
+    pub fn get_result_value_type(&mut self) -> Result<ValueType, ParseError> {
         match self.consume_token(){
             Some(token) => match token.kind {
-                TokenKind::Identifier(value_type) => Ok(self.string_to_value_type(value_type)),
+                TokenKind::Identifier(value_type) => self.string_to_value_type(value_type),
                 TokenKind::Option => {
                     self.extract_token(TokenKind::Lt);
                     let value_type = match self.consume_token() {
                         Some(token) => match token.kind {
-                            TokenKind::Identifier(value_type) => self.string_to_value_type(value_type),
+                            TokenKind::Identifier(value_type) => self.string_to_value_type(value_type)?,
                             TokenKind::Option => {
                                 self.extract_token(TokenKind::Lt);
                                 let result = self.get_result_value_type()?;
@@ -59,7 +59,7 @@ impl Parser {
                 _ => Err(ParseError::new("unexpected token", &token)),
             },
             _ => {
-                Err(ParseError::new("unexpected token", &Token{kind: TokenKind::Eof, line: 0, column: 0}))
+                Err(ParseError::new("unexpected token", &Token{kind: TokenKind::Eof, line: 0, column: 0, end_column: 0}))
             }
         }
     }
@@ -76,10 +76,14 @@ impl Parser {
         };
         match self.consume_token() {
             Some(Token{kind: TokenKind::Equal, ..}) => {
-                let value = self.parse_expression(0)?;
+                // 型を推論できない値は代入する値の先頭を指すエラーにする
+                let value_token = self.get_current_token().unwrap_or(mutable_or_immutable.clone());
+                let value = self.parse_value_expression()?;
                 let value_type = match self.infer_type(&value) {
+                    // 型注釈のない辞書は値の型を制約しない
+                    Ok(ValueType::Dict(_)) => ValueType::Dict(Box::new(ValueType::Any)),
                     Ok(value_type) => value_type,
-                    Err(e) => panic!("{}", e),
+                    Err(e) => return Err(ParseError::new(e.as_str(), &value_token)),
                 };
                 let variable_type = if mutable_or_immutable.kind == TokenKind::Mutable {
                     EnvVariableType::Mutable
@@ -87,6 +91,7 @@ impl Parser {
                     EnvVariableType::Immutable
                 };
 
+                self.check_use_before_declaration(&scope, &name)?;
                 self.register_variables(scope.clone(), &name, &value_type, &variable_type);
                 Ok(ASTNode::Assign {
                     name,
@@ -107,14 +112,14 @@ impl Parser {
                             "bool" => ValueType::Bool,
                             "void" => ValueType::Void,
                             _ => {
-                                self.string_to_value_type(value_type)
+                                self.string_to_value_type(value_type)?
                             }
                         },
                         TokenKind::Option => {
                             self.extract_token(TokenKind::Lt);
                             let value_type = match self.consume_token() {
                                 Some(token) => match token.kind {
-                                    TokenKind::Identifier(value_type) => self.string_to_value_type(value_type),
+                                    TokenKind::Identifier(value_type) => self.string_to_value_type(value_type)?,
                                     _ => return Err(ParseError::new("unexpected token", &token)),
                                 },
                                 _ => return Err(ParseError::new("unexpected token", &token)),
@@ -137,7 +142,7 @@ impl Parser {
                 let token = self.consume_token();
                 match token {
                     Some(Token{kind: TokenKind::Equal, ..}) => {
-                        let value = self.parse_expression(0)?;
+                        let value = self.parse_value_expression()?;
                         let variable_type = if mutable_or_immutable.kind == TokenKind::Mutable {
                             EnvVariableType::Mutable
                         } else {
@@ -171,11 +176,13 @@ impl Parser {
                                 }
                             },
                             _ => {
-                                if value_type != self.infer_type(&value).unwrap() {
+                                let inferred_type = self.infer_type(&value).map_err(|e| ParseError::new(e.as_str(), token.as_ref().unwrap()))?;
+                                if value_type != inferred_type {
                                     return Err(ParseError::new("type mismatch", &token.unwrap()));
                                 }
                             }
                         }
+                        self.check_use_before_declaration(&scope, &name)?;
                         self.register_variables(scope, &name, &value_type, &variable_type);
                         Ok(ASTNode::Assign {
                             name,
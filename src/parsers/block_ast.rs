@@ -15,7 +15,8 @@ impl Parser {
                 return Err(ParseError {
                     message: "Expected '{' at the start of a block".to_string(),
                     line,
-                    column
+                    column,
+                    end_column: column
                 });
             }
         }
@@ -38,7 +39,8 @@ impl Parser {
                         return Err(ParseError {
                             message: "Unexpected end of file, expected '}'".to_string(),
                             line,
-                            column
+                            column,
+                            end_column: column
                         });
                     }
                     self.pos = 0;
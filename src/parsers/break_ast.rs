@@ -1,11 +1,24 @@
 use crate::ast::ASTNode;
 use crate::parsers::Parser;
+use crate::token::{Token, TokenKind};
 use crate::parsers::parse_error::ParseError;
 
 impl Parser {
     pub fn parse_break(&mut self) -> Result<ASTNode, ParseError> {
         self.pos += 1;
         let (line, column) = self.get_line_column();
-        Ok(ASTNode::Break{line, column})
+        let label = self.parse_loop_label();
+        Ok(ASTNode::Break{label, line, column})
+    }
+
+    /// break/continueの後ろに書かれたループのラベルを読む
+    pub fn parse_loop_label(&mut self) -> Option<String> {
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::Identifier(label), ..}) => {
+                self.consume_token();
+                Some(label)
+            }
+            _ => None,
+        }
     }
 }
@@ -6,6 +6,7 @@ impl Parser {
     pub fn parse_continue(&mut self) -> Result<ASTNode, ParseError> {
         self.pos += 1;
         let (line, column) = self.get_line_column();
-        Ok(ASTNode::Continue{line, column})
+        let label = self.parse_loop_label();
+        Ok(ASTNode::Continue{label, line, column})
     }
 }
@@ -3,12 +3,12 @@ use crate::parsers::Parser;
 use crate::token::TokenKind;
 use crate::value::Value;
 use crate::parsers::parse_error::ParseError;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 impl Parser {
     pub fn parse_dict(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        let mut dict = HashMap::new();
+        let mut dict = IndexMap::new();
         let mut key = None;
         while let Some(token) = self.get_current_token() {
             if token.kind == TokenKind::Colon {
@@ -69,7 +69,7 @@ mod tests {
     #[test]
     fn test_parse_empty_dict() {
         let input = r#"val d = {::}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -79,7 +79,7 @@ mod tests {
     #[test]
     fn test_parse_dict_with_number_value() {
         let input = r#"val d = {: "key" => 42 :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -106,7 +106,7 @@ mod tests {
     #[test]
     fn test_parse_dict_with_string_value() {
         let input = r#"val d = {: "name" => "Alice" :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -133,7 +133,7 @@ mod tests {
     #[test]
     fn test_parse_dict_with_multiple_entries() {
         let input = r#"val d = {: "a" => 5, "b" => "hello", "c" => 10 :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -165,7 +165,7 @@ mod tests {
 val v = {: "a" => 5, "b" => 2 :}
 v["a"]
 "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -175,7 +175,7 @@ v["a"]
     #[test]
     fn test_parse_dict_with_decimal_numbers() {
         let input = r#"val d = {: "pi" => 3.14, "e" => 2.71 :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -203,7 +203,7 @@ v["a"]
     #[test]
     fn test_parse_dict_with_single_entry() {
         let input = r#"val d = {: "single" => 1 :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -230,7 +230,7 @@ v["a"]
     #[test]
     fn test_parse_dict_with_mixed_values() {
         let input = r#"val d = {: "number" => 42, "text" => "hello", "decimal" => 3.14 :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -259,7 +259,7 @@ v["a"]
     #[test]
     fn test_parse_dict_with_spaces() {
         let input = r#"val d = {: "key1" => 1 , "key2" => "value" :}"#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
@@ -283,4 +283,80 @@ v["a"]
             panic!("Expected at least one AST node");
         }
     }
+
+    #[test]
+    fn test_infer_dict_literal_type() {
+        let infer = |input: &str| {
+            let tokens = tokenize(&input.to_string()).unwrap();
+            let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
+            let ast = parser.parse().unwrap();
+            parser.infer_type(&ast).unwrap()
+        };
+        assert_eq!(format!("{:?}", infer(r#"{: "a" => 1, "b" => 2 :}"#)), "Dict(Number)");
+        assert_eq!(format!("{:?}", infer(r#"{: "a" => "x" :}"#)), "Dict(String)");
+        // 値の型がそろっていなければAnyになる
+        assert_eq!(format!("{:?}", infer(r#"{: "a" => 1, "b" => "x" :}"#)), "Dict(Any)");
+        assert_eq!(format!("{:?}", infer("{::}")), "Dict(Any)");
+    }
+
+    #[test]
+    fn test_typed_dict_binding() {
+        let input = "val d: Dict<string, number> = {: \"a\" => 1, \"b\" => 2 :}\nd";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut env = Env::new();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        match &ast[0] {
+            ASTNode::Assign { value_type, .. } => assert_eq!(format!("{:?}", value_type), "Dict(Number)"),
+            other => panic!("Expected Assign node: {:?}", other),
+        }
+        let result = crate::evals::evals(ast, &mut env).unwrap();
+        assert_eq!(result[1], Value::Dict(indexmap::IndexMap::from([
+            ("a".to_string(), Value::Number(Fraction::from(1))),
+            ("b".to_string(), Value::Number(Fraction::from(2))),
+        ])));
+    }
+
+    #[test]
+    fn test_typed_dict_binding_mismatch() {
+        let input = r#"val d: Dict<string, number> = {: "a" => "x" :}"#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "type mismatch");
+    }
+
+    #[test]
+    fn test_typed_dict_with_option_and_result_values() {
+        let input = "val d: Dict<string, Option<number>> = {::}\nval r: Dict<string, Result<number, string>> = {::}";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut env = Env::new();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        match (&ast[0], &ast[1]) {
+            (ASTNode::Assign { value_type: option_type, .. }, ASTNode::Assign { value_type: result_type, .. }) => {
+                assert_eq!(format!("{:?}", option_type), "Dict(OptionType(Number))");
+                assert_eq!(format!("{:?}", result_type), "Dict(ResultType { success: Number, failure: String })");
+            }
+            other => panic!("Expected Assign nodes: {:?}", other),
+        }
+        assert!(crate::evals::evals(ast, &mut env).is_ok());
+    }
+
+    #[test]
+    fn test_typed_dict_non_string_key_error() {
+        let input = r#"val d: Dict<number, string> = {::}"#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "Dict keys must be string");
+        // numberの位置を指す
+        assert_eq!((error.line, error.column), (1, 13));
+
+        let input = r#"val d: Dict<string number> = {::}"#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "expected , after Dict key type");
+    }
 }
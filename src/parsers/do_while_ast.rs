@@ -0,0 +1,74 @@
+use crate::ast::ASTNode;
+use crate::token::{Token, TokenKind};
+use crate::parsers::Parser;
+use crate::parsers::parse_error::ParseError;
+
+impl Parser {
+    pub fn parse_do_while(&mut self) -> Result<ASTNode, ParseError> {
+        let (line, column) = match self.get_current_token() {
+            Some(token) => (token.line, token.column),
+            None => (self.line, self.pos),
+        };
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::Do, ..}) => self.consume_token(),
+            _ => {
+                let current_token = self.get_current_token().unwrap();
+                return Err(ParseError::new("unexpected token missing do", &current_token))
+            }
+        };
+        let body = self.parse_expression(0)?;
+        // ブロックの閉じ括弧の後で行が区切られるので、whileは次の行から探す
+        if let Some(Token{kind: TokenKind::Eof, ..}) = self.get_current_token() {
+            self.pos = 0;
+            self.line += 1;
+        }
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::While, ..}) => self.consume_token(),
+            Some(current_token) => return Err(ParseError::new("unexpected token missing while", &current_token)),
+            None => return Err(ParseError { message: "unexpected end of file, expected while".to_string(), line, column, end_column: column }),
+        };
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::LParen, ..}) => self.consume_token(),
+            Some(current_token) => return Err(ParseError::new("unexpected token missing (", &current_token)),
+            None => return Err(ParseError { message: "unexpected end of file, expected (".to_string(), line, column, end_column: column }),
+        };
+        let condition = self.parse_expression(0)?;
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::RParen, ..}) => self.consume_token(),
+            Some(current_token) => return Err(ParseError::new("unexpected token missing )", &current_token)),
+            None => return Err(ParseError { message: "unexpected end of file, expected )".to_string(), line, column, end_column: column }),
+        };
+        Ok(ASTNode::DoWhile {
+            body: Box::new(body),
+            condition: Box::new(condition),
+            label: None,
+            line,
+            column,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+    use crate::environment::Env;
+    use crate::builtin::register_builtins;
+
+    #[test]
+    fn test_parse_do_while() {
+        let input = "val mut i = 0\ndo { i = i + 1 } while (i < 3)".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let ast = parser.parse_lines().unwrap();
+        assert_eq!(ast.len(), 2);
+        match &ast[1] {
+            ASTNode::DoWhile { body, condition, .. } => {
+                assert!(matches!(body.as_ref(), ASTNode::Block { .. }));
+                assert!(matches!(condition.as_ref(), ASTNode::Lt { .. }));
+            },
+            _ => panic!("unexpected ast"),
+        }
+    }
+}
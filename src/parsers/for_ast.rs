@@ -38,6 +38,7 @@ impl Parser {
             variable,
             iterable: Box::new(iterable),
             body: Box::new(body),
+            label: None,
             line,
             column,
         })
@@ -54,7 +55,7 @@ mod tests {
     #[test]
     fn test_parse_for() {
         let input = "for i in range(10) { i }".to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_for();
@@ -79,4 +80,26 @@ mod tests {
             _ => panic!("unexpected ast"),
         }
     }
+
+    #[test]
+    fn test_for_as_value_is_error() {
+        let input = "val x = for i in [] { }".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "for loop cannot be used as a value");
+        assert_eq!(error.column, 9);
+    }
+
+    #[test]
+    fn test_for_as_reassigned_value_is_error() {
+        let input = "val mut x = 1\nx = for i in [1] { i }".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "for loop cannot be used as a value");
+        assert_eq!((error.line, error.column), (2, 5));
+    }
 }
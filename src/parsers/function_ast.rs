@@ -21,7 +21,7 @@ impl Parser {
         self.extract_token(TokenKind::LParen);
 
         let arguments = self.parse_function_arguments()?;
-        let return_type = self.parse_return_type();
+        let return_type = self.parse_return_type()?;
         self.register_functions(
             function_scope,
             &name,
@@ -92,6 +92,18 @@ impl Parser {
                 self.line += 1;
                 continue;
             }
+            if token.kind == TokenKind::Spread {
+                arguments.push(self.parse_spread()?);
+                continue;
+            }
+            if let TokenKind::Identifier(name) = token.kind
+                && let Some(Token{kind: TokenKind::Colon, ..}) = self.tokens[self.line].get(self.pos + 1)
+            {
+                self.pos += 2;
+                let value = self.parse_expression(0)?;
+                arguments.push(ASTNode::NamedArgument { name, value: Box::new(value), line: token.line, column: token.column });
+                continue;
+            }
             let value = self.parse_expression(0)?;
             arguments.push(value);
         }
@@ -111,7 +123,7 @@ impl Parser {
                 let arg_type = if current_token.is_none() {
                     self.extract_token(TokenKind::Colon);
                     match self.consume_token() {
-                        Some(Token{kind: TokenKind::Identifier(type_name), ..}) => self.string_to_value_type(type_name),
+                        Some(Token{kind: TokenKind::Identifier(type_name), ..}) => self.string_to_value_type(type_name)?,
                         _ => Err(ParseError::new("Expected type for argument", &self.get_current_token().unwrap()))?,
                     }
                 } else {
@@ -130,7 +142,7 @@ impl Parser {
                     } else {
                         self.extract_token(TokenKind::Colon);
                         match self.consume_token() {
-                            Some(Token{kind: TokenKind::Identifier(type_name), ..}) => self.string_to_value_type(type_name),
+                            Some(Token{kind: TokenKind::Identifier(type_name), ..}) => self.string_to_value_type(type_name)?,
                             _ => Err(ParseError::new("Expected type for argument", &self.get_current_token().unwrap()))?,
                         }
                     }
@@ -163,11 +175,7 @@ impl Parser {
         Ok(arguments)
     }
 
-    pub fn parse_function_call_front(&mut self, name: String, arguments: ASTNode) -> Result<ASTNode, ParseError> {
-        let (line, column) = match self.get_current_token() {
-            Some(token) => (token.line, token.column),
-            None => (self.line, self.pos),
-        };
+    pub fn parse_function_call_front(&mut self, name: String, arguments: ASTNode, line: usize, column: usize) -> Result<ASTNode, ParseError> {
         Ok(ASTNode::FunctionCall {
             name,
             arguments: Box::new(arguments),
@@ -188,13 +196,21 @@ impl Parser {
             None => (self.line, self.pos),
         };
 
-        let arguments = ASTNode::FunctionCallArgs{args: match left {
+        let mut args = match left {
             ASTNode::FunctionCallArgs{args: arguments, ..} => arguments,
             _ => vec![left],
-        }, line, column};
+        };
 
         self.consume_token();
 
+        // x -> f(y) は f(x, y) として扱う
+        if let Some(Token{kind: TokenKind::LParen, ..}) = self.get_current_token()
+            && let ASTNode::FunctionCallArgs{args: rest, ..} = self.parse_function_call_arguments_paren()?
+        {
+            args.extend(rest);
+        }
+        let arguments = ASTNode::FunctionCallArgs{args, line, column};
+
 
         Ok(ASTNode::FunctionCall {
             name,
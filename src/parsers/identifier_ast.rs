@@ -1,7 +1,7 @@
 use crate::ast::ASTNode;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
-use crate::environment::{ValueType, EnvVariableType};
+use crate::environment::{ValueType, EnvVariableType, describe_type};
 use crate::parsers::parse_error::ParseError;
 use std::collections::HashMap;
 
@@ -28,7 +28,19 @@ impl Parser {
         self.pos += 1;
         let scope = self.get_current_scope().to_string();
         let variable_info = self.find_variables(scope.clone(), name.clone());
-        
+        // 関数呼び出し・再代入・型注釈付きの宣言以外は変数の参照になる
+        if variable_info.is_none()
+            && !self.in_method_scope
+            && !self.in_pattern
+            && !matches!(
+                self.get_current_token().map(|token| token.kind),
+                Some(TokenKind::LParen) | Some(TokenKind::Equal) | Some(TokenKind::Colon)
+            )
+        {
+            let name_token = self.tokens[self.line][self.pos - 1].clone();
+            self.record_undeclared_use(&scope, &name, &name_token);
+        }
+
         //let struct_exists = self.get_struct(scope.clone(), name.clone()).is_some();
         //if name == "self" && self.is_in_method_scope() {
         //    // メソッド内での'self'は変数チェックから除外
@@ -148,7 +160,13 @@ impl Parser {
             },
             Some(Token{kind: TokenKind::LParen, ..}) => self.create_function_call(name.clone()),
             Some(Token{kind: TokenKind::Equal, ..}) => self.create_assignment(name.clone(), variable_info),
-            Some(Token{kind: TokenKind::Colon, ..}) => self.create_variable_declaration(name.clone()),
+            Some(Token{kind: TokenKind::Colon, ..}) => {
+                // outer: for ... のようにループの前に置かれた識別子はラベルとして扱う
+                match self.tokens[self.line].get(self.pos + 1).map(|token| &token.kind) {
+                    Some(TokenKind::For) | Some(TokenKind::While) | Some(TokenKind::Do) => self.create_labeled_loop(name.clone()),
+                    _ => self.create_variable_declaration(name.clone()),
+                }
+            },
             Some(Token{kind: TokenKind::Dot, ..}) => self.create_struct_field_access(name.clone()),
             _ => {
                 // 代入
@@ -161,6 +179,13 @@ impl Parser {
     fn create_struct_instance(&mut self, name: String) -> Result<ASTNode, ParseError> {
         // 構造体のインスタンス化
         self.consume_token();
+        let fields = self.parse_struct_instance_fields()?;
+        let (line, column) = self.get_line_column();
+        Ok(ASTNode::StructInstance { name, fields, line, column })
+    }
+
+    /// `{` の直後から `}` までの `field: value` の並びを読む
+    pub fn parse_struct_instance_fields(&mut self) -> Result<HashMap<String, ASTNode>, ParseError> {
         let mut fields = HashMap::new();
         while let Some(token) = self.get_current_token() {
             if token.kind == TokenKind::RBrace {
@@ -178,16 +203,17 @@ impl Parser {
                 fields.insert(field_name, value);
                 continue;
             }
+            return Err(ParseError::new(format!("unexpected token in struct fields: {:?}", token.kind).as_str(), &token));
         }
-        let (line, column) = self.get_line_column();
-        Ok(ASTNode::StructInstance { name, fields, line, column })
+        Ok(fields)
     }
 
     fn create_function_call(&mut self, name: String) -> Result<ASTNode, ParseError> {
-        // 関数呼び出し
+        // 関数呼び出し(位置は関数名のトークンを指す)
+        let name_token = self.tokens[self.line][self.pos - 1].clone();
         self.consume_token();
         let arguments = self.parse_function_call_arguments_paren()?;
-        let function_call = self.parse_function_call_front(name, arguments)?;
+        let function_call = self.parse_function_call_front(name, arguments, name_token.line, name_token.column)?;
         Ok(function_call)
     }
 
@@ -209,7 +235,9 @@ impl Parser {
                 &current_token,
             ));
         }
-        let value = self.parse_expression(0)?;
+        // 型の不一致は代入する値の先頭を指す
+        let value_token = self.get_current_token().unwrap_or(prev_token.clone());
+        let value = self.parse_value_expression()?;
         let infer_type = self.infer_type(&value);
         if infer_type.is_err() {
             let current_token = self.get_current_token().unwrap();
@@ -293,11 +321,11 @@ impl Parser {
                 }
             }
             _ => {
-                if value_type != infer_type.unwrap() {
-                    let current_token = self.get_current_token().unwrap();
+                let infer_type = infer_type.unwrap();
+                if value_type != infer_type {
                     return Err(ParseError::new(
-                        format!("type mismatch").as_str(),
-                        &current_token,
+                        format!("type mismatch: cannot change the type of {:?} from {} to {}", name, describe_type(&value_type), describe_type(&infer_type)).as_str(),
+                        &value_token,
                     ));
                 }
             }
@@ -313,11 +341,51 @@ impl Parser {
             column,
         })
     }
+    fn create_labeled_loop(&mut self, label: String) -> Result<ASTNode, ParseError> {
+        self.consume_token(); // Consume the colon
+        let mut ast_loop = match self.get_current_token() {
+            Some(Token{kind: TokenKind::While, ..}) => self.parse_while()?,
+            Some(Token{kind: TokenKind::Do, ..}) => self.parse_do_while()?,
+            _ => self.parse_for()?,
+        };
+        match &mut ast_loop {
+            ASTNode::For { label: loop_label, .. } | ASTNode::While { label: loop_label, .. } | ASTNode::DoWhile { label: loop_label, .. } => *loop_label = Some(label),
+            _ => {}
+        }
+        Ok(ast_loop)
+    }
+
+    /// a.b.cのようにフィールドの値が構造体の場合は、フィールドアクセスを入れ子にして読み進める
+    /// メソッド呼び出し(.name(...))の手前で止め、呼び出しは式の解析に任せる
+    fn parse_nested_field_access(&mut self, mut access: ASTNode) -> ASTNode {
+        loop {
+            let is_field = matches!(self.get_current_token(), Some(Token{kind: TokenKind::Dot, ..}))
+                && matches!(self.tokens[self.line].get(self.pos + 1), Some(Token{kind: TokenKind::Identifier(_), ..}))
+                && !matches!(self.tokens[self.line].get(self.pos + 2), Some(Token{kind: TokenKind::LParen, ..}));
+            if !is_field {
+                return access;
+            }
+            self.consume_token();
+            let field_token = self.get_current_token().unwrap();
+            let field_name = match field_token.kind {
+                TokenKind::Identifier(field_name) => field_name,
+                _ => unreachable!(),
+            };
+            self.consume_token();
+            access = ASTNode::StructFieldAccess {
+                instance: Box::new(access),
+                field_name,
+                line: field_token.line,
+                column: field_token.column,
+            };
+        }
+    }
+
     fn create_variable_declaration(&mut self, name: String) -> Result<ASTNode, ParseError> {
         self.consume_token();
         let value_type =
             if let Some(Token{kind: TokenKind::Identifier(type_name), ..}) = self.get_current_token() {
-                Some(self.string_to_value_type(type_name))
+                Some(self.string_to_value_type(type_name)?)
             } else {
                 let current_token = self.get_current_token().unwrap();
                 return Err(ParseError::new(
@@ -375,11 +443,23 @@ impl Parser {
                 column,
             })
         } else if let Some(Token{kind: TokenKind::Dot, ..}) = self.get_current_token() {
-            match struct_instance_access.clone() {
-                ASTNode::StructFieldAccess { field_name, instance: _, .. } => {
-                    self.parse_identifier(field_name)
-                },
-                _ => panic!("unexpected token"),
+            let nested_access = self.parse_nested_field_access(struct_instance_access);
+            // a.b.x = 1 のような入れ子のフィールドへの代入
+            match (&nested_access, self.get_current_token()) {
+                (ASTNode::StructFieldAccess { field_name, .. }, Some(Token{kind: TokenKind::Equal, ..})) => {
+                    let field_name = field_name.clone();
+                    self.consume_token();
+                    let value = self.parse_expression(0)?;
+                    let (line, column) = self.get_line_column();
+                    Ok(ASTNode::StructFieldAssign {
+                        instance: Box::new(nested_access),
+                        field_name,
+                        value: Box::new(value),
+                        line,
+                        column,
+                    })
+                }
+                _ => Ok(nested_access),
             }
         } else {
             Ok(struct_instance_access)
@@ -29,7 +29,8 @@ impl Parser {
                         let current_token = self.get_current_token().unwrap_or(Token {
                             kind: TokenKind::Eof,
                             line: self.line,
-                            column: self.pos
+                            column: self.pos,
+                            end_column: self.pos,
                         });
                         return Err(ParseError::new("unexpected token missing )", &current_token))
                     }
@@ -39,7 +40,8 @@ impl Parser {
                 let current_token = self.get_current_token().unwrap_or(Token {
                     kind: TokenKind::Eof,
                     line: self.line,
-                    column: self.pos
+                    column: self.pos,
+                    end_column: self.pos,
                 });
                 return Err(ParseError::new("unexpected token missing (", &current_token))
             }
@@ -33,7 +33,7 @@ impl Parser {
                     Some(token) => (token.line, token.column),
                     None => (self.line, self.pos),
                 };
-                return Err(ParseError::new("Expected module name", &Token{kind: TokenKind::Eof, line, column}))
+                return Err(ParseError::new("Expected module name", &Token{kind: TokenKind::Eof, line, column, end_column: column}))
             },
         };
         let (line, column) = match self.get_current_token() {
@@ -65,7 +65,7 @@ mod tests {
     fn test_parse_import() {
         let input = "import foo1, foo2, foo3 from Foo";
         let builtin = register_builtins(&mut Env::new());
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse();
         match ast {
@@ -80,7 +80,7 @@ mod tests {
     #[test]
     fn test_parse_public() {
         let input = "pub val foo = \"hello\"";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse();
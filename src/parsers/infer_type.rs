@@ -2,6 +2,7 @@ use crate::ast::ASTNode;
 use crate::parsers::Parser;
 use crate::value::Value;
 use crate::environment::ValueType;
+use crate::token::TokenKind;
 use std::collections::HashMap;
 
 impl Parser {
@@ -29,37 +30,29 @@ impl Parser {
                     let value = values.first().unwrap();
                     Ok(ValueType::List(Box::new(value.value_type().clone())))
                 },
-                Value::Dict(dict) => {
-                    if dict.is_empty() {
-                        return Ok(ValueType::Dict(Box::new(ValueType::Any)));
-                    }
-                    let mut value_type = ValueType::Any;
-                    for (_, value) in dict.iter() {
-                        value_type = value.value_type();
-                        break;
-                    }
-                    Ok(ValueType::Dict(Box::new(value_type)))
-                },
+                Value::Dict(_) => Ok(v.value_type()),
                 _ => Ok(ValueType::Any),
             },
             ASTNode::Lambda { .. } => Ok(ValueType::Lambda),
+            ASTNode::Eq { .. } | ASTNode::Gte { .. } | ASTNode::Gt { .. } | ASTNode::Lte { .. } | ASTNode::Lt { .. } => Ok(ValueType::Bool),
             ASTNode::PrefixOp { op: _, expr, .. } => {
                 let value_type = self.infer_type(&expr)?;
                 Ok(value_type)
             }
             ASTNode::StructFieldAccess { instance, field_name, ..} => {
                 let instance_type = self.infer_type(&instance)?;
-                if let ValueType::Struct { fields, .. } = instance_type {
-                    if let Some(field_type) = fields.get(field_name) {
-                        match field_type.clone() {
-                            ValueType::StructField { value_type, is_public: _ } => Ok(*value_type),
-                            _ => Ok(field_type.clone())
-                        }
-                    } else {
-                        Err(format!("field not found: {:?}", field_name))
+                // 構造体のフィールドが構造体の場合、a.b.cのbの型はインスタンスの型として伝わる
+                let fields = match instance_type {
+                    ValueType::Struct { fields, .. } | ValueType::StructInstance { fields, .. } => fields,
+                    _ => return Err("field access on non-struct".to_string()),
+                };
+                if let Some(field_type) = fields.get(field_name) {
+                    match field_type.clone() {
+                        ValueType::StructField { value_type, is_public: _ } => Ok(*value_type),
+                        _ => Ok(field_type.clone())
                     }
                 } else {
-                    Err("field access on non-struct".to_string())
+                    Err(format!("field not found: {:?}", field_name))
                 }
             }
             ASTNode::StructInstance { name, fields, .. } => {
@@ -72,9 +65,31 @@ impl Parser {
                     fields: field_types,
                 })
             }
+            ASTNode::StructUpdate { base, .. } => self.infer_type(base),
+            ASTNode::List { elements, .. } => {
+                let mut element_types = elements.iter().map(|element| self.infer_type(element)).collect::<Result<Vec<_>, _>>()?.into_iter();
+                let first = match element_types.next() {
+                    Some(first) => first,
+                    None => return Ok(ValueType::List(Box::new(ValueType::Any))),
+                };
+                if element_types.all(|element_type| element_type == first) {
+                    Ok(ValueType::List(Box::new(first)))
+                } else {
+                    Ok(ValueType::List(Box::new(ValueType::Any)))
+                }
+            }
+            // 展開される要素の型はリストの要素の型になる
+            ASTNode::Spread { expr, .. } => match self.infer_type(expr)? {
+                ValueType::List(element_type) => Ok(*element_type),
+                _ => Ok(ValueType::Any),
+            },
             ASTNode::FunctionCall { name, arguments: _, .. } => {
                 let function = self.get_function(self.get_current_scope(), name.clone());
                 if function.is_none() {
+                    // 変数に格納されたラムダの戻り値の型は実行時まで分からない
+                    if let Some((ValueType::Lambda, _)) = self.find_variables(self.get_current_scope(), name.clone()) {
+                        return Ok(ValueType::Any);
+                    }
                     return Err(format!("undefined function: {:?}", name));
                 }
                 let value_type = function.unwrap();
@@ -96,8 +111,10 @@ impl Parser {
 
                 match (&left_type, &right_type) {
                     (ValueType::Number, ValueType::Number) => Ok(ValueType::Number),
-                    (ValueType::Number, ValueType::String) => Ok(ValueType::String),
-                    (ValueType::String, ValueType::Number) => Ok(ValueType::String),
+                    // 文字列の連結は文字列同士だけ。数値は自動で変換しない(評価時と同じ規則)
+                    (ValueType::String, ValueType::String) if *op == TokenKind::Plus => Ok(ValueType::String),
+                    (ValueType::String, ValueType::Number) if *op == TokenKind::Plus => Err("cannot add number to string, call to_string first".to_string()),
+                    (ValueType::Number, ValueType::String) if *op == TokenKind::Plus => Err("cannot add string to number, call to_string first".to_string()),
                     (ValueType::Bool, ValueType::Bool) => Ok(ValueType::Bool),
                     _ => Err(
                         format!("type mismatch: {:?} {:?} {:?}", left_type, op, right_type).into(),
@@ -137,6 +154,7 @@ impl Parser {
             ASTNode::OptionNone { .. } => {
                 Ok(ValueType::OptionType(Box::new(ValueType::Any)))
             },
+            ASTNode::TypePattern { value_type, .. } => Ok(value_type.clone()),
             ASTNode::OptionSome { value, .. } => {
                 let value_type = self.infer_type(&value)?;
                 Ok(ValueType::OptionType(Box::new(value_type)))
@@ -1,7 +1,7 @@
 use crate::ast::ASTNode;
 use crate::token::{Token, TokenKind};
 use crate::parsers::Parser;
-use crate::environment::EnvVariableType;
+use crate::environment::{EnvVariableType, ValueType};
 use crate::parsers::parse_error::ParseError;
 
 impl Parser {
@@ -27,13 +27,18 @@ impl Parser {
                     };
                     if let TokenKind::Identifier(argument) = token.kind {
                         self.consume_token();
-                        self.extract_token(TokenKind::Colon);
-                        let value_type =
+                        // 型注釈は省略可能で、省略した場合はAnyとして扱う
+                        let has_type_annotation = matches!(self.get_current_token(), Some(Token{kind: TokenKind::Colon, ..}));
+                        let value_type = if has_type_annotation {
+                            self.extract_token(TokenKind::Colon);
                             if let Some(Token{kind: TokenKind::Identifier(type_name), ..}) = self.get_current_token() {
-                                Some(self.string_to_value_type(type_name))
+                                Some(self.string_to_value_type(type_name)?)
                             } else {
                                 None
-                            };
+                            }
+                        } else {
+                            None
+                        };
 
                         let (line, column) = self.get_line_column();
                         arguments.push(ASTNode::Variable {
@@ -45,10 +50,12 @@ impl Parser {
                         self.register_variables(
                             "lambda".to_string(),
                             &argument,
-                            &value_type.unwrap(),
+                            &value_type.unwrap_or(ValueType::Any),
                             &EnvVariableType::Immutable,
                         );
-                        self.consume_token();
+                        if has_type_annotation {
+                            self.consume_token();
+                        }
                         continue;
                     }
                 }
@@ -58,7 +65,7 @@ impl Parser {
                 self.extract_token(TokenKind::Colon);
                 let value_type =
                     if let Some(Token{kind: TokenKind::Identifier(type_name), ..}) = self.get_current_token() {
-                        Some(self.string_to_value_type(type_name))
+                        Some(self.string_to_value_type(type_name)?)
                     } else {
                         None
                     };
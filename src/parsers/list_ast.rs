@@ -17,20 +17,31 @@ impl Parser {
                 self.consume_token();
                 continue;
             }
-            let value = match token.kind {
-                TokenKind::Number(value) => Value::Number(value),
-                TokenKind::String(value) => Value::String(value),
-                _ => panic!("unexpected token: {:?}", token),
-            };
-            list.push(ASTNode::Literal{value, line: token.line, column: token.column});
-            self.consume_token();
+            if token.kind == TokenKind::Eof {
+                self.pos = 0;
+                self.line += 1;
+                continue;
+            }
+            if token.kind == TokenKind::Spread {
+                list.push(self.parse_spread()?);
+                continue;
+            }
+            list.push(self.parse_expression(0)?);
         }
         let (line, column) = self.get_line_column();
-        Ok(ASTNode::Literal{
-            value:Value::List(list.iter().map(|x| match x {
-                ASTNode::Literal{value, ..} => value.clone(),
-                _ => panic!("unexpected node"),
-            }).collect()),
+        // 要素がすべてリテラルならリストもリテラルとして扱う
+        if list.iter().all(|element| matches!(element, ASTNode::Literal{..})) {
+            return Ok(ASTNode::Literal{
+                value: Value::List(list.into_iter().map(|x| match x {
+                    ASTNode::Literal{value, ..} => value,
+                    _ => unreachable!(),
+                }).collect()),
+                line,
+                column,
+            });
+        }
+        Ok(ASTNode::List{
+            elements: list,
             line,
             column,
         })
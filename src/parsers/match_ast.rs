@@ -1,6 +1,6 @@
 use crate::ast::ASTNode;
 use crate::parsers::Parser;
-use crate::token::TokenKind;
+use crate::token::{Token, TokenKind};
 use crate::parsers::parse_error::ParseError;
 use crate::environment::{EnvVariableType, ValueType};
 
@@ -23,10 +23,20 @@ impl Parser {
                 self.line += 1;
                 continue;
             }
-            let pattern = self.parse_expression(0)?;
+            // Suc(v)のvのようなパターン内の変数は束縛なので、宣言前の参照として記録しない
+            self.in_pattern = true;
+            let pattern = match self.tokens[self.line].get(self.pos + 1).map(|token| &token.kind) {
+                Some(TokenKind::Colon) => self.parse_type_pattern(),
+                _ => self.parse_expression(0),
+            };
+            self.in_pattern = false;
+            let pattern = pattern?;
             self.enter_scope(format!("match-{:?}", count).to_string());
             count += 1;
             match pattern {
+                ASTNode::TypePattern { ref name, ref value_type, .. } if name != "_" => {
+                    self.register_variables(self.get_current_scope().clone(), name, value_type, &EnvVariableType::Immutable);
+                }
                 ASTNode::OptionSome { ref value, .. } => {
                     match *value.clone() {
                         ASTNode::Variable { name, .. } => {
@@ -116,6 +126,25 @@ impl Parser {
             column
         })
     }
+
+    /// n: number や _: string のような型パターンを読む
+    fn parse_type_pattern(&mut self) -> Result<ASTNode, ParseError> {
+        let current_token = self.get_current_token().unwrap();
+        let name = match current_token.kind {
+            TokenKind::Identifier(ref name) => name.clone(),
+            _ => return Err(ParseError::new("Unsupported pattern", &current_token)),
+        };
+        self.consume_token();
+        self.extract_token(TokenKind::Colon);
+        let type_name = match self.get_current_token() {
+            Some(Token{kind: TokenKind::Identifier(type_name), ..}) => type_name,
+            Some(token) => return Err(ParseError::new("expected type name", &token)),
+            None => return Err(ParseError::new("expected type name", &current_token)),
+        };
+        self.consume_token();
+        let value_type = self.string_to_value_type(type_name)?;
+        Ok(ASTNode::TypePattern { name, value_type, line: current_token.line, column: current_token.column })
+    }
 }
 
 #[cfg(test)]
@@ -130,17 +159,17 @@ mod tests {
     #[test]
     fn test_parse_match() {
         let mut parser = Parser::new(vec![
-            Token{kind: TokenKind::Match, line: 1, column: 1},
-            Token{kind: TokenKind::LParen, line: 1, column: 7},
-            Token{kind: TokenKind::Number(Fraction::from(0)), line: 1, column: 8},
-            Token{kind: TokenKind::RParen, line: 1, column: 9},
-            Token{kind: TokenKind::LBrace, line: 1, column: 11},
-            Token{kind: TokenKind::Number(Fraction::from(1)), line: 1, column: 13},
-            Token{kind: TokenKind::RRocket, line: 1, column: 15},
-            Token{kind: TokenKind::LBrace, line: 1, column: 17},
-            Token{kind: TokenKind::Number(Fraction::from(2)), line: 1, column: 18},
-            Token{kind: TokenKind::RBrace, line: 1, column: 20},
-            Token{kind: TokenKind::RBrace, line: 1, column: 20},
+            Token{kind: TokenKind::Match, line: 1, column: 1, end_column: 1},
+            Token{kind: TokenKind::LParen, line: 1, column: 7, end_column: 7},
+            Token{kind: TokenKind::Number(Fraction::from(0)), line: 1, column: 8, end_column: 8},
+            Token{kind: TokenKind::RParen, line: 1, column: 9, end_column: 9},
+            Token{kind: TokenKind::LBrace, line: 1, column: 11, end_column: 11},
+            Token{kind: TokenKind::Number(Fraction::from(1)), line: 1, column: 13, end_column: 13},
+            Token{kind: TokenKind::RRocket, line: 1, column: 15, end_column: 15},
+            Token{kind: TokenKind::LBrace, line: 1, column: 17, end_column: 17},
+            Token{kind: TokenKind::Number(Fraction::from(2)), line: 1, column: 18, end_column: 18},
+            Token{kind: TokenKind::RBrace, line: 1, column: 20, end_column: 20},
+            Token{kind: TokenKind::RBrace, line: 1, column: 20, end_column: 20},
         ], HashMap::new());
         let result = parser.parse();
         assert_eq!(result.is_ok(), true);
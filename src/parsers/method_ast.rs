@@ -42,7 +42,7 @@ impl Parser {
         } else {
             true // No arguments, so static method
         };
-        let return_type = self.parse_return_type();
+        let return_type = self.parse_return_type()?;
         let body = self.parse_block()?;
         // メソッドスコープから出る
         self.leave_method_scope();
@@ -81,6 +81,9 @@ impl Parser {
                                 ValueType::Void => true,
                                 ValueType::List(_) => true,
                                 ValueType::Dict(_) => true,
+                                ValueType::OptionType(_) => true,
+                                ValueType::ResultType{..} => true,
+                                ValueType::Lambda => true,
                                 _ => false,
                             }
                         },
@@ -96,6 +99,9 @@ impl Parser {
                                 ValueType::Void => true,
                                 ValueType::List(_) => true,
                                 ValueType::Dict(_) => true,
+                                ValueType::OptionType(_) => true,
+                                ValueType::ResultType{..} => true,
+                                ValueType::Lambda => true,
                                 _ => false,
                             }
                         },
@@ -111,6 +117,8 @@ impl Parser {
                     Ok(ValueType::Void) => true,
                     Ok(ValueType::List(_)) => true,
                     Ok(ValueType::Dict(_)) => true,
+                    Ok(ValueType::OptionType(_)) => true,
+                    Ok(ValueType::ResultType{..}) => true,
                     _ => false,
                 }
             },
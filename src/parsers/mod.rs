@@ -10,6 +10,8 @@ pub mod pipe_ast;
 pub mod lambda_ast;
 pub mod infer_type;
 pub mod for_ast;
+pub mod while_ast;
+pub mod do_while_ast;
 pub mod if_ast;
 pub mod identifier_ast;
 pub mod method_ast;
@@ -22,6 +24,7 @@ pub mod parse_error;
 pub mod option_ast;
 pub mod result_ast;
 pub mod match_ast;
+pub mod spread_ast;
 
 
 use crate::environment::{EnvVariableType, ValueType, MethodInfo};
@@ -41,6 +44,8 @@ pub struct Parser {
     functions: HashMap<(String, String), ValueType>, // key: (scope, name, arguments), value: (body, return_type)
     current_struct: Option<String>,
     in_method_scope: bool, // メソッド内かどうかを追跡
+    undeclared_uses: HashMap<(String, String), Token>, // key: (scope, name), value: 宣言前に参照した最初の位置
+    in_pattern: bool, // matchのパターンを解析中か(パターン内の変数は参照ではなく束縛)
 }
 
 impl Parser {
@@ -56,6 +61,8 @@ impl Parser {
             functions: initial_functions,
             current_struct: None,
             in_method_scope: false, // 初期状態ではメソッドスコープではない
+            undeclared_uses: HashMap::new(),
+            in_pattern: false,
         }
     }
 
@@ -188,13 +195,13 @@ impl Parser {
             },
             ValueType::List(_value_type) => {
                 match method_name.as_str() {
-                    "push" => Some(MethodInfo {
+                    "push" | "set_at" | "insert" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
                         return_type: ValueType::Void,
                         is_mut: true,
                     }),
-                    "pop" => Some(MethodInfo {
+                    "pop" | "remove_at" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
                         return_type: ValueType::OptionType(Box::new(_value_type.as_ref().clone())),
@@ -212,7 +219,7 @@ impl Parser {
                         return_type: ValueType::Bool,
                         is_mut: false,
                     }),
-                    "first" => Some(MethodInfo {
+                    "first" | "head" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
                         return_type: ValueType::OptionType(Box::new(_value_type.as_ref().clone())),
@@ -224,6 +231,12 @@ impl Parser {
                         return_type: ValueType::OptionType(Box::new(_value_type.as_ref().clone())),
                         is_mut: false,
                     }),
+                    "tail" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
                     "clear" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -242,6 +255,87 @@ impl Parser {
                         return_type: ValueType::Void,
                         is_mut: true,
                     }),
+                    "map" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(Box::new(ValueType::Any)),
+                        is_mut: false,
+                    }),
+                    "filter" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "count" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    "partition" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(Box::new(ValueType::List(_value_type.clone()))),
+                        is_mut: false,
+                    }),
+                    "flatten" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: match _value_type.as_ref() {
+                            ValueType::List(inner) => ValueType::List(inner.clone()),
+                            _ => ValueType::List(Box::new(ValueType::Any)),
+                        },
+                        is_mut: false,
+                    }),
+                    "take" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "slice" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "max_by" | "min_by" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::OptionType(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "sum" | "product" | "average" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    "chunk" | "window" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(Box::new(ValueType::List(_value_type.clone()))),
+                        is_mut: false,
+                    }),
+                    "drop" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "unique" | "sort" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(_value_type.clone()),
+                        is_mut: false,
+                    }),
+                    "flat_map" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::List(Box::new(ValueType::Any)),
+                        is_mut: false,
+                    }),
                     _ => None
                 }
             }
@@ -253,6 +347,12 @@ impl Parser {
                         return_type: ValueType::OptionType(Box::new(_value_type.as_ref().clone())),
                         is_mut: false,
                     }),
+                    "get_or" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: _value_type.as_ref().clone(),
+                        is_mut: false,
+                    }),
                     "insert" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -283,6 +383,12 @@ impl Parser {
                         return_type: ValueType::List(Box::new(_value_type.as_ref().clone())),
                         is_mut: false,
                     }),
+                    "sort_keys" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Dict(_value_type.clone()),
+                        is_mut: false,
+                    }),
                     "len" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -307,6 +413,12 @@ impl Parser {
                         return_type: ValueType::Void,
                         is_mut: true,
                     }),
+                    "merge" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Dict(_value_type.clone()),
+                        is_mut: false,
+                    }),
                     "entry" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -327,6 +439,13 @@ impl Parser {
                     Some(ValueType::Struct { name: _, fields: _, methods }) => {
                         match methods.get(&method_name) {
                             Some(method) => Some(method.clone()),
+                            // to_stringは未定義でもデフォルトの表示を返す
+                            None if method_name == "to_string" => Some(MethodInfo {
+                                arguments: vec![],
+                                body: None,
+                                return_type: ValueType::String,
+                                is_mut: false,
+                            }),
                             None => None
                         }
                     },
@@ -341,6 +460,18 @@ impl Parser {
                         return_type: ValueType::String,
                         is_mut: false,
                     }),
+                    "to_fixed" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::String,
+                        is_mut: false,
+                    }),
+                    "to_hex" | "to_binary" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::String,
+                        is_mut: false,
+                    }),
                     "sqrt" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -353,6 +484,74 @@ impl Parser {
                         return_type: ValueType::Number,
                         is_mut: false,
                     }),
+                    "clamp" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    "to_bool" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Bool,
+                        is_mut: false,
+                    }),
+                    "numerator" | "denominator" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    _ => None
+                }
+            }
+            ValueType::Bool => {
+                match method_name.as_str() {
+                    "to_number" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    _ => None
+                }
+            }
+            ValueType::Lambda => {
+                match method_name.as_str() {
+                    "arity" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Number,
+                        is_mut: false,
+                    }),
+                    "call" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::Any,
+                        is_mut: false,
+                    }),
+                    _ => None
+                }
+            }
+            ValueType::OptionType(value_type) => {
+                match method_name.as_str() {
+                    "ok_or" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::ResultType{success: value_type, failure: Box::new(ValueType::Any)},
+                        is_mut: false,
+                    }),
+                    _ => None
+                }
+            }
+            ValueType::ResultType{success, ..} => {
+                match method_name.as_str() {
+                    "to_option" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::OptionType(success),
+                        is_mut: false,
+                    }),
                     _ => None
                 }
             }
@@ -382,12 +581,36 @@ impl Parser {
                         return_type: ValueType::String,
                         is_mut: false,
                     }),
-                    "trim" => Some(MethodInfo {
+                    "trim" | "reverse" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
                         return_type: ValueType::String,
                         is_mut: false,
                     }),
+                    "char_at" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::OptionType(Box::new(ValueType::String)),
+                        is_mut: false,
+                    }),
+                    "code_at" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::OptionType(Box::new(ValueType::Number)),
+                        is_mut: false,
+                    }),
+                    "substring" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::String,
+                        is_mut: false,
+                    }),
+                    "to_number" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::OptionType(Box::new(ValueType::Number)),
+                        is_mut: false,
+                    }),
                     "contains" => Some(MethodInfo {
                         arguments: vec![],
                         body: None,
@@ -418,6 +641,12 @@ impl Parser {
                         return_type: ValueType::String,
                         is_mut: false,
                     }),
+                    "pad_left" | "pad_right" => Some(MethodInfo {
+                        arguments: vec![],
+                        body: None,
+                        return_type: ValueType::String,
+                        is_mut: false,
+                    }),
                     _ => None
                 }
             }
@@ -438,6 +667,30 @@ impl Parser {
         );
     }
 
+    /// まだ宣言されていない変数の参照を記録する
+    /// 関数・構造体は後から定義しても呼び出せるので記録しない
+    fn record_undeclared_use(&mut self, scope: &str, name: &str, token: &Token) {
+        if self.get_function(scope.to_string(), name.to_string()).is_some()
+            || self.get_struct(scope.to_string(), name.to_string()).is_some()
+        {
+            return;
+        }
+        self.undeclared_uses
+            .entry((scope.to_string(), name.to_string()))
+            .or_insert_with(|| token.clone());
+    }
+
+    /// 同じスコープで宣言より前に参照されていた変数ならエラーにする
+    fn check_use_before_declaration(&mut self, scope: &str, name: &str) -> Result<(), ParseError> {
+        match self.undeclared_uses.remove(&(scope.to_string(), name.to_string())) {
+            Some(token) => Err(ParseError::new(
+                format!("variable {:?} is used before its declaration", name).as_str(),
+                &token,
+            )),
+            None => Ok(()),
+        }
+    }
+
     fn find_variables(
         &self,
         scope: String,
@@ -453,6 +706,7 @@ impl Parser {
                     &ValueType::String => return Some((ValueType::String, value.1.clone())),
                     &ValueType::Bool => return Some((ValueType::Bool, value.1.clone())),
                     &ValueType::Function => return Some((ValueType::Function, value.1.clone())),
+                    &ValueType::Lambda => return Some((ValueType::Lambda, value.1.clone())),
                     &ValueType::StructInstance{ref name, ref fields} => {
                         return Some((ValueType::StructInstance{name: name.to_string(), fields: fields.clone()}, value.1.clone()))
                     },
@@ -477,7 +731,7 @@ impl Parser {
         for token in tokens.clone() {
             if token.kind == TokenKind::Eof {
                 if !current_line.is_empty() {
-                    current_line.push(Token{kind: TokenKind::Eof, line: token.line, column: token.column + 1});
+                    current_line.push(Token{kind: TokenKind::Eof, line: token.line, column: token.column + 1, end_column: token.column + 1});
                     lines.push(current_line);
                     current_line = Vec::new();
                 }
@@ -486,7 +740,7 @@ impl Parser {
             }
         }
         if !current_line.is_empty() {
-            current_line.push(Token{kind: TokenKind::Eof, line: tokens.len(), column: tokens.last().unwrap().column + 1});
+            current_line.push(Token{kind: TokenKind::Eof, line: tokens.len(), column: tokens.last().unwrap().column + 1, end_column: tokens.last().unwrap().column + 1});
             lines.push(current_line);
         }
         lines
@@ -508,9 +762,9 @@ impl Parser {
 
     pub fn extract_token(&mut self, token: TokenKind) -> Token {
         match self.get_current_token() {
-            Some(Token{kind: current_token_kind, line, column}) if current_token_kind == token => {
+            Some(Token{kind: current_token_kind, line, column, end_column}) if current_token_kind == token => {
                 self.pos += 1;
-                Token{kind: current_token_kind, line, column}
+                Token{kind: current_token_kind, line, column, end_column}
             }
             _ => panic!("unexpected token: {:?}", token),
         }
@@ -527,6 +781,20 @@ impl Parser {
     }
 
 
+    /// 代入の右辺など値が必要な位置の式を読む
+    /// ループは値を返さない文なので、値の位置に書かれていたらエラーにする
+    pub fn parse_value_expression(&mut self) -> Result<ASTNode, ParseError> {
+        if let Some(token) = self.get_current_token() {
+            match token.kind {
+                TokenKind::For => return Err(ParseError::new("for loop cannot be used as a value", &token)),
+                TokenKind::While => return Err(ParseError::new("while loop cannot be used as a value", &token)),
+                TokenKind::Do => return Err(ParseError::new("do-while loop cannot be used as a value", &token)),
+                _ => {}
+            }
+        }
+        self.parse_expression(0)
+    }
+
     fn parse_primary(&mut self) -> Result<ASTNode, ParseError> {
         let token = match self.get_current_token() {
             Some(token) => token,
@@ -551,6 +819,8 @@ impl Parser {
             TokenKind::BackSlash => self.parse_lambda(),
             TokenKind::Mutable | TokenKind::Immutable => self.parse_assign(),
             TokenKind::For => self.parse_for(),
+            TokenKind::While => self.parse_while(),
+            TokenKind::Do => self.parse_do_while(),
             TokenKind::Import => self.parse_import(),
             TokenKind::Some => self.parse_option_some(),
             TokenKind::None => self.parse_option_none(),
@@ -593,7 +863,8 @@ impl Parser {
                         Err(ParseError {
                             message: "Expected closing parenthesis".to_string(),
                             line,
-                            column
+                            column,
+                            end_column: column
                         })
                     }
                 }
@@ -626,6 +897,21 @@ impl Parser {
                 Some(token) => token,
                 _ => break,
             };
+            if token.kind == TokenKind::With {
+                self.consume_token();
+                match self.get_current_token() {
+                    Some(Token{kind: TokenKind::LBrace, ..}) => self.consume_token(),
+                    _ => return Err(ParseError::new("expected '{' after with", &token)),
+                };
+                let fields = self.parse_struct_instance_fields()?;
+                lhs = ASTNode::StructUpdate {
+                    base: Box::new(lhs),
+                    fields,
+                    line: token.line,
+                    column: token.column,
+                };
+                continue;
+            }
             if token.kind == TokenKind::Dot {
                 self.pos += 2;
                 if let TokenKind::LParen = self.get_current_token().unwrap().kind {
@@ -651,6 +937,8 @@ impl Parser {
                                             ValueType::Void => true,
                                             ValueType::List(_) => true,
                                             ValueType::Dict(_) => true,
+                                            ValueType::OptionType(_) => true,
+                                            ValueType::ResultType{..} => true,
                                             _ => false,
                                         }
                                     }
@@ -690,6 +978,8 @@ impl Parser {
                                             ValueType::Void => true,
                                             ValueType::List(_) => true,
                                             ValueType::Dict(_) => true,
+                                            ValueType::OptionType(_) => true,
+                                            ValueType::ResultType{..} => true,
                                             _ => false,
                                         }
                                     },
@@ -706,6 +996,9 @@ impl Parser {
                                             ValueType::Void => true,
                                             ValueType::List(_) => true,
                                             ValueType::Dict(_) => true,
+                                            ValueType::OptionType(_) => true,
+                                            ValueType::ResultType{..} => true,
+                                            ValueType::Lambda => true,
                                             _ => false,
                                         }
                                     }
@@ -719,6 +1012,8 @@ impl Parser {
                                 Ok(ValueType::Void) => true,
                                 Ok(ValueType::List(_)) => true,
                                 Ok(ValueType::Dict(_)) => true,
+                                Ok(ValueType::OptionType(_)) => true,
+                                Ok(ValueType::ResultType{..}) => true,
                                 _ => false,
                             },
                         };
@@ -727,20 +1022,7 @@ impl Parser {
                             caller: Box::new(lhs.clone()),
                             method_name,
                             builtin,
-                            arguments: match args {
-                                ASTNode::FunctionCallArgs{args, line, column} => {
-                                    Box::new(ASTNode::FunctionCallArgs{
-                                        args: vec![lhs].into_iter().chain(args.into_iter()).collect(),
-                                        line,
-                                        column,
-                                    })
-                                }
-                                _ => Box::new(ASTNode::FunctionCallArgs{
-                                    args: vec![lhs],
-                                    line: token.line,
-                                    column: token.column,
-                                }),
-                            },
+                            arguments: Box::new(args),
                             line: token.line,
                             column: token.column,
                         };
@@ -848,7 +1130,63 @@ impl Parser {
         self.parse_expression(0)
     }
 
+    /// トップレベルの関数の戻り値の型を先に登録し、定義より前の行から呼び出せるようにする
+    fn hoist_functions(&mut self) {
+        let mut depth = 0usize;
+        for line in 0..self.tokens.len() {
+            let name_pos = match self.tokens[line].first().map(|token| &token.kind) {
+                Some(TokenKind::Pub) => 2,
+                _ => 1,
+            };
+            if depth == 0 && self.tokens[line].get(name_pos - 1).map(|token| &token.kind) == Some(&TokenKind::Function) {
+                self.hoist_function_signature(line, name_pos);
+            }
+            for token in &self.tokens[line] {
+                match token.kind {
+                    TokenKind::LBrace => depth += 1,
+                    TokenKind::RBrace => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn hoist_function_signature(&mut self, line: usize, name_pos: usize) {
+        let tokens = &self.tokens[line];
+        let name = match tokens.get(name_pos) {
+            Some(Token{kind: TokenKind::Identifier(name), ..}) => name.clone(),
+            _ => return,
+        };
+        // 引数の閉じ括弧から本体の開き括弧までが戻り値の型になる
+        let rparen = tokens.iter().skip(name_pos).position(|token| token.kind == TokenKind::RParen).map(|index| index + name_pos);
+        let lbrace = rparen.and_then(|rparen| tokens.iter().skip(rparen).position(|token| token.kind == TokenKind::LBrace).map(|index| index + rparen));
+        let (rparen, lbrace) = match (rparen, lbrace) {
+            (Some(rparen), Some(lbrace)) => (rparen, lbrace),
+            _ => return,
+        };
+        // 後で定義する構造体を使う型はここでは解決できないので、定義の位置で登録する
+        let is_known_type = tokens[rparen + 1..lbrace].iter().all(|token| match &token.kind {
+            TokenKind::Identifier(type_name) => matches!(type_name.as_str(), "number" | "string" | "bool" | "void" | "List")
+                || self.get_struct("global".to_string(), type_name.clone()).is_some(),
+            _ => true,
+        });
+        if !is_known_type {
+            return;
+        }
+        let (saved_line, saved_pos) = (self.line, self.pos);
+        self.line = line;
+        self.pos = rparen + 1;
+        let return_type = self.parse_return_type();
+        self.line = saved_line;
+        self.pos = saved_pos;
+        // 型の書き方の誤りは定義の位置で解析するときにエラーとして返す
+        if let Ok(return_type) = return_type {
+            self.register_functions("global".to_string(), &name, &vec![], &return_type);
+        }
+    }
+
     pub fn parse_lines(&mut self) -> Result<Vec<ASTNode>, ParseError> {
+        self.hoist_functions();
         let mut ast_nodes = vec![];
         for _ in 0..self.tokens.len() {
             ast_nodes.push(self.parse()?);
@@ -879,7 +1217,7 @@ mod tests {
         let input = "-1 + 2 * 3 % 3";
 
         let builtins = register_builtins(&mut Env::new());
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
             Ok(ASTNode::BinaryOp {
@@ -889,17 +1227,8 @@ mod tests {
                 ..
             }) => {
                 match left.as_ref() {
-                    ASTNode::PrefixOp {
-                        op: TokenKind::Minus,
-                        expr,
-                        ..
-                    } => {
-                        match expr.as_ref() {
-                            ASTNode::Literal{value, ..} => {
-                                assert_eq!(*value, Value::Number(Fraction::from(1)));
-                            }
-                            _ => panic!("Invalid ASTNode"),
-                        }
+                    ASTNode::Literal{value, ..} => {
+                        assert_eq!(*value, Value::Number(Fraction::from(-1)));
                     }
                     _ => panic!("Invalid ASTNode"),
                 }
@@ -952,7 +1281,7 @@ mod tests {
     #[test]
     fn test_type_specified() {
         let input = "val mut x: number = 1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -982,7 +1311,7 @@ mod tests {
     #[test]
     fn test_type_estimate() {
         let input = "val mut x = 1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1012,7 +1341,7 @@ mod tests {
     #[test]
     fn test_register_function() {
         let input = "fun foo(x: number, y: number): number { return x + y }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1071,7 +1400,7 @@ mod tests {
     fn test_block() {
         // Define variables first to avoid undefined variable errors
         let input = "{ val x = 5\n val y = 10\n x + y\n return 1 - 1 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let block_result = parser.parse_block();
@@ -1140,7 +1469,7 @@ mod tests {
     #[test]
     fn test_reassign_to_mutable_variable() {
         let input = "val mut x = 1\nx = 2";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
 
@@ -1196,7 +1525,7 @@ mod tests {
     fn test_function_call() {
         // First define the function f1 to avoid undefined function errors
         let input = "fun f1(a: number, b: number, c: number): number { return a + b + c }\n|1, 2, 3| -> f1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let ast = parser.parse_lines().unwrap();
@@ -1241,7 +1570,7 @@ mod tests {
     #[test]
     fn test_reassign_to_immutable_variable_should_panic() {
         let input = "val x = 1\n x = 2";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let asts = parser.parse_lines();
@@ -1255,7 +1584,7 @@ mod tests {
     #[test]
     fn test_function_without_arguments_and_void_return() {
         let input = "fun no_args() { return 42 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1296,7 +1625,7 @@ mod tests {
     #[test]
     fn test_function_call_with_no_arguments() {
         let input = "|| -> func()";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1329,7 +1658,7 @@ mod tests {
         }
         return x + 1
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
 
@@ -1427,18 +1756,29 @@ mod tests {
     #[test]
     fn test_prefix_operator_only() {
         let input = "-5";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
-            Ok(ASTNode::PrefixOp { op, expr, .. }) => {
-                assert_eq!(op, TokenKind::Minus);
-                match *expr {
-                    ASTNode::Literal { value, .. } => {
-                        assert_eq!(value, Value::Number(Fraction::from(5)));
-                    }
-                    _ => assert!(false, "Invalid ASTNode"),
-                }
+            Ok(ASTNode::Literal { value, line, column }) => {
+                assert_eq!(value, Value::Number(Fraction::from(-5)));
+                assert_eq!((line, column), (1, 1));
+            }
+            _ => assert!(false, "Invalid ASTNode"),
+        }
+    }
+
+    #[test]
+    fn test_prefix_operator_on_variable() {
+        let input = "val x = 5\n-x";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtins = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtins);
+        let ast = parser.parse_lines().unwrap();
+        match &ast[1] {
+            ASTNode::PrefixOp { op, expr, .. } => {
+                assert_eq!(*op, TokenKind::Minus);
+                assert!(matches!(**expr, ASTNode::Variable { .. }));
             }
             _ => assert!(false, "Invalid ASTNode"),
         }
@@ -1447,7 +1787,7 @@ mod tests {
     #[test]
     fn test_list() {
         let input = "[1, 2, 3]";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1464,7 +1804,7 @@ mod tests {
     #[test]
     fn test_fraction_and_decimal_operations() {
         let input = "5.2 + 3.2";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins.clone());
 
@@ -1494,7 +1834,7 @@ mod tests {
 
         let input = "1/3 * 2/5";
         // 分数の演算テスト
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins.clone());
         match parser.parse() {
             Ok(ASTNode::BinaryOp {
@@ -1553,7 +1893,7 @@ mod tests {
     #[test]
     fn test_function_call_chain() {
         let input = "1 -> f1 -> f2";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1599,7 +1939,7 @@ mod tests {
     #[test]
     fn test_lambda() {
         let input = "val inc = \\|x: number| => x + 1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -1673,7 +2013,7 @@ mod tests {
     fn test_if() {
         // Define x first to avoid undefined variable error
         let input = "val x = 1\nif (x == 1) { 1 } else { 0 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let ast = parser.parse_lines().unwrap();
@@ -1714,7 +2054,7 @@ mod tests {
     fn test_partial_return_if() {
         // Define x first to avoid undefined variable error
         let input = "val x = 1\nif (x == 1) { 1 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         
@@ -1729,7 +2069,7 @@ mod tests {
     fn test_if_statement() {
         // Define x first to avoid undefined variable error
         let input = "val x = 1\nif (x == 1) { return 1 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let ast = parser.parse_lines().unwrap();
@@ -1774,7 +2114,7 @@ mod tests {
     fn test_else() {
         // Define x first to avoid undefined variable error
         let input = "val x = 1\nif (x == 1) { return 1 } else { return 0 }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         let ast = parser.parse_lines().unwrap();
@@ -1841,7 +2181,7 @@ mod tests {
             return 0
           }
         "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         if let Ok(ASTNode::If{condition: result_condition, then: result_then, else_: result_else_, value_type: result_value_type, ..}) = parser.parse() {
@@ -1943,7 +2283,7 @@ mod tests {
     #[test]
     fn test_comparison_operations() {
         let input = "1 == 1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins.clone());
         match parser.parse() {
@@ -1964,7 +2304,7 @@ mod tests {
             _ => assert!(false, "Invalid ASTNode")
         }
         let input = "2 > 1";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins.clone());
 
         match parser.parse() {
@@ -1986,7 +2326,7 @@ mod tests {
         }
 
         let input = "3 >= 3";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins.clone());
 
         match parser.parse() {
@@ -2008,7 +2348,7 @@ mod tests {
         }
 
         let input = "1 < 2";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins.clone());
 
         match parser.parse() {
@@ -2030,7 +2370,7 @@ mod tests {
         }
 
         let input = "4 <= 4";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, builtins.clone());
 
         match parser.parse() {
@@ -2055,7 +2395,7 @@ mod tests {
     #[test]
     fn test_struct() {
         let input = "struct Point { x: number, y: number }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtins = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtins);
         match parser.parse() {
@@ -2092,7 +2432,7 @@ mod tests {
             }
             Point { x: 1, y: 2 }
         "#.to_string();
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
         let results = parser.parse_lines().unwrap();
         assert_eq!(results.len(), 2);
@@ -2130,7 +2470,7 @@ mod tests {
           point.x
           point.x = 3
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut Env::new()));
         let results = parser.parse_lines().unwrap();
         assert_eq!(results.len(), 4);
@@ -2241,7 +2581,7 @@ mod tests {
             }
         }
         "#.to_string();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut env = Env::new();
         let builtins = register_builtins(&mut env);
         let mut parser = Parser::new(tokens, builtins);
@@ -2368,7 +2708,7 @@ mod tests {
     #[test]
     fn test_for() {
         let input = "for i in [1, 2, 3] { print(i) }";
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let mut env = Env::new();
         let builtins = register_builtins(&mut env);
         let mut parser = Parser::new(tokens, builtins);
@@ -2418,4 +2758,36 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_use_before_declaration() {
+        let input = "print(x + 1)\nval x = 1";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut env = Env::new();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "variable \"x\" is used before its declaration");
+        assert_eq!((error.line, error.column), (1, 7));
+    }
+
+    #[test]
+    fn test_forward_references_in_function_bodies() {
+        // 関数の本体から後で宣言するグローバル変数や関数を参照するのは呼び出し時に解決されるので許可する
+        let input = "fun f(): number {\n    return g() + y\n}\nfun g(): number {\n    return 1\n}\nval y = 2\nf()";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut env = Env::new();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        assert!(parser.parse_lines().is_ok());
+    }
+
+    #[test]
+    fn test_binding_sites_are_not_uses_before_declaration() {
+        // matchのパターン、ラムダや関数の引数は束縛なので、後で同名の変数を宣言してもよい
+        let input = "val r = Suc(1)\nmatch r {\n    Suc(v) => { print(v) }\n    Fail(e) => { print(e) }\n}\nval v = 3\nval e = 4\nval f = \\|n: number| => n\nfun g(m: number): number {\n    return m\n}\nval n = 5\nval m = 6\nprint(v)";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut env = Env::new();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        assert!(crate::evals::evals(ast, &mut env).is_ok());
+    }
 }
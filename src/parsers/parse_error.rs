@@ -1,4 +1,5 @@
 use crate::token::Token;
+use crate::tokenizer::normalize_line_breaks;
 
 
 #[derive(Debug, Clone)]
@@ -6,6 +7,8 @@ pub struct ParseError {
     pub message: String,
     pub line: usize,
     pub column: usize,
+    // エラー範囲の終端(この列の直前までがエラー範囲)
+    pub end_column: usize,
 }
 
 impl ParseError {
@@ -14,15 +17,127 @@ impl ParseError {
             message: message.to_string(),
             line: token.line,
             column: token.column,
+            end_column: token.end_column,
         }
     }
 
     pub fn message_with_source(&self, source: &str) -> String {
-        let lines: Vec<&str> = source.lines().collect();
-        let error_line = lines.get(self.line - 1).unwrap_or(&"");
         format!(
-            "Parse Error: {}\n --> line {}, column {}\n | {}\n | {}^",
-            self.message, self.line, self.column, error_line, " ".repeat(self.column)
+            "Parse Error: {}\n --> line {}, column {}\n{}",
+            self.message, self.line, self.column, render_source_line(source, self.line, self.column, self.end_column)
         )
     }
 }
+
+const TAB_WIDTH: usize = 4;
+
+// 全角文字(CJK・ハングル・全角記号・絵文字など)は端末上で2文字分の幅になる
+fn char_display_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// エラー行と、columnからend_columnまでの範囲を指す下線を表示用に整形する
+/// タブは空白に展開し、全角文字は2文字分として下線の位置を合わせる
+pub fn render_source_line(source: &str, line: usize, column: usize, end_column: usize) -> String {
+    // トークナイザと同じく単独の\rも改行として数え、エラーの行番号と表示する行をそろえる
+    let source: String = normalize_line_breaks(source).into_iter().collect();
+    let lines: Vec<&str> = source.lines().collect();
+    let error_line = lines.get(line.saturating_sub(1)).unwrap_or(&"");
+    let mut expanded = String::new();
+    let mut width = 0;
+    let mut caret_start = None;
+    let mut caret_end = None;
+    // columnは1始まりで、1文字(タブも含む)ごとに1つ進む
+    for (i, c) in error_line.chars().enumerate() {
+        if i + 1 == column {
+            caret_start = Some(width);
+        }
+        if i + 1 == end_column {
+            caret_end = Some(width);
+        }
+        if c == '\t' {
+            let spaces = TAB_WIDTH - width % TAB_WIDTH;
+            expanded.push_str(&" ".repeat(spaces));
+            width += spaces;
+        } else {
+            expanded.push(c);
+            width += char_display_width(c);
+        }
+    }
+    let caret_start = caret_start.unwrap_or(width);
+    let caret_end = caret_end.unwrap_or(width);
+    // 範囲を持たないエラーでも1文字分は下線を引く
+    let caret_width = caret_end.saturating_sub(caret_start).max(1);
+    format!(" | {}\n | {}{}", expanded, " ".repeat(caret_start), "^".repeat(caret_width))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::parsers::Parser;
+    use crate::tokenizer::tokenize;
+    use super::render_source_line;
+
+    fn parse_error(input: &str) -> super::ParseError {
+        let mut env = Env::new();
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        parser.parse_lines().unwrap_err()
+    }
+
+    #[test]
+    fn test_error_range_covers_identifier() {
+        let error = parse_error("undefined_name = 1");
+        assert_eq!(error.column, 1);
+        assert_eq!(error.end_column - error.column, "undefined_name".len());
+    }
+
+    #[test]
+    fn test_error_range_covers_keyword() {
+        let error = parse_error("for return in [1] {}");
+        assert_eq!(error.column, 5);
+        assert_eq!(error.end_column - error.column, "return".len());
+        let message = error.message_with_source("for return in [1] {}");
+        assert!(message.ends_with(" | for return in [1] {}\n |     ^^^^^^"));
+    }
+
+    #[test]
+    fn test_caret_after_tab() {
+        let error = parse_error("\tundefined_name = 1");
+        assert_eq!(error.column, 2);
+        let message = error.message_with_source("\tundefined_name = 1");
+        assert!(message.ends_with(" |     undefined_name = 1\n |     ^^^^^^^^^^^^^^"));
+    }
+
+    #[test]
+    fn test_caret_after_wide_characters() {
+        let rendered = render_source_line("val 名前 = x", 1, 10, 11);
+        assert_eq!(rendered, " | val 名前 = x\n |            ^");
+    }
+
+    #[test]
+    fn test_error_line_after_lone_carriage_return() {
+        let source = "val x = 1\rundefined_name = 1";
+        let error = parse_error(source);
+        assert_eq!((error.line, error.column), (2, 1));
+        let message = error.message_with_source(source);
+        assert!(message.ends_with(" | undefined_name = 1\n | ^^^^^^^^^^^^^^"));
+    }
+}
@@ -2,11 +2,25 @@ use crate::ast::ASTNode;
 use crate::parsers::Parser;
 use crate::token::TokenKind;
 use crate::parsers::parse_error::ParseError;
+use crate::value::Value;
+use fraction::Fraction;
 
 impl Parser {
     pub fn parse_prefix_op(&mut self, op: TokenKind) -> Result<ASTNode, ParseError> {
-        self.pos += 1;
+        let op_token = self.consume_token();
         let value = self.parse_expression(std::u8::MAX)?;
+        // -5のように数値リテラルに直接付いたマイナスは負の数のリテラルにする(matchのパターンで使える)
+        if op == TokenKind::Minus
+            && let ASTNode::Literal { value: Value::Number(number), .. } = value
+            && let Some(op_token) = op_token
+        {
+            let number = if number == Fraction::from(0) { number } else { -number };
+            return Ok(ASTNode::Literal {
+                value: Value::Number(number),
+                line: op_token.line,
+                column: op_token.column,
+            });
+        }
         let (line, column) = self.get_line_column();
         Ok(ASTNode::PrefixOp {
             op,
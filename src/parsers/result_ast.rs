@@ -37,7 +37,7 @@ mod tests {
         x = Some("hello")
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         match ast.unwrap_err() {
@@ -53,12 +53,12 @@ mod tests {
         x = Some("hello")
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         match ast.unwrap_err() {
             ParseError{message, ..} => {
-                assert_eq!(message, "type mismatch");
+                assert_eq!(message, "type mismatch: cannot change the type of \"x\" from number to Option<string>");
             }
         }
     }
@@ -69,12 +69,12 @@ mod tests {
         x = None
         "#.to_string();
         let mut env = Env::new();
-        let tokens = tokenize(&input);
+        let tokens = tokenize(&input).unwrap();
         let mut parser = Parser::new(tokens, register_builtins(&mut env));
         let ast = parser.parse_lines();
         match ast.unwrap_err() {
             ParseError{message, ..} => {
-                assert_eq!(message, "type mismatch");
+                assert_eq!(message, "type mismatch: cannot change the type of \"x\" from number to Option<any>");
             }
         }
     }
@@ -12,7 +12,7 @@ impl Parser {
         Ok(ASTNode::Return{expr: Box::new(value), line, column})
     }
 
-    pub fn parse_return_type(&mut self) -> ValueType {
+    pub fn parse_return_type(&mut self) -> Result<ValueType, ParseError> {
         match self.get_current_token() {
             Some(Token{kind: TokenKind::Colon, ..}) => {
                 self.consume_token();
@@ -26,12 +26,12 @@ impl Parser {
                     let some = match self.get_current_token() {
                         Some(Token{kind: TokenKind::Identifier(type_name), ..}) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
                     self.extract_token(TokenKind::Gt);
-                    return ValueType::OptionType(Box::new(some));
+                    return Ok(ValueType::OptionType(Box::new(some)));
                 }
                 if let Some(Token{kind: TokenKind::Result, ..}) = self.get_current_token() {
                     self.consume_token();
@@ -39,7 +39,7 @@ impl Parser {
                     let success = match self.get_current_token() {
                         Some(Token{kind: TokenKind::Identifier(type_name), ..}) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
@@ -47,15 +47,15 @@ impl Parser {
                     let failure = match self.get_current_token() {
                         Some(Token{kind: TokenKind::Identifier(type_name), ..}) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
                     self.consume_token();
-                    return ValueType::ResultType{
+                    return Ok(ValueType::ResultType{
                         success: Box::new(success),
                         failure: Box::new(failure),
-                    };
+                    });
                 }
                 if let Some(Token{kind: TokenKind::List, ..}) = self.get_current_token() {
                     self.consume_token();
@@ -63,16 +63,16 @@ impl Parser {
                     let element_type = match self.get_current_token() {
                         Some(Token{kind: TokenKind::Identifier(type_name), ..}) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
                     self.extract_token(TokenKind::Gt);
-                    return ValueType::List(Box::new(element_type));
+                    return Ok(ValueType::List(Box::new(element_type)));
                 }
             },
             _ => {}
         };
-        ValueType::Void
+        Ok(ValueType::Void)
     }
 }
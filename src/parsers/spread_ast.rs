@@ -0,0 +1,20 @@
+use crate::ast::ASTNode;
+use crate::parsers::Parser;
+use crate::parsers::parse_error::ParseError;
+
+impl Parser {
+    /// `...式` を解析する(リストリテラルと関数呼び出しの引数の中でのみ使える)
+    pub fn parse_spread(&mut self) -> Result<ASTNode, ParseError> {
+        let (line, column) = match self.get_current_token() {
+            Some(token) => (token.line, token.column),
+            None => (self.line, self.pos),
+        };
+        self.consume_token();
+        let expr = self.parse_expression(0)?;
+        Ok(ASTNode::Spread {
+            expr: Box::new(expr),
+            line,
+            column,
+        })
+    }
+}
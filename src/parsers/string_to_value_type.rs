@@ -1,15 +1,16 @@
 use crate::parsers::Parser;
 use crate::environment::ValueType;
-use crate::token::TokenKind;
+use crate::token::{Token, TokenKind};
+use crate::parsers::parse_error::ParseError;
 
 impl Parser {
-    pub fn string_to_value_type(&mut self, type_name: String) -> ValueType {
+    pub fn string_to_value_type(&mut self, type_name: String) -> Result<ValueType, ParseError> {
         let scope = self.get_current_scope();
         if let Some(struct_value) = self.get_struct(scope, type_name.clone()) {
-            return struct_value;
+            return Ok(struct_value);
         }
 
-        match type_name.as_str() {
+        let value_type = match type_name.as_str() {
             "number" => ValueType::Number,
             "string" => ValueType::String,
             "bool" => ValueType::Bool,
@@ -20,7 +21,7 @@ impl Parser {
                     Some(token) => {
                         if let TokenKind::Identifier(type_name) = &token.kind {
                             self.consume_token();
-                            self.string_to_value_type(type_name.clone())
+                            self.string_to_value_type(type_name.clone())?
                         } else {
                             panic!("expected type name");
                         }
@@ -30,7 +31,31 @@ impl Parser {
                 self.extract_token(TokenKind::Gt);
                 ValueType::List(Box::new(element_type))
             }
+            // 辞書のキーは文字列だけなので、Dict<string, 値の型>と書く
+            "Dict" => {
+                // 型注釈はユーザー入力なので、書き方の誤りはpanicせずParseErrorで返す
+                let lt = self.extract_token(TokenKind::Lt);
+                match self.consume_token() {
+                    Some(token) if token.kind == TokenKind::Identifier("string".to_string()) => {}
+                    token => return Err(ParseError::new("Dict keys must be string", &token.unwrap_or(lt))),
+                }
+                match self.consume_token() {
+                    Some(Token{kind: TokenKind::Comma, ..}) => {}
+                    token => return Err(ParseError::new("expected , after Dict key type", &token.unwrap_or(lt))),
+                }
+                // 値の型はOption<...>やResult<...>も書けるよう、型注釈と同じく再帰的に読む
+                let value_type = match self.get_current_token() {
+                    Some(Token{kind: TokenKind::Identifier(_) | TokenKind::Option | TokenKind::Result | TokenKind::List, ..}) => self.get_result_value_type()?,
+                    token => return Err(ParseError::new("expected type name", &token.unwrap_or(lt))),
+                };
+                match self.consume_token() {
+                    Some(Token{kind: TokenKind::Gt, ..}) => {}
+                    token => return Err(ParseError::new("expected > after Dict value type", &token.unwrap_or(lt))),
+                }
+                ValueType::Dict(Box::new(value_type))
+            }
             _ => panic!("undefined type: {:?}", type_name),
-        }
+        };
+        Ok(value_type)
     }
 }
@@ -43,18 +43,18 @@ impl Parser {
             if let Token{kind: TokenKind::Identifier(name), ..} = token {
                 self.consume_token();
                 self.extract_token(TokenKind::Colon);
+                let (line, column) = self.get_line_column();
                 let value_type = match self.get_current_token() {
-                    Some(Token{kind: TokenKind::Identifier(type_name), ..}) => self.string_to_value_type(type_name),
-                    _ => panic!("undefined type"),
+                    Some(Token{kind: TokenKind::Identifier(_) | TokenKind::Option | TokenKind::Result | TokenKind::List, ..}) => self.get_result_value_type()?,
+                    Some(token) => return Err(ParseError::new("undefined type", &token)),
+                    None => return Err(ParseError { message: "unexpected end of file, expected type".to_string(), line, column, end_column: column }),
                 };
-                let (line, column) = self.get_line_column();
                 fields.insert(name, ASTNode::StructField {
                     value_type,
                     is_public: field_is_public,
                     line,
                     column
                 });
-                self.consume_token();
                 field_is_public = false;
                 continue;
             }
@@ -203,10 +203,48 @@ val mut point = Point{x: x, y: y}
 point.move(5, 2)
 point.clear()
 "#;
-        let tokens = tokenize(&input.to_string());
+        let tokens = tokenize(&input.to_string()).unwrap();
         let builtin = register_builtins(&mut Env::new());
         let mut parser = Parser::new(tokens, builtin);
         let ast = parser.parse_lines();
         assert!(ast.is_ok());
     }
+
+    #[test]
+    fn test_parse_struct_field_with_nested_type() {
+        let input = r#"
+struct Inventory {
+  counts: Dict<string, List<number>>,
+  note: Option<string>
+}
+"#;
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let ast = parser.parse_lines().unwrap();
+        match &ast[0] {
+            ASTNode::Struct { fields, .. } => {
+                match fields.get("counts") {
+                    Some(ASTNode::StructField { value_type, .. }) => assert_eq!(format!("{:?}", value_type), "Dict(List(Number))"),
+                    other => panic!("unexpected field: {:?}", other),
+                }
+                match fields.get("note") {
+                    Some(ASTNode::StructField { value_type, .. }) => assert_eq!(format!("{:?}", value_type), "OptionType(String)"),
+                    other => panic!("unexpected field: {:?}", other),
+                }
+            }
+            other => panic!("unexpected ast: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_struct_field_with_invalid_type_is_error() {
+        let input = "struct Point {\n  x: 1\n}";
+        let tokens = tokenize(&input.to_string()).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "undefined type");
+        assert_eq!((error.line, error.column), (2, 6));
+    }
 }
@@ -0,0 +1,64 @@
+use crate::ast::ASTNode;
+use crate::token::{Token, TokenKind};
+use crate::parsers::Parser;
+use crate::parsers::parse_error::ParseError;
+
+impl Parser {
+    pub fn parse_while(&mut self) -> Result<ASTNode, ParseError> {
+        let (line, column) = match self.get_current_token() {
+            Some(token) => (token.line, token.column),
+            None => (self.line, self.pos),
+        };
+        match self.get_current_token() {
+            Some(Token{kind: TokenKind::While, ..}) => self.consume_token(),
+            _ => {
+                let current_token = self.get_current_token().unwrap();
+                return Err(ParseError::new("unexpected token missing while", &current_token))
+            }
+        };
+        let condition = self.parse_expression(0)?;
+        let body = self.parse_expression(0)?;
+        Ok(ASTNode::While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+            label: None,
+            line,
+            column,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+    use crate::environment::Env;
+    use crate::builtin::register_builtins;
+
+    #[test]
+    fn test_parse_while() {
+        let input = "val mut i = 0\nwhile i < 3 { i = i + 1 }".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let ast = parser.parse_lines().unwrap();
+        assert_eq!(ast.len(), 2);
+        match &ast[1] {
+            ASTNode::While { condition, body, .. } => {
+                assert!(matches!(condition.as_ref(), ASTNode::Lt { .. }));
+                assert!(matches!(body.as_ref(), ASTNode::Block { .. }));
+            },
+            _ => panic!("unexpected ast"),
+        }
+    }
+
+    #[test]
+    fn test_parse_while_as_value_is_error() {
+        let input = "val x = while true { 1 }".to_string();
+        let tokens = tokenize(&input).unwrap();
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let error = parser.parse_lines().unwrap_err();
+        assert_eq!(error.message, "while loop cannot be used as a value");
+    }
+}
@@ -1,20 +1,26 @@
 use fraction::Fraction;
+use serde::{Serialize, Serializer};
 
-#[derive(Debug, Clone, PartialEq)]
+/// JSONでは{"kind": "Identifier", "value": "x", "line": 1, "column": 1, "end_column": 2}のようになる
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Token {
+    #[serde(flatten)]
     pub kind: TokenKind,
     pub line: usize,
     pub column: usize,
+    // トークンの直後の列(column..end_columnがトークンの範囲になる)
+    pub end_column: usize,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "value")]
 pub enum TokenKind {
     Immutable,
     Mutable,
     Colon,
     Identifier(String),
     String(String),
-    Number(Fraction),
+    Number(#[serde(serialize_with = "serialize_fraction")] Fraction),
     Bool(bool),
     Void,
     Equal,
@@ -57,10 +63,14 @@ pub enum TokenKind {
     Struct,
     Pub,
     Dot,
+    Spread,
     Impl,
     CommentBlock(String),
     CommentLine(String),
     For,
+    Do,
+    While,
+    With,
     In,
     Import,
     From,
@@ -73,3 +83,8 @@ pub enum TokenKind {
     List,
     Dict,
 }
+
+/// 数値トークンは"3/4"のような分数の文字列にする
+fn serialize_fraction<S: Serializer>(value: &Fraction, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
@@ -1,6 +1,34 @@
 use crate::token::{Token, TokenKind};
+use crate::parsers::parse_error::render_source_line;
 use fraction::Fraction;
 
+#[derive(Debug, Clone)]
+pub struct TokenizeError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    // エラー範囲の終端(この列の直前までがエラー範囲)
+    pub end_column: usize,
+}
+
+impl TokenizeError {
+    pub fn new(message: &str, line: usize, column: usize, end_column: usize) -> Self {
+        Self {
+            message: message.to_string(),
+            line,
+            column,
+            end_column,
+        }
+    }
+
+    pub fn message_with_source(&self, source: &str) -> String {
+        format!(
+            "Tokenize Error: {}\n --> line {}, column {}\n{}",
+            self.message, self.line, self.column, render_source_line(source, self.line, self.column, self.end_column)
+        )
+    }
+}
+
 struct Tokenizer {
     tokens: Vec<Token>,
     chars: Vec<char>,
@@ -16,7 +44,7 @@ impl Tokenizer {
             pos: 0,
             column: 1,
             line: 1,
-            chars: line.chars().collect(),
+            chars: normalize_line_breaks(line),
             tokens: vec![],
             nesting_count: 0,
         }
@@ -35,6 +63,23 @@ impl Tokenizer {
     }
 }
 
+// \r\nと単独の\rを\nにそろえて、行と列の位置や文字列リテラルの中身に\rが残らないようにする
+pub(crate) fn normalize_line_breaks(source: &str) -> Vec<char> {
+    let mut chars = Vec::new();
+    let mut source_chars = source.chars().peekable();
+    while let Some(c) = source_chars.next() {
+        if c == '\r' {
+            if source_chars.peek() == Some(&'\n') {
+                source_chars.next();
+            }
+            chars.push('\n');
+        } else {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
 fn is_space(c: &char) -> bool {
     *c == ' '
 }
@@ -72,7 +117,7 @@ fn get_digit(tokenizer: &mut Tokenizer) -> Fraction {
         }
     }
     tokenizer.pos = pos;
-    tokenizer.column += num.len();
+    tokenizer.column += num.chars().count();
     match num.parse::<f64>() {
         Ok(n) => fraction::Fraction::from(n),
         Err(_) => fraction::Fraction::from(0),
@@ -83,12 +128,17 @@ fn is_string(c: &char) -> bool {
     *c == '"'
 }
 
+fn is_identifier_char(c: &char) -> bool {
+    c.is_alphanumeric() || *c == '_'
+}
+
 fn get_identifier(tokenizer: &mut Tokenizer) -> String {
     let mut identifier = String::new();
     let mut pos = tokenizer.pos;
     loop {
         let c = tokenizer.get_position_char(pos);
-        if c == '\0'
+        if !is_identifier_char(&c)
+            || c == '\0'
             || c == '\n'
             || c == ' '
             || c == ':'
@@ -121,24 +171,44 @@ fn get_identifier(tokenizer: &mut Tokenizer) -> String {
     identifier
 }
 
-fn get_string(tokenizer: &mut Tokenizer) -> String {
+fn get_string(tokenizer: &mut Tokenizer) -> Result<String, TokenizeError> {
     let mut str = String::new();
+    let start = tokenizer.pos;
     let mut pos = tokenizer.pos + 1;
     loop {
         let c = tokenizer.get_position_char(pos);
         if c == '"' {
             pos += 1;
-            tokenizer.pos = pos;
             break;
         }
         if c == '\0' {
-            break;
+            // 閉じる"が見つからないまま入力が終わったら、開始の"の位置をエラーにする
+            let (line, column) = tokenizer.store_position();
+            return Err(TokenizeError::new("unterminated string literal", line, column, column + 1));
+        }
+        // \" \\ \n \r \t はエスケープとして1文字に置き換える
+        if c == '\\' {
+            let escaped = match tokenizer.get_position_char(pos + 1) {
+                '"' => Some('"'),
+                '\\' => Some('\\'),
+                'n' => Some('\n'),
+                'r' => Some('\r'),
+                't' => Some('\t'),
+                _ => None,
+            };
+            if let Some(escaped) = escaped {
+                str.push(escaped);
+                pos += 2;
+                continue;
+            }
         }
         str += &c.to_string();
         pos += 1;
     }
-    tokenizer.column += str.len() + 2;
-    str
+    tokenizer.pos = pos;
+    // 列はエスケープを展開する前のソース上の文字数だけ進める
+    tokenizer.column += pos - start;
+    Ok(str)
 }
 
 fn is_line_break(c: &char) -> bool {
@@ -209,11 +279,11 @@ fn get_line_comment_string(tokenizer: &mut Tokenizer) -> String {
         comment += &c.to_string();
         pos += 1;
     }
-    tokenizer.column += comment.len() + 2;
+    tokenizer.column += comment.chars().count() + 2;
     comment
 }
 
-fn get_comment_string(tokenizer: &mut Tokenizer) -> String {
+fn get_comment_string(tokenizer: &mut Tokenizer) -> Result<String, TokenizeError> {
     let mut comment = String::new();
     let mut pos = tokenizer.pos + 3;
     let mut back_quote_count = 0;
@@ -221,8 +291,9 @@ fn get_comment_string(tokenizer: &mut Tokenizer) -> String {
     loop {
         let c = tokenizer.get_position_char(pos);
         if c == '\0' {
-            tokenizer.pos = pos;
-            break;
+            // 閉じる```が見つからないまま入力が終わったら、コメントの開始位置をエラーにする
+            let (line, column) = tokenizer.store_position();
+            return Err(TokenizeError::new("unterminated block comment", line, column, column + 3));
         }
         if back_quote_count == 3 {
             pos += 2;
@@ -244,8 +315,8 @@ fn get_comment_string(tokenizer: &mut Tokenizer) -> String {
         comment += &c.to_string();
         pos += 1;
     }
-    tokenizer.column += comment.len() + 6;
-    comment
+    tokenizer.column += comment.chars().count() + 6;
+    Ok(comment)
 }
 
 fn is_break(tokenizer: &mut Tokenizer) -> bool {
@@ -329,6 +400,15 @@ fn is_right_arrow(tokenizer: &mut Tokenizer) -> bool {
     true
 }
 
+fn is_spread(tokenizer: &mut Tokenizer) -> bool {
+    for (i, c) in "...".chars().enumerate() {
+        if c != tokenizer.get_position_char(i + tokenizer.pos) {
+            return false;
+        }
+    }
+    true
+}
+
 fn is_right_rocket(tokenizer: &mut Tokenizer) -> bool {
     for (i, c) in "=>".chars().enumerate() {
         if c != tokenizer.get_position_char(i + tokenizer.pos) {
@@ -464,6 +544,33 @@ fn is_for(tokenizer: &mut Tokenizer) -> bool {
     true
 }
 
+fn is_do(tokenizer: &mut Tokenizer) -> bool {
+    for (i, c) in "do ".chars().enumerate() {
+        if c != tokenizer.get_position_char(i + tokenizer.pos) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_while(tokenizer: &mut Tokenizer) -> bool {
+    for (i, c) in "while ".chars().enumerate() {
+        if c != tokenizer.get_position_char(i + tokenizer.pos) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_with(tokenizer: &mut Tokenizer) -> bool {
+    for (i, c) in "with ".chars().enumerate() {
+        if c != tokenizer.get_position_char(i + tokenizer.pos) {
+            return false;
+        }
+    }
+    true
+}
+
 fn is_in(tokenizer: &mut Tokenizer) -> bool {
     for (i, c) in "in ".chars().enumerate() {
         if c != tokenizer.get_position_char(i + tokenizer.pos) {
@@ -544,7 +651,7 @@ fn is_else(tokenizer: &mut Tokenizer) -> bool {
     true
 }
 
-pub fn tokenize(line: &String) -> Vec<Token> {
+pub fn tokenize(line: &String) -> Result<Vec<Token>, TokenizeError> {
     let mut tokenizer = Tokenizer::new(&line);
     loop {
         let c = tokenizer.get_position_char(tokenizer.pos);
@@ -553,10 +660,10 @@ pub fn tokenize(line: &String) -> Vec<Token> {
             match tokenizer.tokens.last() {
                 Some(Token {kind, ..}) => {
                     if kind != &TokenKind::Eof {
-                        tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column});
+                        tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column, end_column: tokenizer.column});
                     }
                 }
-                _ => tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column})
+                _ => tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column, end_column: tokenizer.column})
             }
             tokenizer.line += 1;
             tokenizer.pos += 1;
@@ -579,21 +686,21 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_digit(&c) {
             let (line, column) = tokenizer.store_position();
             let num = get_digit(&mut tokenizer);
-            tokenizer.tokens.push(Token{kind: TokenKind::Number(num), line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Number(num), line, column, end_column: tokenizer.column});
             continue;
         }
 
         if is_string(&c) {
             let (line, column) = tokenizer.store_position();
-            let str = get_string(&mut tokenizer);
-            tokenizer.tokens.push(Token{kind: TokenKind::String(str), line, column});
+            let str = get_string(&mut tokenizer)?;
+            tokenizer.tokens.push(Token{kind: TokenKind::String(str), line, column, end_column: tokenizer.column});
             continue;
         }
 
         if is_break(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 5;
-            tokenizer.tokens.push(Token{kind: TokenKind::Break, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Break, line, column, end_column: tokenizer.column});
             tokenizer.pos += 5;
             continue;
         }
@@ -601,7 +708,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_continue(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 8;
-            tokenizer.tokens.push(Token{kind: TokenKind::Continue, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Continue, line, column, end_column: tokenizer.column});
             tokenizer.pos += 8;
             continue;
         }
@@ -609,7 +716,8 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_mutable(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 8;
-            tokenizer.tokens.push(Token{kind: TokenKind::Mutable, line, column});
+            // "val mut "の末尾の空白はトークンの範囲に含めない
+            tokenizer.tokens.push(Token{kind: TokenKind::Mutable, line, column, end_column: column + "val mut".len()});
             tokenizer.pos += 8;
             continue;
         }
@@ -617,7 +725,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_immutable(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::Immutable, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Immutable, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
@@ -631,7 +739,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         }
 
         if is_comment_block(&mut tokenizer) {
-            let _comment = get_comment_string(&mut tokenizer);
+            let _comment = get_comment_string(&mut tokenizer)?;
             //tokenizer.tokens.push(Token::CommentBlock(comment));
             continue;
         }
@@ -639,7 +747,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_function(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::Function, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Function, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
@@ -647,7 +755,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_import(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 7;
-            tokenizer.tokens.push(Token{kind: TokenKind::Import, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Import, line, column, end_column: column + "import".len()});
             tokenizer.pos += 7;
             continue;
         }
@@ -655,7 +763,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_from(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 5;
-            tokenizer.tokens.push(Token{kind: TokenKind::From, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::From, line, column, end_column: column + "from".len()});
             tokenizer.pos += 5;
             continue;
         }
@@ -663,7 +771,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_exponent(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::Pow, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Pow, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -671,7 +779,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_match(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 6;
-            tokenizer.tokens.push(Token{kind: TokenKind::Match, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Match, line, column, end_column: column + "match".len()});
             tokenizer.pos += 6;
             continue;
         }
@@ -679,7 +787,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_return(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 7;
-            tokenizer.tokens.push(Token{kind: TokenKind::Return, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Return, line, column, end_column: column + "return".len()});
             tokenizer.pos += 7;
             continue;
         }
@@ -687,15 +795,23 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_right_arrow(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::RArrow, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::RArrow, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
 
+        if is_spread(&mut tokenizer) {
+            let (line, column) = tokenizer.store_position();
+            tokenizer.column += 3;
+            tokenizer.tokens.push(Token{kind: TokenKind::Spread, line, column, end_column: tokenizer.column});
+            tokenizer.pos += 3;
+            continue;
+        }
+
         if is_struct(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 6;
-            tokenizer.tokens.push(Token{kind: TokenKind::Struct, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Struct, line, column, end_column: tokenizer.column});
             tokenizer.pos += 6;
             continue;
         }
@@ -703,7 +819,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_impl(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::Impl, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Impl, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -711,7 +827,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_pub(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::Pub, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Pub, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
@@ -719,7 +835,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_option(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 6;
-            tokenizer.tokens.push(Token{kind: TokenKind::Option, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Option, line, column, end_column: tokenizer.column});
             tokenizer.pos += 6;
             continue;
         }
@@ -727,7 +843,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_some(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::Some, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Some, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -735,7 +851,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_none(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::None, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::None, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -743,7 +859,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_void(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::Void, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Void, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -751,14 +867,14 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_and(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::And, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::And, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
         if is_or(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::Or, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Or, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -766,7 +882,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_xor(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::Xor, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Xor, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
@@ -774,7 +890,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_result(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 6;
-            tokenizer.tokens.push(Token{kind: TokenKind::Result, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Result, line, column, end_column: tokenizer.column});
             tokenizer.pos += 6;
             continue;
         }
@@ -782,7 +898,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_success(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::Success, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Success, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
@@ -790,7 +906,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_failure(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::Failure, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Failure, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -798,15 +914,39 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_for(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 3;
-            tokenizer.tokens.push(Token{kind: TokenKind::For, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::For, line, column, end_column: tokenizer.column});
             tokenizer.pos += 3;
             continue;
         }
 
+        if is_do(&mut tokenizer) {
+            let (line, column) = tokenizer.store_position();
+            tokenizer.column += 2;
+            tokenizer.tokens.push(Token{kind: TokenKind::Do, line, column, end_column: tokenizer.column});
+            tokenizer.pos += 2;
+            continue;
+        }
+
+        if is_while(&mut tokenizer) {
+            let (line, column) = tokenizer.store_position();
+            tokenizer.column += 5;
+            tokenizer.tokens.push(Token{kind: TokenKind::While, line, column, end_column: tokenizer.column});
+            tokenizer.pos += 5;
+            continue;
+        }
+
+        if is_with(&mut tokenizer) {
+            let (line, column) = tokenizer.store_position();
+            tokenizer.column += 4;
+            tokenizer.tokens.push(Token{kind: TokenKind::With, line, column, end_column: tokenizer.column});
+            tokenizer.pos += 4;
+            continue;
+        }
+
         if is_in(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::In, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::In, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -814,7 +954,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_right_rocket(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::RRocket, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::RRocket, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -822,7 +962,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_if(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::If, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::If, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -830,7 +970,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_else(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::Else, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Else, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -838,7 +978,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_eq(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::Eq, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Eq, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -846,7 +986,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_lte(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::Lte, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Lte, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -854,7 +994,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_lt(c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
-            tokenizer.tokens.push(Token{kind: TokenKind::Lt, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Lt, line, column, end_column: tokenizer.column});
             tokenizer.pos += 1;
             continue;
         }
@@ -862,7 +1002,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_gte(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 2;
-            tokenizer.tokens.push(Token{kind: TokenKind::Gte, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Gte, line, column, end_column: tokenizer.column});
             tokenizer.pos += 2;
             continue;
         }
@@ -870,7 +1010,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_gt(c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
-            tokenizer.tokens.push(Token{kind: TokenKind::Gt, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Gt, line, column, end_column: tokenizer.column});
             tokenizer.pos += 1;
             continue;
         }
@@ -878,7 +1018,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_true(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
-            tokenizer.tokens.push(Token{kind: TokenKind::True, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::True, line, column, end_column: tokenizer.column});
             tokenizer.pos += 4;
             continue;
         }
@@ -886,7 +1026,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_false(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 5;
-            tokenizer.tokens.push(Token{kind: TokenKind::False, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::False, line, column, end_column: tokenizer.column});
             tokenizer.pos += 5;
             continue;
         }
@@ -894,7 +1034,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_colon(&c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
-            tokenizer.tokens.push(Token{kind: TokenKind::Colon, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Colon, line, column, end_column: tokenizer.column});
             tokenizer.pos += 1;
             continue;
         }
@@ -902,7 +1042,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_comma(&c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
-            tokenizer.tokens.push(Token{kind: TokenKind::Comma, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Comma, line, column, end_column: tokenizer.column});
             tokenizer.pos += 1;
             continue;
         }
@@ -910,7 +1050,7 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_function_call_args(&c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
-            tokenizer.tokens.push(Token{kind: TokenKind::Pipe, line, column});
+            tokenizer.tokens.push(Token{kind: TokenKind::Pipe, line, column, end_column: tokenizer.column});
             tokenizer.pos += 1;
             continue;
         }
@@ -918,34 +1058,36 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         let (line, column) = tokenizer.store_position();
         tokenizer.column += 1;
         match c {
-            '+' => tokenizer.tokens.push(Token{kind: TokenKind::Plus, line, column}),
-            '-' => tokenizer.tokens.push(Token{kind: TokenKind::Minus, line, column}),
-            '*' => tokenizer.tokens.push(Token{kind: TokenKind::Mul, line, column}),
-            '/' => tokenizer.tokens.push(Token{kind: TokenKind::Div, line, column}),
-            '%' => tokenizer.tokens.push(Token{kind: TokenKind::Mod, line, column}),
-            '(' => tokenizer.tokens.push(Token{kind: TokenKind::LParen, line, column}),
-            ')' => tokenizer.tokens.push(Token{kind: TokenKind::RParen, line, column}),
-            '[' => tokenizer.tokens.push(Token{kind: TokenKind::LBrancket, line, column}),
-            ']' => tokenizer.tokens.push(Token{kind: TokenKind::RBrancket, line, column}),
-            '.' => tokenizer.tokens.push(Token{kind: TokenKind::Dot, line, column}),
-            '\\' => tokenizer.tokens.push(Token{kind: TokenKind::BackSlash, line, column}),
+            '+' => tokenizer.tokens.push(Token{kind: TokenKind::Plus, line, column, end_column: tokenizer.column}),
+            '-' => tokenizer.tokens.push(Token{kind: TokenKind::Minus, line, column, end_column: tokenizer.column}),
+            '*' => tokenizer.tokens.push(Token{kind: TokenKind::Mul, line, column, end_column: tokenizer.column}),
+            '/' => tokenizer.tokens.push(Token{kind: TokenKind::Div, line, column, end_column: tokenizer.column}),
+            '%' => tokenizer.tokens.push(Token{kind: TokenKind::Mod, line, column, end_column: tokenizer.column}),
+            '(' => tokenizer.tokens.push(Token{kind: TokenKind::LParen, line, column, end_column: tokenizer.column}),
+            ')' => tokenizer.tokens.push(Token{kind: TokenKind::RParen, line, column, end_column: tokenizer.column}),
+            '[' => tokenizer.tokens.push(Token{kind: TokenKind::LBrancket, line, column, end_column: tokenizer.column}),
+            ']' => tokenizer.tokens.push(Token{kind: TokenKind::RBrancket, line, column, end_column: tokenizer.column}),
+            '.' => tokenizer.tokens.push(Token{kind: TokenKind::Dot, line, column, end_column: tokenizer.column}),
+            '\\' => tokenizer.tokens.push(Token{kind: TokenKind::BackSlash, line, column, end_column: tokenizer.column}),
             '{' => {
                 tokenizer.nesting_count += 1;
-                tokenizer.tokens.push(Token{kind: TokenKind::LBrace, line, column});
+                tokenizer.tokens.push(Token{kind: TokenKind::LBrace, line, column, end_column: tokenizer.column});
             }
             '}' => {
                 tokenizer.nesting_count -= 1;
-                tokenizer.tokens.push(Token{kind: TokenKind::RBrace, line, column});
+                tokenizer.tokens.push(Token{kind: TokenKind::RBrace, line, column, end_column: tokenizer.column});
                 if tokenizer.nesting_count == 0 {
-                    tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column});
+                    tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column, end_column: tokenizer.column});
                 }
             }
-            '=' => tokenizer.tokens.push(Token{kind: TokenKind::Equal, line, column}),
+            '=' => tokenizer.tokens.push(Token{kind: TokenKind::Equal, line, column, end_column: tokenizer.column}),
+            _ if !is_identifier_char(&c) => {
+                return Err(TokenizeError::new(&format!("unexpected character {:?}", c), line, column, tokenizer.column));
+            }
             _ => {
-                let (line, column) = tokenizer.store_position();
                 let value = get_identifier(&mut tokenizer);
-                tokenizer.column += value.len() - 1;
-                tokenizer.tokens.push(Token{kind: TokenKind::Identifier(value), line, column});
+                tokenizer.column += value.chars().count() - 1;
+                tokenizer.tokens.push(Token{kind: TokenKind::Identifier(value), line, column, end_column: tokenizer.column});
                 continue;
             }
         }
@@ -955,12 +1097,12 @@ pub fn tokenize(line: &String) -> Vec<Token> {
     match tokenizer.tokens.last() {
         Some(Token {kind, ..}) => {
             if kind != &TokenKind::Eof {
-                tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column});
+                tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column, end_column: tokenizer.column});
             }
         }
-        _ => tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column})
+        _ => tokenizer.tokens.push(Token{kind: TokenKind::Eof, line, column, end_column: tokenizer.column})
     }
-    tokenizer.tokens
+    Ok(tokenizer.tokens)
 }
 
 #[cfg(test)]
@@ -982,7 +1124,7 @@ mod tests {
             TokenKind::Number(Fraction::from(3)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"-1 + 2 * 3/4 % 3".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"-1 + 2 * 3/4 % 3".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -995,7 +1137,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"val mut x = 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val mut x = 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![
@@ -1007,7 +1149,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"val x: num = 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val x: num = 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1026,7 +1168,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"-1 + 2\n val x = 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"-1 + 2\n val x = 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1034,7 +1176,7 @@ mod tests {
     #[test]
     fn test_string() {
         let result = vec![TokenKind::String("Hello World!!".into()), TokenKind::Eof];
-        for (i, token) in tokenize(&"\"Hello World!!\"".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"\"Hello World!!\"".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1066,7 +1208,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"fun foo = (x:number, y: number): number {\n return x + y \n}".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"fun foo = (x:number, y: number): number {\n return x + y \n}".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1082,7 +1224,7 @@ mod tests {
             TokenKind::Identifier("foo".into()),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"(x, y) -> foo".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"(x, y) -> foo".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1090,7 +1232,7 @@ mod tests {
     #[test]
     fn test_decimal_point() {
         let result = vec![TokenKind::Number(Fraction::from(1.5)), TokenKind::Eof];
-        for (i, token) in tokenize(&"1.5".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1.5".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1107,7 +1249,7 @@ mod tests {
             TokenKind::RBrancket,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"[1, 2, 3]".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"[1, 2, 3]".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![
@@ -1118,7 +1260,7 @@ mod tests {
             TokenKind::RBrancket,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"[\"Hello\", \"World\"]".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"[\"Hello\", \"World\"]".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1133,7 +1275,7 @@ mod tests {
             TokenKind::Identifier("f2".into()),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"1 -> f1 -> f2".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1 -> f1 -> f2".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1156,7 +1298,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"val inc = \\|x: number| => x + 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val inc = \\|x: number| => x + 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1176,7 +1318,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"if x == 1 {\n return 1\n }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"if x == 1 {\n return 1\n }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1204,7 +1346,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"if x == 1 {\n return 1\n } else {\n return 0 \n}".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"if x == 1 {\n return 1\n } else {\n return 0 \n}".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1244,7 +1386,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"if x == 1 {\n return 1\n } else if x == 2 {\n return 2 \n} else {\n return 0 \n}".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"if x == 1 {\n return 1\n } else if x == 2 {\n return 2 \n} else {\n return 0 \n}".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1257,7 +1399,7 @@ mod tests {
             TokenKind::RParen,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"f1()".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"f1()".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1270,7 +1412,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"1 == 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1 == 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
 
@@ -1281,7 +1423,7 @@ mod tests {
             TokenKind::Eof
         ];
 
-        for (i, token) in tokenize(&"2 > 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"2 > 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
 
@@ -1291,7 +1433,7 @@ mod tests {
             TokenKind::Number(Fraction::from(3)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"3 >= 3".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"3 >= 3".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
 
@@ -1301,7 +1443,7 @@ mod tests {
             TokenKind::Number(Fraction::from(2)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"1 < 2".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1 < 2".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
 
@@ -1311,7 +1453,7 @@ mod tests {
             TokenKind::Number(Fraction::from(4)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"4 <= 4".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"4 <= 4".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1335,7 +1477,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"struct Point {\n x: number,\n y: number\n }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"struct Point {\n x: number,\n y: number\n }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![
@@ -1357,7 +1499,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"pub struct Point {\n pub x: number,\n y: number\n }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"pub struct Point {\n pub x: number,\n y: number\n }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1376,7 +1518,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"Point { x: 1, y: 2 }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"Point { x: 1, y: 2 }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1399,7 +1541,7 @@ mod tests {
             TokenKind::RBrace,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"val point = Point { x: 1, y: 2 }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val point = Point { x: 1, y: 2 }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1412,7 +1554,7 @@ mod tests {
             TokenKind::Identifier("x".into()),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"point.x".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"point.x".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1420,7 +1562,7 @@ mod tests {
     #[test]
     fn test_impl() {
         let result = vec![TokenKind::Impl, TokenKind::Identifier("Point".into()), TokenKind::LBrace, TokenKind::Eof, TokenKind::Function, TokenKind::Identifier("x".into()), TokenKind::Equal, TokenKind::LParen, TokenKind::Identifier("self".into()), TokenKind::Colon, TokenKind::Identifier("Point".into()), TokenKind::RParen, TokenKind::LBrace, TokenKind::Eof, TokenKind::Identifier("self".into()), TokenKind::Dot, TokenKind::Identifier("x".into()), TokenKind::Eof, TokenKind::RBrace, TokenKind::Eof, TokenKind::RBrace, TokenKind::Eof];
-        for (i, token) in tokenize(&"impl Point {\n fun x = (self: Point) {\n self.x\n }\n }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"impl Point {\n fun x = (self: Point) {\n self.x\n }\n }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1428,16 +1570,24 @@ mod tests {
     #[test]
     fn test_comment_block() {
         assert_eq!(
-            tokenize(&"```# Title\n## title1```".to_string())[0].kind,
+            tokenize(&"```# Title\n## title1```".to_string()).unwrap()[0].kind,
             TokenKind::Eof
         );
     }
 
+    #[test]
+    fn test_unterminated_comment_block_is_error() {
+        let input = "val x = 1\n  ``` unclosed\nval y = 2";
+        let error = tokenize(&input.to_string()).unwrap_err();
+        assert_eq!(error.message, "unterminated block comment");
+        assert_eq!((error.line, error.column, error.end_column), (2, 3, 6));
+    }
+
     #[test]
     fn test_commnet_line() {
 
         assert_eq!(
-            tokenize(&"// comment".to_string())[0].kind,
+            tokenize(&"// comment".to_string()).unwrap()[0].kind,
             TokenKind::Eof
         );
     }
@@ -1452,7 +1602,7 @@ mod tests {
             TokenKind::Number(Fraction::from(3)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"1\t+ 2\t+ 3".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1\t+ 2\t+ 3".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1460,7 +1610,7 @@ mod tests {
     #[test]
     fn test_identifier() {
         let result = vec![TokenKind::Identifier("x".into()), TokenKind::LBrancket, TokenKind::RBrancket, TokenKind::Eof];
-        for (i, token) in tokenize(&"x[]".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"x[]".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1480,7 +1630,7 @@ mod tests {
             TokenKind::RBrancket,
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"for x in [1, 2, 3]".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"for x in [1, 2, 3]".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1488,7 +1638,7 @@ mod tests {
     #[test]
     fn test_import() {
         let token_kinds = vec![TokenKind::Import, TokenKind::Identifier("foo1".into()), TokenKind::Comma, TokenKind::Identifier("foo2".into()), TokenKind::Comma, TokenKind::Identifier("foo3".into()), TokenKind::From, TokenKind::Identifier("Foo".into()), TokenKind::Eof];
-        for (i, token) in tokenize(&"import foo1,foo2, foo3 from Foo".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"import foo1,foo2, foo3 from Foo".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, token_kinds[i]);
         }
     }
@@ -1502,7 +1652,7 @@ mod tests {
             TokenKind::Number(Fraction::from(1)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"pub foo1 = 1".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"pub foo1 = 1".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1510,7 +1660,7 @@ mod tests {
     #[test]
     fn test_convert_number() {
         let result = vec![TokenKind::Number(Fraction::from(1.2)), TokenKind::Dot, TokenKind::Identifier("a".into()), TokenKind::Eof];
-        for (i, token) in tokenize(&"1.2.a".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"1.2.a".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1518,11 +1668,11 @@ mod tests {
     #[test]
     fn test_option() {
         let result = vec![TokenKind::Mutable, TokenKind::Identifier("foo".into()), TokenKind::Colon, TokenKind::Option, TokenKind::Lt, TokenKind::Identifier("number".into()), TokenKind::Gt, TokenKind::Equal, TokenKind::Some, TokenKind::LParen, TokenKind::Number(Fraction::from(1)), TokenKind::RParen, TokenKind::Eof];
-        for (i, token) in tokenize(&"val mut foo: Option<number> = Some(1)".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val mut foo: Option<number> = Some(1)".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![TokenKind::Mutable, TokenKind::Identifier("foo".into()), TokenKind::Colon, TokenKind::Option, TokenKind::Lt, TokenKind::Identifier("number".into()), TokenKind::Gt, TokenKind::Equal, TokenKind::None, TokenKind::Eof];
-        for (i, token) in tokenize(&"val mut foo: Option<number> = None".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val mut foo: Option<number> = None".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1530,11 +1680,11 @@ mod tests {
     #[test]
     fn test_result() {
         let result = vec![TokenKind::Mutable, TokenKind::Identifier("foo".into()), TokenKind::Colon, TokenKind::Result, TokenKind::Lt, TokenKind::Identifier("number".into()), TokenKind::Comma, TokenKind::Identifier("string".into()), TokenKind::Gt, TokenKind::Equal, TokenKind::Success, TokenKind::LParen, TokenKind::Number(Fraction::from(1)), TokenKind::RParen, TokenKind::Eof];
-        for (i, token) in tokenize(&"val mut foo: Result<number, string> = Suc(1)".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val mut foo: Result<number, string> = Suc(1)".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![TokenKind::Mutable, TokenKind::Identifier("foo".into()), TokenKind::Colon, TokenKind::Result, TokenKind::Lt, TokenKind::Identifier("number".into()), TokenKind::Comma, TokenKind::Identifier("string".into()), TokenKind::Gt, TokenKind::Equal, TokenKind::Failure, TokenKind::LParen, TokenKind::String("fail".into()), TokenKind::RParen, TokenKind::Eof];
-        for (i, token) in tokenize(&"val mut foo: Result<number, string> = Fail(\"fail\")".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"val mut foo: Result<number, string> = Fail(\"fail\")".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1542,11 +1692,11 @@ mod tests {
     #[test]
     fn test_true_false() {
         let result = vec![TokenKind::True, TokenKind::Eof];
-        for (i, token) in tokenize(&"true".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"true".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
         let result = vec![TokenKind::False, TokenKind::Eof];
-        for (i, token) in tokenize(&"false".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"false".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1584,7 +1734,7 @@ mod tests {
             TokenKind::Eof,
         ];
 
-        for (i, token) in tokenize(&"fun f(xs: List<number>) {\n for x in xs {\n print(x)\n }\n }".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"fun f(xs: List<number>) {\n for x in xs {\n print(x)\n }\n }".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
@@ -1596,8 +1746,104 @@ mod tests {
             TokenKind::Number(Fraction::from(3)),
             TokenKind::Eof
         ];
-        for (i, token) in tokenize(&"2 ** 3".to_string()).into_iter().enumerate() {
+        for (i, token) in tokenize(&"2 ** 3".to_string()).unwrap().into_iter().enumerate() {
+            assert_eq!(token.kind, result[i]);
+        }
+    }
+    #[test]
+    fn test_spread() {
+        let result = vec![
+            TokenKind::LBrancket,
+            TokenKind::Number(Fraction::from(0)),
+            TokenKind::Comma,
+            TokenKind::Spread,
+            TokenKind::Identifier("xs".into()),
+            TokenKind::RBrancket,
+            TokenKind::Identifier("xs".into()),
+            TokenKind::Dot,
+            TokenKind::Identifier("len".into()),
+            TokenKind::Eof
+        ];
+        for (i, token) in tokenize(&"[0, ...xs] xs.len".to_string()).unwrap().into_iter().enumerate() {
             assert_eq!(token.kind, result[i]);
         }
     }
+
+    fn positions(input: &str) -> Vec<(TokenKind, usize, usize, usize)> {
+        tokenize(&input.to_string()).unwrap().into_iter()
+            .map(|token| (token.kind, token.line, token.column, token.end_column))
+            .collect()
+    }
+
+    #[test]
+    fn test_positions_after_keyword_and_operator() {
+        let tokens = positions("return x >= 10");
+        assert_eq!(tokens[0], (TokenKind::Return, 1, 1, 7));
+        assert_eq!(tokens[1], (TokenKind::Identifier("x".into()), 1, 8, 9));
+        assert_eq!(tokens[2], (TokenKind::Gte, 1, 10, 12));
+        assert_eq!(tokens[3], (TokenKind::Number(Fraction::from(10)), 1, 13, 15));
+    }
+
+    #[test]
+    fn test_positions_after_string_with_escapes() {
+        let tokens = positions(r#""a\"b\\c\n" + x"#);
+        assert_eq!(tokens[0], (TokenKind::String("a\"b\\c\n".into()), 1, 1, 12));
+        assert_eq!(tokens[1], (TokenKind::Plus, 1, 13, 14));
+        assert_eq!(tokens[2], (TokenKind::Identifier("x".into()), 1, 15, 16));
+    }
+
+    #[test]
+    fn test_positions_count_characters_not_bytes() {
+        // 列は文字単位で数える(全角文字の表示幅はエラー表示側で考慮する)
+        let tokens = positions("\"名前\" + 値 + 1");
+        assert_eq!(tokens[0], (TokenKind::String("名前".into()), 1, 1, 5));
+        assert_eq!(tokens[1], (TokenKind::Plus, 1, 6, 7));
+        assert_eq!(tokens[2], (TokenKind::Identifier("値".into()), 1, 8, 9));
+        assert_eq!(tokens[4], (TokenKind::Number(Fraction::from(1)), 1, 12, 13));
+    }
+
+    #[test]
+    fn test_crlf_line_breaks() {
+        let tokens = positions("val x = 1\r\nval y = 2\rx + y\r\n");
+        assert_eq!(tokens[5], (TokenKind::Immutable, 2, 1, 4));
+        assert_eq!(tokens[6], (TokenKind::Identifier("y".into()), 2, 5, 6));
+        assert_eq!(tokens[8], (TokenKind::Number(Fraction::from(2)), 2, 9, 10));
+        assert_eq!(tokens[10], (TokenKind::Identifier("x".into()), 3, 1, 2));
+        assert_eq!(tokens[12], (TokenKind::Identifier("y".into()), 3, 5, 6));
+        // 文字列リテラルの中の\r\nは\nとして扱い、エスケープした\rだけが残る
+        assert_eq!(positions("\"a\r\nb\"")[0].0, TokenKind::String("a\nb".into()));
+        assert_eq!(positions(r#""a\rb""#)[0].0, TokenKind::String("a\rb".into()));
+    }
+
+    #[test]
+    fn test_positions_on_second_line() {
+        let tokens = positions("val x = 1\nval mut y = x");
+        assert_eq!(tokens[4].0, TokenKind::Eof);
+        assert_eq!(tokens[5], (TokenKind::Mutable, 2, 1, 8));
+        assert_eq!(tokens[6], (TokenKind::Identifier("y".into()), 2, 9, 10));
+        assert_eq!(tokens[8], (TokenKind::Identifier("x".into()), 2, 13, 14));
+    }
+
+    #[test]
+    fn test_unterminated_string_is_error() {
+        let input = "val x = 1\nval s = \"abc";
+        let error = tokenize(&input.to_string()).unwrap_err();
+        assert_eq!(error.message, "unterminated string literal");
+        assert_eq!((error.line, error.column, error.end_column), (2, 9, 10));
+        assert_eq!(
+            error.message_with_source(input),
+            "Tokenize Error: unterminated string literal\n --> line 2, column 9\n | val s = \"abc\n |         ^"
+        );
+    }
+
+    #[test]
+    fn test_illegal_character_is_error() {
+        let error = tokenize(&"val x = 1 @ 2".to_string()).unwrap_err();
+        assert_eq!(error.message, "unexpected character '@'");
+        assert_eq!((error.line, error.column, error.end_column), (1, 11, 12));
+        // 識別子の途中に現れた記号もその位置でエラーになる
+        let error = tokenize(&"val a$b = 1".to_string()).unwrap_err();
+        assert_eq!(error.message, "unexpected character '$'");
+        assert_eq!((error.line, error.column), (1, 6));
+    }
 }
@@ -1,10 +1,15 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::cell::Cell;
 use std::collections::HashMap;
+use indexmap::IndexMap;
 use fraction::Fraction;
 use crate::environment::{ValueType, MethodInfo};
 use crate::ast::ASTNode;
 use crate::environment::Env;
 
+// Lambdaは捕捉した環境(Env)を丸ごと持つため、他のバリアントより大きくなる
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Option(Option<Box<Value>>),
@@ -14,16 +19,18 @@ pub enum Value {
     Bool(bool),
     Void,
     List(Vec<Value>),
-    Dict(HashMap<String, Value>),
+    Dict(IndexMap<String, Value>),
     Function,
     Return(Box<Value>),
-    Break,
-    Continue,
+    // 対象のループのラベル(Noneなら最も内側のループ)
+    Break(Option<String>),
+    Continue(Option<String>),
     Struct {
         name: String,
         fields: HashMap<String, Value>,  // field_name: value
         methods: HashMap<String, MethodInfo>
     },
+    // 構造体名とフィールドの値がすべて等しいインスタンス同士が等しい
     StructInstance {
         name: String,
         fields: HashMap<String, Value>,
@@ -102,11 +109,18 @@ impl Value {
                     ValueType::List(Box::new(value_type))
                 }
             },
+            // キーは常に文字列なので値の型だけを持つ。値の型がそろっていなければAnyになる
             Value::Dict(dict) => {
-                let mut value_type = ValueType::Any;
-                for key in dict.keys() {
-                    value_type = dict.get(key).unwrap().value_type();
-                    break
+                let mut values = dict.values();
+                let mut value_type = match values.next() {
+                    Some(value) => value.value_type(),
+                    None => ValueType::Any,
+                };
+                for value in values {
+                    if value.value_type() != value_type {
+                        value_type = ValueType::Any;
+                        break;
+                    }
                 }
                 ValueType::Dict(Box::new(value_type))
             },
@@ -118,8 +132,8 @@ impl Value {
                     value.value_type()
                 }
             },
-            Value::Break => ValueType::Void,
-            Value::Continue => ValueType::Void,
+            Value::Break(_) => ValueType::Void,
+            Value::Continue(_) => ValueType::Void,
             Value::Lambda { .. } => ValueType::Lambda,
         }
     }
@@ -147,20 +161,213 @@ impl Value {
             _ => panic!("expected list"),
         }
     }
+    /// JSON文字列に変換する
+    /// 関数やラムダは環境ごと書き出してしまわないよう、値のどこに含まれていてもエラーにする
+    /// 有限小数で表せない分数はfraction_policyに従って書き出す
+    pub fn to_json(&self, fraction_policy: JsonFractionPolicy) -> Result<String, String> {
+        match self {
+            Value::Number(value) => json_number(value, fraction_policy),
+            Value::String(value) => Ok(json_string(value)),
+            Value::Bool(value) => Ok(value.to_string()),
+            Value::Void | Value::Option(None) => Ok("null".to_string()),
+            Value::Option(Some(value)) => value.to_json(fraction_policy),
+            Value::List(values) => {
+                let values = values.iter().map(|value| value.to_json(fraction_policy)).collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("[{}]", values.join(",")))
+            }
+            Value::Dict(dict) => {
+                let entries = dict.iter()
+                    .map(|(key, value)| Ok(format!("{}:{}", json_string(key), value.to_json(fraction_policy)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(format!("{{{}}}", entries.join(",")))
+            }
+            Value::StructInstance { fields, .. } => {
+                let mut field_names = fields.keys().collect::<Vec<_>>();
+                field_names.sort();
+                let entries = field_names.iter()
+                    .map(|name| Ok(format!("{}:{}", json_string(name), fields[*name].to_json(fraction_policy)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(format!("{{{}}}", entries.join(",")))
+            }
+            Value::Function | Value::Lambda { .. } => Err("json_stringify cannot serialize a function".to_string()),
+            _ => Err(format!("json_stringify cannot serialize {}", self)),
+        }
+    }
+    /// 集合の要素や辞書のキーとして使えるかを確認する
+    /// 数値・文字列・真偽値と、それらだけからなるリスト・辞書・構造体インスタンスなどがハッシュ可能
+    pub fn check_hashable(&self) -> Result<(), String> {
+        match self {
+            Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Void => Ok(()),
+            Value::Option(value) => match value {
+                Some(value) => value.check_hashable(),
+                None => Ok(()),
+            },
+            Value::Result(value) => match value {
+                Ok(value) | Err(value) => value.check_hashable(),
+            },
+            Value::List(values) => values.iter().try_for_each(|value| value.check_hashable()),
+            Value::Dict(dict) => dict.values().try_for_each(|value| value.check_hashable()),
+            Value::StructInstance { fields, .. } => fields.values().try_for_each(|value| value.check_hashable()),
+            Value::Function | Value::Lambda { .. } => Err("a function cannot be hashed".to_string()),
+            _ => Err(format!("{} cannot be hashed", self)),
+        }
+    }
+}
+
+// ハッシュ可能な値はcheck_hashableで確認してから使う
+// ハッシュできない値はバリアントの種類だけをハッシュするので、Eqとの整合性は保たれる
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Number(value) => {
+                // -0と0は等しいので同じハッシュにする
+                if value.numer() == Some(&0) {
+                    Fraction::from(0).hash(state);
+                } else {
+                    value.hash(state);
+                }
+            }
+            Value::String(value) => value.hash(state),
+            Value::Bool(value) => value.hash(state),
+            Value::Option(value) => value.hash(state),
+            Value::Result(value) => value.hash(state),
+            Value::List(values) => values.hash(state),
+            // 辞書と構造体のフィールドは順序に関係なく等しいので、キーの順に並べてからハッシュする
+            Value::Dict(dict) => {
+                let mut keys = dict.keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    key.hash(state);
+                    dict[key].hash(state);
+                }
+            }
+            Value::StructInstance { name, fields } => {
+                name.hash(state);
+                let mut field_names = fields.keys().collect::<Vec<_>>();
+                field_names.sort();
+                for field_name in field_names {
+                    field_name.hash(state);
+                    fields[field_name].hash(state);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// 1/3のように有限小数で表せない分数をJSONでどう書き出すか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonFractionPolicy {
+    /// "1/3"のような文字列にする
+    String,
+    /// 小数点以下16桁に丸めた小数にする
+    Decimal,
+}
+
+impl JsonFractionPolicy {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "string" => Ok(JsonFractionPolicy::String),
+            "decimal" => Ok(JsonFractionPolicy::Decimal),
+            _ => Err(format!("json_stringify fraction policy must be \"string\" or \"decimal\": {:?}", name)),
+        }
+    }
+}
+
+/// 分母が2と5だけの積なら有限小数になるので、その小数点以下の桁数を返す
+fn terminating_decimal_places(denom: u64) -> Option<usize> {
+    let (mut denom, mut twos, mut fives) = (denom, 0, 0);
+    while denom % 2 == 0 {
+        denom /= 2;
+        twos += 1;
+    }
+    while denom % 5 == 0 {
+        denom /= 5;
+        fives += 1;
+    }
+    (denom == 1).then_some(twos.max(fives))
+}
+
+fn json_number(value: &Fraction, fraction_policy: JsonFractionPolicy) -> Result<String, String> {
+    match (value.numer(), value.denom()) {
+        (Some(_), Some(1)) => Ok(format!("{}", value)),
+        (Some(_), Some(denom)) => match (terminating_decimal_places(*denom), fraction_policy) {
+            (Some(places), _) => Ok(format!("{:.*}", places, value)),
+            (None, JsonFractionPolicy::String) => Ok(json_string(&format!("{}", value))),
+            (None, JsonFractionPolicy::Decimal) => {
+                let decimal = format!("{:.16}", value);
+                Ok(decimal.trim_end_matches('0').trim_end_matches('.').to_string())
+            }
+        },
+        _ => Err(format!("json_stringify cannot serialize {}", value)),
+    }
+}
+
+
+/// 分子・分母の桁数がこの値を超えたら小数の近似値で表示する(0なら常に分数で表示する)
+pub const DEFAULT_FRACTION_DISPLAY_DIGITS: usize = 12;
+
+thread_local! {
+    static FRACTION_DISPLAY_DIGITS: Cell<usize> = const { Cell::new(DEFAULT_FRACTION_DISPLAY_DIGITS) };
+}
+
+pub fn set_fraction_display_digits(digits: usize) {
+    FRACTION_DISPLAY_DIGITS.with(|limit| limit.set(digits));
+}
+
+fn format_number(value: &Fraction) -> String {
+    let (numer, denom) = match (value.numer(), value.denom()) {
+        (Some(numer), Some(denom)) => (numer, denom),
+        // NaNや無限大はそのまま表示する
+        _ => return format!("{}", value),
+    };
+    // -0は0として表示する
+    if *numer == 0 {
+        return "0".to_string();
+    }
+    let limit = FRACTION_DISPLAY_DIGITS.with(|limit| limit.get());
+    if limit == 0 || (numer.to_string().len() <= limit && denom.to_string().len() <= limit) {
+        return format!("{}", value);
+    }
+    // 桁数が大きすぎる分数は近似値であることを示す記号を付けて小数で表示する
+    let approx = format!("{:.16}", value);
+    let approx = approx.trim_end_matches('0').trim_end_matches('.');
+    format!("≈{}", approx)
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Value::Number(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", format_number(value)),
             Value::String(s) => write!(f, "{}", s),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Void => write!(f, "Void"),
-            Value::Function => write!(f, "Function"),
-            Value::Lambda { .. } => write!(f, "Lambda"),
+            // ラムダは捕捉した環境を持つので中身は表示しない
+            Value::Function => write!(f, "<function>"),
+            Value::Lambda { .. } => write!(f, "<lambda>"),
             Value::Return(value) => write!(f, "{}", value),
-            Value::Break => write!(f, "Break"),
-            Value::Continue => write!(f, "Continue"),
+            Value::Break(_) => write!(f, "Break"),
+            Value::Continue(_) => write!(f, "Continue"),
             Value::Option(option) => match option {
                 Some(value) => write!(f, "{}", value),
                 None => write!(f, "None"),
@@ -180,15 +387,21 @@ impl fmt::Display for Value {
                 write!(f, "{}", result)
             }
             Value::StructInstance { name, fields } => {
+                // HashMapの順序は不定なのでフィールド名でソートして表示する
+                let mut field_names = fields.keys().collect::<Vec<_>>();
+                field_names.sort();
+                if field_names.is_empty() {
+                    return write!(f, "{} {{}}", name);
+                }
                 let mut result = String::new();
-                result.push_str(&format!("{} {{\n", name));
-                for (i, field) in fields.iter().enumerate() {
+                result.push_str(&format!("{} {{ ", name));
+                for (i, field_name) in field_names.iter().enumerate() {
                     if i > 0 {
-                        result.push_str(",\n");
+                        result.push_str(", ");
                     }
-                    result.push_str(&format!("    {}: {}", field.0, field.1));
+                    result.push_str(&format!("{}: {}", field_name, fields[*field_name]));
                 }
-                result.push_str("\n}");
+                result.push_str(" }");
                 write!(f, "{}", result)
             }
             Value::Struct { name, fields, .. } => {
@@ -215,6 +428,7 @@ impl fmt::Display for Value {
                 write!(f, "[{}]", result)
             },
             Value::Dict(dict) => {
+                // 挿入順に表示する(キー順にしたい場合はsort_keysで並べ替える)
                 let mut result = String::new();
                 for (i, (key, value)) in dict.iter().enumerate() {
                     if i > 0 {
@@ -227,3 +441,99 @@ impl fmt::Display for Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_list_as_set_member() {
+        let mut set = HashSet::new();
+        let list = Value::List(vec![Value::Number(Fraction::from(1)), Value::String("a".to_string())]);
+        assert!(list.check_hashable().is_ok());
+        set.insert(list.clone());
+        set.insert(Value::List(vec![Value::Number(Fraction::from(1)), Value::String("a".to_string())]));
+        set.insert(Value::List(vec![Value::Number(Fraction::from(2))]));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&list));
+    }
+
+    #[test]
+    fn test_struct_instance_as_dict_key() {
+        let point = |x: i32, y: i32| Value::StructInstance {
+            name: "Point".to_string(),
+            fields: HashMap::from([
+                ("x".to_string(), Value::Number(Fraction::from(x))),
+                ("y".to_string(), Value::Number(Fraction::from(y))),
+            ]),
+        };
+        let mut dict = HashMap::new();
+        dict.insert(point(1, 2), "a");
+        dict.insert(point(3, 4), "b");
+        assert_eq!(dict.get(&point(1, 2)), Some(&"a"));
+        assert_eq!(dict.get(&point(3, 4)), Some(&"b"));
+        assert_eq!(dict.get(&point(2, 1)), None);
+    }
+
+    #[test]
+    fn test_lambda_is_not_hashable() {
+        let lambda = Value::Lambda {
+            arguments: vec![],
+            body: Box::new(ASTNode::Literal { value: Value::Void, line: 0, column: 0 }),
+            env: Env::new(),
+        };
+        let list = Value::List(vec![Value::Number(Fraction::from(1)), lambda]);
+        assert_eq!(list.check_hashable().unwrap_err(), "a function cannot be hashed");
+    }
+
+    #[test]
+    fn test_small_fraction_display_is_exact() {
+        assert_eq!(format!("{}", Value::Number(Fraction::new(1u64, 3u64))), "1/3");
+        assert_eq!(format!("{}", Value::Number(Fraction::from(42))), "42");
+        assert_eq!(format!("{}", Value::Number(Fraction::new_neg(0u64, 1u64))), "0");
+    }
+
+    #[test]
+    fn test_large_fraction_display_falls_back_to_decimal() {
+        let value = Value::Number(Fraction::new(1u64, 3_000_000_000_000_000u64));
+        assert_eq!(format!("{}", value), "≈0.0000000000000003");
+        set_fraction_display_digits(0);
+        assert_eq!(format!("{}", value), "1/3000000000000000");
+        set_fraction_display_digits(DEFAULT_FRACTION_DISPLAY_DIGITS);
+    }
+
+    #[test]
+    fn test_dict_equality_ignores_insertion_order() {
+        let mut first = IndexMap::new();
+        first.insert("b".to_string(), Value::Number(Fraction::from(2)));
+        first.insert("a".to_string(), Value::Number(Fraction::from(1)));
+        first.insert("c".to_string(), Value::String("three".into()));
+        let mut second = IndexMap::new();
+        second.insert("c".to_string(), Value::String("three".into()));
+        second.insert("a".to_string(), Value::Number(Fraction::from(1)));
+        second.insert("b".to_string(), Value::Number(Fraction::from(2)));
+        let (first, second) = (Value::Dict(first), Value::Dict(second));
+        assert_eq!(first, second);
+        assert_eq!(format!("{}", first), "{:b: 2, a: 1, c: three:}");
+        assert_eq!(format!("{}", second), "{:c: three, a: 1, b: 2:}");
+    }
+
+    #[test]
+    fn test_to_json_escapes_strings() {
+        let value = Value::String("say \"hi\"\n".into());
+        assert_eq!(value.to_json(JsonFractionPolicy::Decimal), Ok(r#""say \"hi\"\n""#.to_string()));
+    }
+
+    #[test]
+    fn test_to_json_numbers_with_fraction_policy() {
+        let number = |numer: i64, denom: i64| Value::Number(Fraction::from(numer) / Fraction::from(denom));
+        for policy in [JsonFractionPolicy::String, JsonFractionPolicy::Decimal] {
+            assert_eq!(number(42, 1).to_json(policy), Ok("42".to_string()));
+            assert_eq!(number(-3, 8).to_json(policy), Ok("-0.375".to_string()));
+        }
+        assert_eq!(number(1, 3).to_json(JsonFractionPolicy::String), Ok(r#""1/3""#.to_string()));
+        assert_eq!(number(1, 3).to_json(JsonFractionPolicy::Decimal), Ok("0.3333333333333333".to_string()));
+        assert_eq!(number(-2, 3).to_json(JsonFractionPolicy::String), Ok(r#""-2/3""#.to_string()));
+    }
+}
@@ -1,22 +1,32 @@
 use crate::builtin::register_builtins;
 use crate::environment::Env;
-use crate::evals::evals;
+use crate::evals::eval_program;
 use crate::parsers::Parser;
 use crate::value::Value;
 use crate::tokenizer::tokenize;
 use std::cell::RefCell;
+use serde_json::json;
 use wasm_bindgen::prelude::*;
 
 thread_local! {
     pub(crate) static CONSOLE_OUTPUT: RefCell<String> = RefCell::new(String::new());
 }
 
+/// max_stepsを指定すると、ループの繰り返しと関数呼び出しの合計がその回数を超えた時点で評価を打ち切る
+/// (ブラウザで無限ループによりタブが固まるのを防ぐ。Noneなら無制限)
 #[wasm_bindgen]
-pub fn evaluate(input: &str) -> String {
+pub fn evaluate(input: &str, max_steps: Option<u32>) -> String {
     CONSOLE_OUTPUT.with(|output| output.borrow_mut().clear());
 
-    let tokens = tokenize(&input.to_string());
+    let tokens = match tokenize(&input.to_string()) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let error_message = e.message_with_source(input);
+            return format!("__ConsoleOutput__{}__Result__{}", error_message, Value::Void);
+        }
+    };
     let mut env = Env::new();
+    env.set_step_limit(max_steps.map(|max_steps| max_steps as usize));
     let builtins = register_builtins(&mut env);
     let mut parser = Parser::new(tokens, builtins.clone());
     let ast_nodes = parser.parse_lines();
@@ -24,14 +34,18 @@ pub fn evaluate(input: &str) -> String {
         let error_message = e.message_with_source(&input);
         return format!("__ConsoleOutput__{}__Result__{}", error_message, Value::Void);
     }
-    let result = evals(ast_nodes.unwrap(), &mut env);
-    if let Err(ref e) = result {
-        let error_message = e.message_with_source(&input);
-        return format!("__ConsoleOutput__{}__Result__{}", error_message, Value::Void);
-    }
+    let result = match eval_program(ast_nodes.unwrap(), &mut env) {
+        Ok(value) => value,
+        // exitは正常な終了として扱い、それまでの出力を返す
+        Err(e) if e.exit_code.is_some() => Value::Void,
+        Err(e) => {
+            let error_message = e.message_with_source(&input);
+            return format!("__ConsoleOutput__{}__Result__{}", error_message, Value::Void);
+        }
+    };
 
     let output = CONSOLE_OUTPUT.with(|output| output.borrow().clone());
-    let result_str = format!("{}", result.unwrap().last().unwrap_or(&Value::Void));
+    let result_str = format!("{}", result);
     format!(
         "__ConsoleOutput__{}__Result__{}",
         output.trim_end(),
@@ -39,13 +53,45 @@ pub fn evaluate(input: &str) -> String {
     )
 }
 
+/// 評価せずにトークン列とASTをJSONで返す(エディタのシンタックスハイライト用)
+/// 成功時は {"tokens": [...], "ast": [...]}、構文エラー時は {"tokens": [...], "error": {...}} になる
+/// トークンは種類と値と位置(end_columnはその列の直前まで)を持ち、ASTは{"type": "BinaryOp", ...}のようなノードのオブジェクトになる
+#[wasm_bindgen]
+pub fn parse(input: &str) -> String {
+    let tokens = match tokenize(&input.to_string()) {
+        Ok(tokens) => tokens,
+        Err(e) => return json!({
+            "tokens": [],
+            "error": {"message": e.message, "line": e.line, "column": e.column, "end_column": e.end_column},
+        }).to_string(),
+    };
+    let tokens_json = serde_json::to_value(&tokens).unwrap_or_default();
+
+    let mut env = Env::new();
+    let builtins = register_builtins(&mut env);
+    let mut parser = Parser::new(tokens, builtins);
+    match parser.parse_lines() {
+        Ok(ast_nodes) => json!({"tokens": tokens_json, "ast": ast_nodes}).to_string(),
+        Err(e) => json!({
+            "tokens": tokens_json,
+            "error": {"message": e.message, "line": e.line, "column": e.column, "end_column": e.end_column},
+        }).to_string(),
+    }
+}
+
+/// 分数を近似表示に切り替える桁数を設定する(0なら常に分数で表示する)
+#[wasm_bindgen]
+pub fn set_fraction_display_digits(digits: usize) {
+    crate::value::set_fraction_display_digits(digits);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_evaluate_basic_arithmetic() {
-        let result = evaluate("1 + 2");
+        let result = evaluate("1 + 2", None);
         assert_eq!(result, "__ConsoleOutput____Result__3");
     }
 
@@ -63,7 +109,7 @@ fun f1(x: number, y: number): number {
 
 |2, 0| -> f1
 "#;
-        let result = evaluate(input);
+        let result = evaluate(input, None);
         assert_eq!(result, "__ConsoleOutput____Result__6");
     }
 
@@ -96,7 +142,98 @@ fun f4(): number {
 || -> f3
 || -> f4
 "#;
-        let result = evaluate(input);
+        let result = evaluate(input, None);
         assert_eq!(result, "__ConsoleOutput____Result__11/4");
     }
+
+    #[test]
+    fn test_evaluate_aborts_infinite_loop_at_step_limit() {
+        let input = r#"
+val mut i = 0
+do {
+    i = i + 1
+} while (true)
+"#;
+        let result = evaluate(input, Some(100));
+        assert!(result.starts_with("__ConsoleOutput__Runtime Error: execution step limit exceeded"));
+    }
+
+    #[test]
+    fn test_evaluate_aborts_infinite_while_at_step_limit() {
+        let input = r#"
+while true {
+}
+"#;
+        let result = evaluate(input, Some(100));
+        assert!(result.starts_with("__ConsoleOutput__Runtime Error: execution step limit exceeded"));
+    }
+
+    #[test]
+    fn test_evaluate_exit_ends_normally() {
+        let input = r#"
+val x = 1
+exit(3)
+x + 1
+"#;
+        let result = evaluate(input, None);
+        assert_eq!(result, "__ConsoleOutput____Result__Void");
+    }
+
+    #[test]
+    fn test_evaluate_within_step_limit() {
+        let input = r#"
+val mut total = 0
+for i in range(10) {
+    total = total + i
+}
+total
+"#;
+        let result = evaluate(input, Some(100));
+        assert_eq!(result, "__ConsoleOutput____Result__45");
+    }
+
+    #[test]
+    fn test_parse_returns_tokens_with_positions() {
+        let result: serde_json::Value = serde_json::from_str(&parse("val x = 1\nx + 2")).unwrap();
+        let tokens = result["tokens"].as_array().unwrap();
+        assert!(tokens.contains(&json!({"kind": "Identifier", "value": "x", "line": 1, "column": 5, "end_column": 6})));
+        assert!(tokens.contains(&json!({"kind": "Number", "value": "1", "line": 1, "column": 9, "end_column": 10})));
+        assert!(tokens.contains(&json!({"kind": "Plus", "line": 2, "column": 3, "end_column": 4})));
+        assert!(result.get("error").is_none());
+    }
+
+    #[test]
+    fn test_parse_returns_structured_ast() {
+        let result: serde_json::Value = serde_json::from_str(&parse("val x: number = 1/3\nx + 2\n\"a\" + \"b\"")).unwrap();
+        let ast = result["ast"].as_array().unwrap();
+        assert_eq!(ast.len(), 3);
+        assert_eq!(ast[0]["type"], "Assign");
+        assert_eq!(ast[0]["name"], "x");
+        assert_eq!(ast[0]["variable_type"], "Immutable");
+        assert_eq!(ast[0]["value_type"], "number");
+        assert_eq!((ast[0]["line"].as_u64(), ast[0]["column"].as_u64()), (Some(1), Some(1)));
+        assert_eq!(ast[0]["value"]["op"], json!({"kind": "Div"}));
+        assert_eq!(ast[0]["value"]["right"]["value"], 3);
+        assert_eq!(ast[1]["type"], "BinaryOp");
+        assert_eq!(ast[1]["op"], json!({"kind": "Plus"}));
+        assert_eq!(ast[1]["left"]["type"], "Variable");
+        assert_eq!(ast[1]["left"]["name"], "x");
+        assert_eq!((&ast[1]["right"]["type"], &ast[1]["right"]["value"]), (&json!("Literal"), &json!(2)));
+        assert_eq!(ast[2]["left"]["value"], "a");
+    }
+
+    #[test]
+    fn test_parse_returns_error() {
+        let result: serde_json::Value = serde_json::from_str(&parse("undefined_name = 1")).unwrap();
+        assert_eq!(result["error"], json!({"message": "undefined variable: \"undefined_name\"", "line": 1, "column": 1, "end_column": 15}));
+        assert!(result.get("ast").is_none());
+    }
+
+    #[test]
+    fn test_tokenize_error_is_reported() {
+        let result = evaluate("val s = \"abc", None);
+        assert!(result.starts_with("__ConsoleOutput__Tokenize Error: unterminated string literal\n --> line 1, column 9"));
+        let result: serde_json::Value = serde_json::from_str(&parse("1 @ 2")).unwrap();
+        assert_eq!(result, json!({"tokens": [], "error": {"message": "unexpected character '@'", "line": 1, "column": 3, "end_column": 4}}));
+    }
 }
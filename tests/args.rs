@@ -0,0 +1,28 @@
+use std::process::Command;
+
+/// sagファイルを書き出して `sag run <file> -- <args>` し、標準出力を返す
+fn run_sag_with_args(name: &str, source: &str, args: &[&str]) -> String {
+    let path = std::env::temp_dir().join(format!("sag_args_{}_{}.sag", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sag"))
+        .arg("run")
+        .arg(&path)
+        .arg("--")
+        .args(args)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_args_returns_arguments_after_separator() {
+    let stdout = run_sag_with_args("two", "print(args().len() == 2)\nprint(args())\n", &["first", "--verbose"]);
+    assert!(stdout.contains("\ntrue\n[first, --verbose]\n"), "{}", stdout);
+}
+
+#[test]
+fn test_args_is_empty_without_arguments() {
+    let stdout = run_sag_with_args("none", "print(args().len())\n", &[]);
+    assert!(stdout.contains("\n0\n"), "{}", stdout);
+}
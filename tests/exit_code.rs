@@ -0,0 +1,45 @@
+use std::process::Command;
+
+/// sagファイルを書き出して `sag run` し、プロセスの終了コードを返す
+fn run_sag(name: &str, source: &str) -> Option<i32> {
+    let path = std::env::temp_dir().join(format!("sag_exit_code_{}_{}.sag", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_sag"))
+        .arg("run")
+        .arg(&path)
+        .output()
+        .unwrap()
+        .status;
+    let _ = std::fs::remove_file(&path);
+    status.code()
+}
+
+#[test]
+fn test_exit_builtin_sets_exit_code() {
+    assert_eq!(run_sag("exit", "print(1)\nexit(2)\nprint(3)\n"), Some(2));
+}
+
+#[test]
+fn test_main_return_value_is_exit_code() {
+    assert_eq!(run_sag("main", "fun main(): number {\n    return 3\n}\n"), Some(3));
+}
+
+#[test]
+fn test_exit_code_without_main_is_zero() {
+    assert_eq!(run_sag("no_main", "val x = 42\nx\n"), Some(0));
+}
+
+#[test]
+fn test_runtime_error_exit_code_is_one() {
+    assert_eq!(run_sag("error", "val x = [1]\nx[5]\n"), Some(1));
+}
+
+#[test]
+fn test_exit_inside_lambda_passed_to_builtin() {
+    assert_eq!(run_sag("lambda_builtin", "map([1], \\|x| => exit(2))\nprint(3)\n"), Some(2));
+}
+
+#[test]
+fn test_exit_inside_lambda_passed_to_method() {
+    assert_eq!(run_sag("lambda_method", "[1].map(\\|x| => exit(2))\nprint(3)\n"), Some(2));
+}
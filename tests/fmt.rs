@@ -0,0 +1,39 @@
+use std::process::Command;
+
+/// sagファイルを書き出して `sag fmt` し、プロセスの終了コードと整形後の内容を返す
+fn fmt_sag(name: &str, source: &str) -> (Option<i32>, String) {
+    let path = std::env::temp_dir().join(format!("sag_fmt_{}_{}.sag", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_sag"))
+        .arg("fmt")
+        .arg(&path)
+        .output()
+        .unwrap()
+        .status;
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    (status.code(), formatted)
+}
+
+#[test]
+fn test_fmt_succeeds_on_valid_source() {
+    assert_eq!(fmt_sag("valid", "val x=1+2\n"), (Some(0), "val x = 1 + 2\n".to_string()));
+}
+
+#[test]
+fn test_fmt_fails_on_broken_source() {
+    assert_eq!(fmt_sag("unterminated", "val x = \"abc\n").0, Some(1));
+    assert_eq!(fmt_sag("parse_error", "val = 1\n").0, Some(1));
+}
+
+#[test]
+fn test_fmt_fails_on_source_with_comments() {
+    let source = "// note\nval x = 1\n";
+    assert_eq!(fmt_sag("comment", source), (Some(1), source.to_string()));
+}
+
+#[test]
+fn test_fmt_formats_string_with_escaped_quote_and_slashes() {
+    let source = "val s = \"a\\\"//\"\n";
+    assert_eq!(fmt_sag("escaped", source), (Some(0), source.to_string()));
+}
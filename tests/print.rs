@@ -0,0 +1,26 @@
+use std::process::Command;
+
+/// sagファイルを書き出して `sag run` し、標準出力を返す
+fn run_sag(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(format!("sag_print_{}_{}.sag", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sag"))
+        .arg("run")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_print_with_custom_separator() {
+    let stdout = run_sag("sep", "print(1, \"two\", [3], sep: \" | \")\nprint(\"a\", \"b\", sep: \"\")\n");
+    assert!(stdout.contains("\n1 | two | [3]\nab\n"), "{}", stdout);
+}
+
+#[test]
+fn test_print_with_empty_terminator() {
+    let stdout = run_sag("end", "print(\"loading\", end: \"\")\nprint(\"...\", end: \"\")\nprint(\"done\")\n");
+    assert!(stdout.contains("\nloading...done\n"), "{}", stdout);
+}